@@ -1,19 +1,19 @@
 pub(crate) mod thread_func;
 
 use ::jni::objects::GlobalRef;
-use ::jni::JavaVM;
 use robusta_jni::bridge;
 use std::sync::OnceLock;
 
-static APP_CONTEXT: OnceLock<(JavaVM, GlobalRef)> = OnceLock::new();
+static APP_CLASS: OnceLock<GlobalRef> = OnceLock::new();
 
 #[bridge]
 mod jni {
-    use crate::APP_CONTEXT;
+    use crate::APP_CLASS;
     use android_logger::Config;
     use jni::objects::{GlobalRef, JObject, JValue};
     use log::info;
     use robusta_jni::convert::{IntoJavaValue, Signature, TryFromJavaValue, TryIntoJavaValue};
+    use robusta_jni::exception::CallResult;
     use robusta_jni::jni::errors::Result as JniResult;
     use robusta_jni::jni::objects::AutoLocal;
     use robusta_jni::jni::JNIEnv;
@@ -34,10 +34,7 @@ mod jni {
             let java_class = env
                 .find_class("com/example/robustaandroidexample/RobustaAndroidExample")
                 .unwrap();
-            let _ = APP_CONTEXT.set((
-                env.get_java_vm().unwrap(),
-                env.new_global_ref(java_class).unwrap(),
-            ));
+            let _ = APP_CLASS.set(env.new_global_ref(java_class).unwrap());
 
             let app_files_dir = RobustaAndroidExample::getAppFilesDir(env, context).unwrap();
             info!("App files dir: {}", app_files_dir);
@@ -58,14 +55,14 @@ mod jni {
             assert!(met_call.is_ok());
 
             let thread_handler = thread::Builder::new()
-                .name("test_thread_fail".to_string())
-                .spawn(move || crate::thread_func::thread_test_fail());
+                .name("test_thread_no_class".to_string())
+                .spawn(crate::thread_func::thread_test_no_class);
             let join_res = thread_handler.unwrap().join().unwrap();
-            assert!(join_res.is_err());
+            assert!(join_res.is_ok());
 
             let thread_handler = thread::Builder::new()
                 .name("test_thread_good".to_string())
-                .spawn(move || crate::thread_func::thread_test_good());
+                .spawn(crate::thread_func::thread_test_good);
             let join_res = thread_handler.unwrap().join().unwrap();
             assert!(join_res.is_ok());
 
@@ -78,12 +75,12 @@ mod jni {
         ) -> JniResult<String> {
         }
 
-        pub extern "java" fn threadTestNoClass(env: &JNIEnv, s: String) -> JniResult<i32> {}
+        pub extern "java" fn threadTestNoClass(env: &JNIEnv, s: String) -> CallResult<i32> {}
         pub extern "java" fn threadTestWithClass(
             env: &JNIEnv,
             class_ref: &GlobalRef,
             s: String,
-        ) -> JniResult<i32> {
+        ) -> CallResult<i32> {
         }
     }
 }
@@ -0,0 +1,572 @@
+//! Generation of Rust bindings of compiled Java classes.
+//!
+//! [`BindingGenerator`] reads classes from a [`Classpath`] and emits a `#[bridge]` module with a `#[package]` struct per class.
+//! The public constructors, static and instance methods of each class are declared as `extern "java"` methods,
+//! and its public instance fields of primitive and `String` types as `#[field]`s.
+//!
+//! It's meant to be called from a build script:
+//! ```no_run
+//! use robusta_build::bindings::BindingGenerator;
+//! use robusta_build::classfile::Classpath;
+//!
+//! fn main() {
+//!     let out_dir = std::env::var("OUT_DIR").unwrap();
+//!
+//!     BindingGenerator::new(Classpath::parse("libs/sdk.jar"))
+//!         .class("com.example.sdk.Client")
+//!         .package("com.example.sdk.model")
+//!         .generate(format!("{}/sdk.rs", out_dir))
+//!         .unwrap();
+//! }
+//! ```
+//! and the bindings are included in the crate with `include!(concat!(env!("OUT_DIR"), "/sdk.rs"));`.
+//!
+//! Java types are mapped onto the conversion types of robusta_jni where possible: primitives, `String`,
+//! wrapper classes (as `Option`s of primitives), primitive arrays (as boxed slices) and the bound classes themselves.
+//! Other objects are passed as `JObject`s, with their Java type given by `#[input_type]` and `#[output_type]`.
+//!
+//! Overloads of a method get a numbered suffix (e.g. `add` and `add_1`) and keep their Java name with `#[java_name]`.
+//! Inherited methods aren't declared: bind the superclass as well to call them.
+//!
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter, Write};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use inflector::cases::camelcase::to_camel_case;
+use inflector::cases::snakecase::to_snake_case;
+
+use crate::classfile::{split_method_descriptor, ClassFile, Classpath, ClasspathError, Member};
+
+/// Names used by the generated code, which can't be used for bound classes.
+const RESERVED_NAMES: &[&str] = &[
+    "AutoLocal",
+    "Box",
+    "Field",
+    "IntoJavaValue",
+    "JNIEnv",
+    "JObject",
+    "JniResult",
+    "Option",
+    "Self",
+    "Signature",
+    "String",
+    "TryFromJavaValue",
+    "TryIntoJavaValue",
+];
+
+const RUST_KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate",
+    "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl",
+    "in", "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+    "return", "self", "static", "struct", "super", "trait", "true", "try", "type", "typeof",
+    "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+/// Error raised when bindings can't be generated.
+#[derive(Debug)]
+pub enum BindingsError {
+    /// A class can't be read from the classpath.
+    Classpath(ClasspathError),
+    /// A class isn't in the classpath.
+    ClassNotFound(String),
+    /// Two bound classes have the same name, or a class has the name of a type used by the bindings.
+    NameClash(String),
+    /// The bindings can't be written.
+    Io(PathBuf, io::Error),
+}
+
+impl Display for BindingsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            BindingsError::Classpath(e) => write!(f, "{}", e),
+            BindingsError::ClassNotFound(class) => {
+                write!(f, "can't find class `{}` in the classpath", class)
+            }
+            BindingsError::NameClash(name) => {
+                write!(f, "can't bind several classes named `{}`", name)
+            }
+            BindingsError::Io(path, e) => write!(f, "can't write `{}`: {}", path.display(), e),
+        }
+    }
+}
+
+impl Error for BindingsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            BindingsError::Classpath(e) => Some(e),
+            BindingsError::Io(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ClasspathError> for BindingsError {
+    fn from(e: ClasspathError) -> Self {
+        BindingsError::Classpath(e)
+    }
+}
+
+/// Generator of the Rust bindings of Java classes.
+pub struct BindingGenerator {
+    classpath: Classpath,
+    classes: Vec<String>,
+    packages: Vec<String>,
+    module: String,
+}
+
+impl BindingGenerator {
+    /// Create a generator reading classes from `classpath`, emitting a module named `jni`.
+    pub fn new(classpath: Classpath) -> Self {
+        BindingGenerator {
+            classpath,
+            classes: Vec::new(),
+            packages: Vec::new(),
+            module: "jni".into(),
+        }
+    }
+
+    /// Bind the class `class`, either in binary (e.g. `java.util.Map$Entry`) or classpath format (e.g. `java/util/Map$Entry`).
+    pub fn class(mut self, class: &str) -> Self {
+        self.classes.push(class.replace('.', "/"));
+        self
+    }
+
+    /// Bind every public class of the package `package` (e.g. `java.util`), without subpackages.
+    pub fn package(mut self, package: &str) -> Self {
+        self.packages.push(package.replace('.', "/"));
+        self
+    }
+
+    /// Name of the generated module.
+    pub fn module(mut self, module: &str) -> Self {
+        self.module = module.into();
+        self
+    }
+
+    fn load_classes(&self) -> Result<Vec<ClassFile>, BindingsError> {
+        let mut classes = BTreeMap::new();
+
+        for class in &self.classes {
+            let class_file = self
+                .classpath
+                .load(class)?
+                .ok_or_else(|| BindingsError::ClassNotFound(class.replace('/', ".")))?;
+            classes.insert(class.clone(), class_file);
+        }
+
+        for package in &self.packages {
+            for class in self.classpath.package_classes(package)? {
+                // Anonymous and local classes can't be named
+                let anonymous = class
+                    .split('$')
+                    .skip(1)
+                    .any(|s| s.starts_with(|c: char| c.is_ascii_digit()));
+                if anonymous || class.ends_with("package-info") || classes.contains_key(&class) {
+                    continue;
+                }
+
+                if let Some(class_file) = self.classpath.load(&class)? {
+                    if class_file.is_public() {
+                        classes.insert(class, class_file);
+                    }
+                }
+            }
+        }
+
+        Ok(classes.into_values().collect())
+    }
+
+    /// Generate the bindings.
+    pub fn bindings(&self) -> Result<String, BindingsError> {
+        let classes = self.load_classes()?;
+
+        let mut bound = BTreeMap::new();
+        for class in &classes {
+            let name = simple_name(&class.name);
+            if RESERVED_NAMES.contains(&name) || bound.values().any(|n| *n == name) {
+                return Err(BindingsError::NameClash(name.to_string()));
+            }
+            bound.insert(class.name.as_str(), name);
+        }
+
+        let module = Module {
+            bound: &bound,
+            imports: Default::default(),
+        };
+        Ok(module.render(&self.module, &classes))
+    }
+
+    /// Generate the bindings in the file `path`.
+    pub fn generate(&self, path: impl Into<PathBuf>) -> Result<(), BindingsError> {
+        let path = path.into();
+        let bindings = self.bindings()?;
+
+        fs::write(&path, bindings).map_err(|e| BindingsError::Io(path, e))
+    }
+}
+
+/// Name of the class `classpath_path` in its package, without enclosing classes (e.g. `Entry` for `java/util/Map$Entry`).
+fn simple_name(classpath_path: &str) -> &str {
+    classpath_path
+        .rsplit(['/', '$'])
+        .next()
+        .unwrap_or(classpath_path)
+}
+
+/// Rust identifier for the Java member `name`.
+fn rust_name(name: &str) -> String {
+    let name = to_snake_case(name);
+    if RUST_KEYWORDS.contains(&name.as_str()) {
+        format!("{}_", name)
+    } else {
+        name
+    }
+}
+
+/// Rust type of a parameter or return value.
+struct RustType {
+    ty: String,
+    // Java type to force on `JObject`s
+    java_type: Option<String>,
+}
+
+struct Module<'a> {
+    // struct name of every bound class, by classpath path
+    bound: &'a BTreeMap<&'a str, &'a str>,
+    imports: BTreeSet<&'static str>,
+}
+
+impl Module<'_> {
+    fn rust_type(&mut self, descriptor: &str) -> RustType {
+        let known = match descriptor {
+            "Z" => "bool",
+            "B" => "i8",
+            "C" => "char",
+            "S" => "i16",
+            "I" => "i32",
+            "J" => "i64",
+            "F" => "f32",
+            "D" => "f64",
+            "V" => "()",
+            "Ljava/lang/String;" => "String",
+            "Ljava/lang/Boolean;" => "Option<bool>",
+            "Ljava/lang/Byte;" => "Option<i8>",
+            "Ljava/lang/Character;" => "Option<char>",
+            "Ljava/lang/Short;" => "Option<i16>",
+            "Ljava/lang/Integer;" => "Option<i32>",
+            "Ljava/lang/Long;" => "Option<i64>",
+            "Ljava/lang/Float;" => "Option<f32>",
+            "Ljava/lang/Double;" => "Option<f64>",
+            "[Z" => "Box<[bool]>",
+            "[B" => "Box<[i8]>",
+            "[C" => "Box<[u16]>",
+            "[S" => "Box<[i16]>",
+            "[I" => "Box<[i32]>",
+            "[J" => "Box<[i64]>",
+            "[F" => "Box<[f32]>",
+            "[D" => "Box<[f64]>",
+            _ => {
+                let class = descriptor
+                    .strip_prefix('L')
+                    .and_then(|d| d.strip_suffix(';'));
+                if let Some(name) = class.and_then(|c| self.bound.get(c)) {
+                    return RustType {
+                        ty: format!("{}<'env, 'borrow>", name),
+                        java_type: None,
+                    };
+                }
+
+                self.imports.insert("JObject");
+                return RustType {
+                    ty: "JObject<'env>".into(),
+                    java_type: Some(descriptor.into()),
+                };
+            }
+        };
+
+        RustType {
+            ty: known.into(),
+            java_type: None,
+        }
+    }
+
+    fn render(mut self, module: &str, classes: &[ClassFile]) -> String {
+        let items: Vec<String> = classes.iter().map(|c| self.render_class(c)).collect();
+
+        let mut out = String::from("// Generated by robusta-build, do not edit.\n\n");
+        writeln!(out, "#[::robusta_jni::bridge]").unwrap();
+        writeln!(out, "#[allow(clippy::too_many_arguments)]").unwrap();
+        writeln!(out, "pub mod {} {{", module).unwrap();
+
+        let field = if self.imports.contains("Field") {
+            "Field, "
+        } else {
+            ""
+        };
+        writeln!(
+            out,
+            "    use robusta_jni::convert::{{{}IntoJavaValue, Signature, TryFromJavaValue, TryIntoJavaValue}};",
+            field
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "    use robusta_jni::jni::errors::Result as JniResult;"
+        )
+        .unwrap();
+        if self.imports.contains("JObject") {
+            writeln!(
+                out,
+                "    use robusta_jni::jni::objects::{{AutoLocal, JObject}};"
+            )
+            .unwrap();
+        } else {
+            writeln!(out, "    use robusta_jni::jni::objects::AutoLocal;").unwrap();
+        }
+        writeln!(out, "    use robusta_jni::jni::JNIEnv;").unwrap();
+
+        for item in items {
+            out.push('\n');
+            out.push_str(&item);
+        }
+
+        out.push_str("}\n");
+        out
+    }
+
+    fn render_class(&mut self, class: &ClassFile) -> String {
+        let name = self.bound[class.name.as_str()];
+        let (package, path) = match class.name.rfind('/') {
+            Some(i) => (class.name[..i].replace('/', "."), &class.name[i + 1..]),
+            None => (String::new(), class.name.as_str()),
+        };
+
+        let mut out = String::new();
+        writeln!(
+            out,
+            "    #[derive(Signature, TryIntoJavaValue, IntoJavaValue, TryFromJavaValue)]"
+        )
+        .unwrap();
+        writeln!(out, "    #[package({})]", package).unwrap();
+        if let Some(i) = path.rfind('$') {
+            writeln!(out, "    #[outer_class({})]", path[..i].replace('$', ".")).unwrap();
+        }
+        writeln!(out, "    pub struct {}<'env: 'borrow, 'borrow> {{", name).unwrap();
+        writeln!(out, "        #[instance]").unwrap();
+        writeln!(out, "        pub raw: AutoLocal<'env, 'borrow>,").unwrap();
+
+        for field in class
+            .fields
+            .iter()
+            .filter(|f| f.is_public() && !f.is_static() && !f.is_synthetic())
+        {
+            let ty = self.rust_type(&field.descriptor);
+            // Fields are looked up by their Rust name, and only simple types can be read through `Field`
+            if ty.java_type.is_some()
+                || ty.ty.contains('<')
+                || field.name == "raw"
+                || field.name.contains('$')
+                || RUST_KEYWORDS.contains(&field.name.as_str())
+            {
+                continue;
+            }
+
+            self.imports.insert("Field");
+            writeln!(out, "        #[field]").unwrap();
+            if to_snake_case(&field.name) != field.name {
+                writeln!(out, "        #[allow(non_snake_case)]").unwrap();
+            }
+            writeln!(
+                out,
+                "        pub {}: Field<'env, 'borrow, {}>,",
+                field.name, ty.ty
+            )
+            .unwrap();
+        }
+        writeln!(out, "    }}\n").unwrap();
+
+        writeln!(
+            out,
+            "    impl<'env: 'borrow, 'borrow> {}<'env, 'borrow> {{",
+            name
+        )
+        .unwrap();
+
+        let mut method_names = BTreeSet::new();
+        let mut methods = Vec::new();
+        let constructors = !class.is_abstract();
+        for method in class.methods.iter().filter(|m| {
+            m.is_public()
+                && !m.is_synthetic()
+                && m.name != "<clinit>"
+                && (m.name != "<init>" || constructors)
+        }) {
+            if let Some(method) = self.render_method(method, &mut method_names) {
+                methods.push(method);
+            }
+        }
+        out.push_str(&methods.join("\n"));
+
+        writeln!(out, "    }}").unwrap();
+        out
+    }
+
+    fn render_method(&mut self, method: &Member, names: &mut BTreeSet<String>) -> Option<String> {
+        let (parameters, return_type) = split_method_descriptor(&method.descriptor)?;
+        let is_constructor = method.name == "<init>";
+
+        let base_name = if is_constructor {
+            "new".to_string()
+        } else {
+            rust_name(&method.name)
+        };
+        let name = (0..)
+            .map(|i| {
+                if i == 0 {
+                    base_name.clone()
+                } else {
+                    format!("{}_{}", base_name, i)
+                }
+            })
+            .find(|n| !names.contains(n))
+            .unwrap();
+        names.insert(name.clone());
+
+        let mut out = String::new();
+        if is_constructor {
+            writeln!(out, "        #[constructor]").unwrap();
+        } else if name != to_snake_case(&method.name) || to_camel_case(&name) != method.name {
+            writeln!(out, "        #[java_name = \"{}\"]", method.name).unwrap();
+        }
+
+        let output = if is_constructor {
+            "Self".to_string()
+        } else {
+            let output = self.rust_type(return_type);
+            if let Some(java_type) = &output.java_type {
+                writeln!(out, "        #[output_type(\"{}\")]", java_type).unwrap();
+            }
+            output.ty
+        };
+
+        let mut inputs = Vec::new();
+        if !method.is_static() && !is_constructor {
+            inputs.push("&self".to_string());
+        }
+        inputs.push("env: &'borrow JNIEnv<'env>".to_string());
+        for (i, parameter) in parameters.iter().enumerate() {
+            let ty = self.rust_type(parameter);
+            inputs.push(match ty.java_type {
+                Some(java_type) => format!("#[input_type(\"{}\")] arg{}: {}", java_type, i, ty.ty),
+                None => format!("arg{}: {}", i, ty.ty),
+            });
+        }
+
+        writeln!(
+            out,
+            "        pub extern \"java\" fn {}({}) -> JniResult<{}> {{}}",
+            name,
+            inputs.join(", "),
+            output
+        )
+        .unwrap();
+        Some(out)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::*;
+
+    fn generator() -> BindingGenerator {
+        BindingGenerator::new(
+            Classpath::new().entry(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")),
+        )
+    }
+
+    #[test]
+    fn classes_are_bound() {
+        let bindings = generator().package("com.example").bindings().unwrap();
+
+        assert_eq!(
+            bindings,
+            r#"// Generated by robusta-build, do not edit.
+
+#[::robusta_jni::bridge]
+#[allow(clippy::too_many_arguments)]
+pub mod jni {
+    use robusta_jni::convert::{Field, IntoJavaValue, Signature, TryFromJavaValue, TryIntoJavaValue};
+    use robusta_jni::jni::errors::Result as JniResult;
+    use robusta_jni::jni::objects::{AutoLocal, JObject};
+    use robusta_jni::jni::JNIEnv;
+
+    #[derive(Signature, TryIntoJavaValue, IntoJavaValue, TryFromJavaValue)]
+    #[package(com.example)]
+    pub struct Sample<'env: 'borrow, 'borrow> {
+        #[instance]
+        pub raw: AutoLocal<'env, 'borrow>,
+        #[field]
+        pub count: Field<'env, 'borrow, i32>,
+    }
+
+    impl<'env: 'borrow, 'borrow> Sample<'env, 'borrow> {
+        #[constructor]
+        pub extern "java" fn new(env: &'borrow JNIEnv<'env>) -> JniResult<Self> {}
+
+        #[constructor]
+        pub extern "java" fn new_1(env: &'borrow JNIEnv<'env>, arg0: i32) -> JniResult<Self> {}
+
+        pub extern "java" fn describe(env: &'borrow JNIEnv<'env>, arg0: String, arg1: i64) -> JniResult<String> {}
+
+        pub extern "java" fn add(&self, env: &'borrow JNIEnv<'env>, arg0: i32) -> JniResult<i32> {}
+
+        #[java_name = "add"]
+        pub extern "java" fn add_1(&self, env: &'borrow JNIEnv<'env>, arg0: i32, arg1: i32) -> JniResult<i32> {}
+
+        #[java_name = "getURL"]
+        pub extern "java" fn get_url(&self, env: &'borrow JNIEnv<'env>) -> JniResult<String> {}
+
+        #[java_name = "type"]
+        pub extern "java" fn type_(&self, env: &'borrow JNIEnv<'env>, arg0: Option<i32>, arg1: Box<[i8]>) -> JniResult<()> {}
+
+        #[output_type("Ljava/util/List;")]
+        pub extern "java" fn names(&self, env: &'borrow JNIEnv<'env>, #[input_type("Ljava/util/List;")] arg0: JObject<'env>) -> JniResult<JObject<'env>> {}
+
+        pub extern "java" fn nested(&self, env: &'borrow JNIEnv<'env>) -> JniResult<Nested<'env, 'borrow>> {}
+
+        pub extern "java" fn run(&self, env: &'borrow JNIEnv<'env>) -> JniResult<()> {}
+    }
+
+    #[derive(Signature, TryIntoJavaValue, IntoJavaValue, TryFromJavaValue)]
+    #[package(com.example)]
+    #[outer_class(Sample)]
+    pub struct Nested<'env: 'borrow, 'borrow> {
+        #[instance]
+        pub raw: AutoLocal<'env, 'borrow>,
+        #[field]
+        pub flag: Field<'env, 'borrow, bool>,
+    }
+
+    impl<'env: 'borrow, 'borrow> Nested<'env, 'borrow> {
+        #[constructor]
+        pub extern "java" fn new(env: &'borrow JNIEnv<'env>) -> JniResult<Self> {}
+    }
+}
+"#
+        );
+    }
+
+    #[test]
+    fn missing_classes_are_reported() {
+        match generator().class("com.example.Missing").bindings() {
+            Err(BindingsError::ClassNotFound(class)) => assert_eq!(class, "com.example.Missing"),
+            _ => panic!("missing class not reported"),
+        }
+    }
+}
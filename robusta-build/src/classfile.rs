@@ -0,0 +1,523 @@
+//! Reading compiled Java classes.
+//!
+//! [`ClassFile::parse`] reads the parts of a `.class` file needed to bind a class: its name, superclass, interfaces,
+//! and the name, descriptor and access flags of its fields and methods, as described in the
+//! [JVM specification](https://docs.oracle.com/javase/specs/jvms/se17/html/jvms-4.html).
+//! Method bodies and other attributes are skipped.
+//!
+//! [`Classpath`] looks classes up in directories and JARs, like the `-classpath` option of `java`, and in JDK modules (JMOD files).
+//!
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// Access flag of public classes and members.
+pub const ACC_PUBLIC: u16 = 0x0001;
+/// Access flag of static members.
+pub const ACC_STATIC: u16 = 0x0008;
+/// Access flag of bridge methods, generated by the compiler.
+pub const ACC_BRIDGE: u16 = 0x0040;
+/// Access flag of interfaces.
+pub const ACC_INTERFACE: u16 = 0x0200;
+/// Access flag of abstract classes and methods.
+pub const ACC_ABSTRACT: u16 = 0x0400;
+/// Access flag of members not present in the source code.
+pub const ACC_SYNTHETIC: u16 = 0x1000;
+
+/// Error raised when a class file is malformed.
+#[derive(Debug)]
+pub enum ClassFileError {
+    /// The file doesn't start with `0xCAFEBABE`.
+    BadMagic,
+    /// The file ends before the end of the class.
+    Truncated,
+    /// A constant pool entry has an unknown tag.
+    UnknownConstant(u8),
+    /// A constant pool index doesn't point to an entry of the expected type.
+    BadConstantIndex(u16),
+}
+
+impl Display for ClassFileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ClassFileError::BadMagic => write!(f, "not a class file"),
+            ClassFileError::Truncated => write!(f, "truncated class file"),
+            ClassFileError::UnknownConstant(tag) => {
+                write!(f, "unknown constant pool tag {}", tag)
+            }
+            ClassFileError::BadConstantIndex(index) => {
+                write!(f, "invalid constant pool index {}", index)
+            }
+        }
+    }
+}
+
+impl Error for ClassFileError {}
+
+/// Field or method of a class.
+#[derive(Clone, Debug)]
+pub struct Member {
+    /// Access flags (e.g. [`ACC_PUBLIC`]).
+    pub access_flags: u16,
+    /// Name, `<init>` for constructors.
+    pub name: String,
+    /// Type descriptor (e.g. `(ILjava/lang/String;)V`).
+    pub descriptor: String,
+}
+
+impl Member {
+    /// Whether the member is public.
+    pub fn is_public(&self) -> bool {
+        self.access_flags & ACC_PUBLIC != 0
+    }
+
+    /// Whether the member is static.
+    pub fn is_static(&self) -> bool {
+        self.access_flags & ACC_STATIC != 0
+    }
+
+    /// Whether the member was generated by the compiler.
+    pub fn is_synthetic(&self) -> bool {
+        self.access_flags & (ACC_SYNTHETIC | ACC_BRIDGE) != 0
+    }
+}
+
+/// Compiled Java class.
+#[derive(Clone, Debug)]
+pub struct ClassFile {
+    /// Access flags (e.g. [`ACC_INTERFACE`]).
+    pub access_flags: u16,
+    /// Class in classpath format (e.g. `java/util/ArrayList`).
+    pub name: String,
+    /// Superclass in classpath format, `None` for `java.lang.Object`.
+    pub super_class: Option<String>,
+    /// Implemented interfaces, in classpath format.
+    pub interfaces: Vec<String>,
+    pub fields: Vec<Member>,
+    pub methods: Vec<Member>,
+}
+
+enum Constant {
+    Utf8(String),
+    Class(u16),
+    Other,
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], ClassFileError> {
+        if self.bytes.len() < n {
+            return Err(ClassFileError::Truncated);
+        }
+
+        let (taken, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u1(&mut self) -> Result<u8, ClassFileError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u2(&mut self) -> Result<u16, ClassFileError> {
+        let b = self.take(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u4(&mut self) -> Result<u32, ClassFileError> {
+        let b = self.take(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+}
+
+/// Decode the "modified UTF-8" of class files, where supplementary characters are encoded as surrogate pairs.
+fn decode_modified_utf8(bytes: &[u8]) -> String {
+    let mut units = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let b = bytes[i] as u16;
+        let continuation = |j: usize| bytes.get(i + j).map_or(0, |&c| c as u16 & 0x3f);
+
+        if b & 0x80 == 0 {
+            units.push(b);
+            i += 1;
+        } else if b & 0xe0 == 0xc0 {
+            units.push((b & 0x1f) << 6 | continuation(1));
+            i += 2;
+        } else {
+            units.push((b & 0x0f) << 12 | continuation(1) << 6 | continuation(2));
+            i += 3;
+        }
+    }
+
+    String::from_utf16_lossy(&units)
+}
+
+impl ClassFile {
+    /// Parse the content of a `.class` file.
+    pub fn parse(bytes: &[u8]) -> Result<ClassFile, ClassFileError> {
+        let mut r = Reader { bytes };
+
+        if r.u4()? != 0xCAFE_BABE {
+            return Err(ClassFileError::BadMagic);
+        }
+        r.take(4)?; // minor and major versions
+
+        let count = r.u2()?;
+        let mut pool = Vec::with_capacity(count as usize);
+        pool.push(Constant::Other); // entries are numbered from 1
+        while pool.len() < count as usize {
+            let tag = r.u1()?;
+            let constant = match tag {
+                1 => {
+                    let length = r.u2()? as usize;
+                    Constant::Utf8(decode_modified_utf8(r.take(length)?))
+                }
+                7 => Constant::Class(r.u2()?),
+                8 | 16 | 19 | 20 => {
+                    r.take(2)?;
+                    Constant::Other
+                }
+                15 => {
+                    r.take(3)?;
+                    Constant::Other
+                }
+                3 | 4 | 9 | 10 | 11 | 12 | 17 | 18 => {
+                    r.take(4)?;
+                    Constant::Other
+                }
+                5 | 6 => {
+                    r.take(8)?;
+                    // Longs and doubles take two entries
+                    pool.push(Constant::Other);
+                    Constant::Other
+                }
+                tag => return Err(ClassFileError::UnknownConstant(tag)),
+            };
+            pool.push(constant);
+        }
+
+        let utf8 = |index: u16| match pool.get(index as usize) {
+            Some(Constant::Utf8(s)) => Ok(s.clone()),
+            _ => Err(ClassFileError::BadConstantIndex(index)),
+        };
+        let class = |index: u16| match pool.get(index as usize) {
+            Some(Constant::Class(name)) => utf8(*name),
+            _ => Err(ClassFileError::BadConstantIndex(index)),
+        };
+
+        let access_flags = r.u2()?;
+        let name = class(r.u2()?)?;
+        let super_class = match r.u2()? {
+            0 => None,
+            index => Some(class(index)?),
+        };
+        let interfaces = (0..r.u2()?)
+            .map(|_| class(r.u2()?))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let members = |r: &mut Reader| {
+            (0..r.u2()?)
+                .map(|_| {
+                    let member = Member {
+                        access_flags: r.u2()?,
+                        name: utf8(r.u2()?)?,
+                        descriptor: utf8(r.u2()?)?,
+                    };
+
+                    for _ in 0..r.u2()? {
+                        r.u2()?;
+                        let length = r.u4()? as usize;
+                        r.take(length)?;
+                    }
+
+                    Ok(member)
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let fields = members(&mut r)?;
+        let methods = members(&mut r)?;
+
+        Ok(ClassFile {
+            access_flags,
+            name,
+            super_class,
+            interfaces,
+            fields,
+            methods,
+        })
+    }
+
+    /// Whether the class is public.
+    pub fn is_public(&self) -> bool {
+        self.access_flags & ACC_PUBLIC != 0
+    }
+
+    /// Whether the class is an interface.
+    pub fn is_interface(&self) -> bool {
+        self.access_flags & ACC_INTERFACE != 0
+    }
+
+    /// Whether the class is abstract (interfaces included).
+    pub fn is_abstract(&self) -> bool {
+        self.access_flags & ACC_ABSTRACT != 0
+    }
+}
+
+/// Split a method descriptor (e.g. `(I[Ljava/lang/String;)V`) into the descriptors of its parameters and return type.
+pub fn split_method_descriptor(descriptor: &str) -> Option<(Vec<&str>, &str)> {
+    let parameters_end = descriptor.find(')')?;
+    let mut parameters_descriptor = descriptor.strip_prefix('(')?.get(..parameters_end - 1)?;
+    let return_type = &descriptor[parameters_end + 1..];
+
+    let mut parameters = Vec::new();
+    while !parameters_descriptor.is_empty() {
+        let dimensions =
+            parameters_descriptor.len() - parameters_descriptor.trim_start_matches('[').len();
+        let length = match parameters_descriptor[dimensions..].chars().next()? {
+            'L' => parameters_descriptor.find(';')? + 1,
+            _ => dimensions + 1,
+        };
+
+        parameters.push(&parameters_descriptor[..length]);
+        parameters_descriptor = &parameters_descriptor[length..];
+    }
+
+    Some((parameters, return_type))
+}
+
+/// Error raised when a class can't be read from the classpath.
+#[derive(Debug)]
+pub enum ClasspathError {
+    /// A directory, JAR or class file can't be read.
+    Io(PathBuf, io::Error),
+    /// A class file is malformed.
+    ClassFile(String, ClassFileError),
+}
+
+impl Display for ClasspathError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ClasspathError::Io(path, e) => write!(f, "can't read `{}`: {}", path.display(), e),
+            ClasspathError::ClassFile(class, e) => write!(f, "can't read class `{}`: {}", class, e),
+        }
+    }
+}
+
+impl Error for ClasspathError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ClasspathError::Io(_, e) => Some(e),
+            ClasspathError::ClassFile(_, e) => Some(e),
+        }
+    }
+}
+
+/// Directories and JARs to look classes up in.
+#[derive(Clone, Debug, Default)]
+pub struct Classpath {
+    entries: Vec<PathBuf>,
+}
+
+/// Directory of the classes in an archive (JAR or JMOD), `None` if `path` isn't an archive.
+fn archive_classes_dir(path: &Path) -> Option<&'static str> {
+    match path.extension()?.to_str()? {
+        "jar" | "zip" => Some(""),
+        // JDK modules, e.g. `$JAVA_HOME/jmods/java.base.jmod`
+        "jmod" => Some("classes/"),
+        _ => None,
+    }
+}
+
+fn open_jar(path: &Path) -> Result<zip::ZipArchive<File>, ClasspathError> {
+    let io_error = |e| ClasspathError::Io(path.to_path_buf(), e);
+    let file = File::open(path).map_err(io_error)?;
+
+    zip::ZipArchive::new(file).map_err(|e| io_error(io::Error::new(io::ErrorKind::InvalidData, e)))
+}
+
+impl Classpath {
+    /// Create an empty classpath.
+    pub fn new() -> Self {
+        Classpath::default()
+    }
+
+    /// Parse a classpath in the format of the `CLASSPATH` environment variable, i.e. a list of directories and JARs
+    /// separated by `:` (`;` on Windows).
+    pub fn parse(classpath: &str) -> Self {
+        Classpath {
+            entries: std::env::split_paths(classpath)
+                .filter(|p| !p.as_os_str().is_empty())
+                .collect(),
+        }
+    }
+
+    /// Add a directory, JAR or JMOD file.
+    pub fn entry(mut self, path: impl Into<PathBuf>) -> Self {
+        self.entries.push(path.into());
+        self
+    }
+
+    /// Directories and JARs of the classpath.
+    pub fn entries(&self) -> &[PathBuf] {
+        &self.entries
+    }
+
+    /// Read the class `classpath_path` (e.g. `java/util/ArrayList`) from the first entry containing it, if any.
+    pub fn load(&self, classpath_path: &str) -> Result<Option<ClassFile>, ClasspathError> {
+        let file_name = format!("{}.class", classpath_path);
+
+        for entry in &self.entries {
+            let bytes = if let Some(classes_dir) = archive_classes_dir(entry) {
+                let mut jar = open_jar(entry)?;
+                let mut file = match jar.by_name(&format!("{}{}", classes_dir, file_name)) {
+                    Ok(file) => file,
+                    Err(zip::result::ZipError::FileNotFound) => continue,
+                    Err(e) => {
+                        return Err(ClasspathError::Io(
+                            entry.clone(),
+                            io::Error::new(io::ErrorKind::InvalidData, e),
+                        ))
+                    }
+                };
+
+                let mut bytes = Vec::new();
+                file.read_to_end(&mut bytes)
+                    .map_err(|e| ClasspathError::Io(entry.clone(), e))?;
+                bytes
+            } else {
+                let path = entry.join(&file_name);
+                match fs::read(&path) {
+                    Ok(bytes) => bytes,
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                    Err(e) => return Err(ClasspathError::Io(path, e)),
+                }
+            };
+
+            return ClassFile::parse(&bytes)
+                .map(Some)
+                .map_err(|e| ClasspathError::ClassFile(classpath_path.to_string(), e));
+        }
+
+        Ok(None)
+    }
+
+    /// Every class of the package `package` (e.g. `java/util`), without subpackages, in classpath format.
+    pub fn package_classes(&self, package: &str) -> Result<Vec<String>, ClasspathError> {
+        let prefix = if package.is_empty() {
+            String::new()
+        } else {
+            format!("{}/", package)
+        };
+        let mut classes = Vec::new();
+
+        for entry in &self.entries {
+            if let Some(classes_dir) = archive_classes_dir(entry) {
+                let jar = open_jar(entry)?;
+                classes.extend(
+                    jar.file_names()
+                        .filter_map(|name| {
+                            name.strip_prefix(classes_dir)?
+                                .strip_prefix(&prefix)?
+                                .strip_suffix(".class")
+                        })
+                        .filter(|name| !name.contains('/'))
+                        .map(|name| format!("{}{}", prefix, name)),
+                );
+            } else {
+                let directory = entry.join(package);
+                let entries = match fs::read_dir(&directory) {
+                    Ok(entries) => entries,
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                    Err(e) => return Err(ClasspathError::Io(directory, e)),
+                };
+
+                for file in entries {
+                    let file = file.map_err(|e| ClasspathError::Io(directory.clone(), e))?;
+                    if let Some(name) = file
+                        .file_name()
+                        .to_str()
+                        .and_then(|n| n.strip_suffix(".class"))
+                    {
+                        classes.push(format!("{}{}", prefix, name));
+                    }
+                }
+            }
+        }
+
+        classes.sort();
+        classes.dedup();
+        Ok(classes)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn fixtures() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+    }
+
+    #[test]
+    fn method_descriptors_are_split() {
+        assert_eq!(
+            split_method_descriptor("(I[[JLjava/lang/String;[Ljava/util/List;Z)V"),
+            Some((
+                vec!["I", "[[J", "Ljava/lang/String;", "[Ljava/util/List;", "Z"],
+                "V"
+            ))
+        );
+        assert_eq!(
+            split_method_descriptor("()Ljava/lang/Object;"),
+            Some((vec![], "Ljava/lang/Object;"))
+        );
+        assert_eq!(split_method_descriptor("I"), None);
+    }
+
+    #[test]
+    fn class_files_are_parsed() {
+        let classpath = Classpath::new().entry(fixtures());
+        let class = classpath.load("com/example/Sample").unwrap().unwrap();
+
+        assert_eq!(class.name, "com/example/Sample");
+        assert_eq!(class.super_class.as_deref(), Some("java/lang/Object"));
+        assert_eq!(class.interfaces, vec!["java/lang/Runnable"]);
+        assert!(class.is_public() && !class.is_abstract());
+
+        let count = class.fields.iter().find(|f| f.name == "count").unwrap();
+        assert_eq!(count.descriptor, "I");
+        assert!(count.is_public() && !count.is_static());
+
+        let describe = class.methods.iter().find(|m| m.name == "describe").unwrap();
+        assert_eq!(
+            describe.descriptor,
+            "(Ljava/lang/String;J)Ljava/lang/String;"
+        );
+        assert!(describe.is_static());
+
+        assert!(classpath.load("com/example/Missing").unwrap().is_none());
+    }
+
+    #[test]
+    fn classes_are_read_from_jars() {
+        let classpath = Classpath::parse(fixtures().join("sample.jar").to_str().unwrap());
+        let class = classpath
+            .load("com/example/Sample$Nested")
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(class.name, "com/example/Sample$Nested");
+        assert_eq!(
+            classpath.package_classes("com/example").unwrap(),
+            vec!["com/example/Sample", "com/example/Sample$Nested"]
+        );
+    }
+}
@@ -0,0 +1,942 @@
+//! Generation of the Java classes of `#[bridge]` modules.
+//!
+//! [`JavaGenerator`] parses Rust sources, looks for `#[bridge]` modules and emits a Java class for every `#[package]` struct
+//! with `extern "jni"` methods. The class declares these methods as `native` methods and loads the library in a `static` block.
+//! Nested classes (`#[outer_class(...)]`) are emitted in the source of their outer class.
+//! Classes of `#[derive(NativeHandle)]` structs also get the field holding the handle, a private constructor,
+//! and the `dispose` method if any (implementing `AutoCloseable` if it's named `close`).
+//!
+//! It's meant to be called from a build script:
+//! ```no_run
+//! use robusta_build::java::JavaGenerator;
+//!
+//! fn main() {
+//!     println!("cargo:rerun-if-changed=src");
+//!
+//!     JavaGenerator::new("robusta_example")
+//!         .source("src")
+//!         .generate("java")
+//!         .unwrap();
+//! }
+//! ```
+//!
+//! The Java types of parameters and return values are inferred from their Rust types, for the types of the
+//! [conversion table](https://github.com/giovanniberti/robusta#conversion-table) and the `#[package]` types of the bridge module.
+//! Methods using other types are reported as errors.
+//!
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter, Write};
+use std::fs;
+use std::io;
+use std::iter;
+use std::path::{Path, PathBuf};
+
+use inflector::cases::camelcase::to_camel_case;
+use quote::ToTokens;
+use syn::punctuated::Punctuated;
+use syn::{
+    Attribute, Expr, ExprLit, FnArg, GenericArgument, ImplItem, ImplItemFn, Item, ItemImpl, Lit,
+    LitStr, Pat, PatType, PathArguments, ReturnType, Token, Type, TypePath, TypeReference,
+};
+
+use crate::types::{java_type, JavaType};
+
+/// Error raised when the Java classes can't be generated.
+#[derive(Debug)]
+pub enum GenerateError {
+    /// A source file can't be read, or a generated class can't be written.
+    Io(PathBuf, io::Error),
+    /// A source file can't be parsed.
+    Parse(PathBuf, syn::Error),
+    /// The Java type of a parameter or return value can't be inferred.
+    UnsupportedType {
+        /// Class declaring the method, in classpath format (e.g. `com/example/robusta/HelloWorld`).
+        class: String,
+        /// Java name of the method.
+        method: String,
+        /// Rust type.
+        ty: String,
+    },
+}
+
+impl Display for GenerateError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            GenerateError::Io(path, e) => write!(f, "can't access `{}`: {}", path.display(), e),
+            GenerateError::Parse(path, e) => write!(f, "can't parse `{}`: {}", path.display(), e),
+            GenerateError::UnsupportedType { class, method, ty } => write!(
+                f,
+                "can't infer the Java type of `{}` in method `{}` of class `{}`",
+                ty, method, class
+            ),
+        }
+    }
+}
+
+impl Error for GenerateError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            GenerateError::Io(_, e) => Some(e),
+            GenerateError::Parse(_, e) => Some(e),
+            GenerateError::UnsupportedType { .. } => None,
+        }
+    }
+}
+
+/// Generator of the Java classes of the `#[bridge]` modules of a crate.
+pub struct JavaGenerator {
+    library: String,
+    sources: Vec<PathBuf>,
+}
+
+impl JavaGenerator {
+    /// Create a generator for classes loading the native library `library`, as passed to `System.loadLibrary`
+    /// (e.g. `robusta_example` for `librobusta_example.so`).
+    pub fn new(library: impl Into<String>) -> Self {
+        JavaGenerator {
+            library: library.into(),
+            sources: Vec::new(),
+        }
+    }
+
+    /// Add a Rust source file, or every `.rs` file of a directory and its subdirectories.
+    pub fn source(mut self, path: impl Into<PathBuf>) -> Self {
+        self.sources.push(path.into());
+        self
+    }
+
+    /// Generate the Java classes.
+    pub fn classes(&self) -> Result<Vec<JavaClass>, GenerateError> {
+        let mut files = Vec::new();
+        for source in &self.sources {
+            collect_sources(source, &mut files)?;
+        }
+
+        let mut classes = Classes::default();
+        for path in files {
+            let content =
+                fs::read_to_string(&path).map_err(|e| GenerateError::Io(path.clone(), e))?;
+            let file =
+                syn::parse_file(&content).map_err(|e| GenerateError::Parse(path.clone(), e))?;
+
+            classes.add_items(&file.items)?;
+        }
+
+        Ok(classes.into_java(&self.library))
+    }
+
+    /// Generate the Java classes in `out_dir`, in a directory per package, and return the paths of the written files.
+    pub fn generate(&self, out_dir: impl AsRef<Path>) -> Result<Vec<PathBuf>, GenerateError> {
+        self.classes()?
+            .into_iter()
+            .map(|class| {
+                let path = out_dir.as_ref().join(class.file_path());
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent).map_err(|e| GenerateError::Io(parent.into(), e))?;
+                }
+
+                fs::write(&path, class.source()).map_err(|e| GenerateError::Io(path.clone(), e))?;
+                Ok(path)
+            })
+            .collect()
+    }
+}
+
+/// Generated Java class.
+pub struct JavaClass {
+    classpath_path: String,
+    source: String,
+}
+
+impl JavaClass {
+    /// Class in classpath format (e.g. `com/example/robusta/HelloWorld`).
+    pub fn classpath_path(&self) -> &str {
+        &self.classpath_path
+    }
+
+    /// Java source of the class.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Path of the source file, relative to the source root (e.g. `com/example/robusta/HelloWorld.java`).
+    pub fn file_path(&self) -> PathBuf {
+        PathBuf::from(format!("{}.java", self.classpath_path))
+    }
+}
+
+fn collect_sources(path: &Path, files: &mut Vec<PathBuf>) -> Result<(), GenerateError> {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+
+    let mut entries = fs::read_dir(path)
+        .and_then(|entries| {
+            entries
+                .map(|e| e.map(|e| e.path()))
+                .collect::<io::Result<Vec<_>>>()
+        })
+        .map_err(|e| GenerateError::Io(path.to_path_buf(), e))?;
+    entries.sort();
+
+    for entry in entries {
+        if entry.is_dir() || entry.extension().is_some_and(|e| e == "rs") {
+            collect_sources(&entry, files)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Java class of a `#[package]` type.
+#[derive(Clone)]
+struct ClassName {
+    package: String,
+    // enclosing classes of a nested class, outermost first
+    outer: Vec<String>,
+    name: String,
+}
+
+impl ClassName {
+    fn from_attrs(attrs: &[Attribute], name: String) -> Option<Self> {
+        let package = attrs
+            .iter()
+            .find(|a| a.path().is_ident("package"))?
+            .meta
+            .require_list()
+            .ok()?
+            .tokens
+            .to_string()
+            .replace(' ', "");
+        let outer = attrs
+            .iter()
+            .find(|a| a.path().is_ident("outer_class"))
+            .and_then(|a| a.meta.require_list().ok())
+            .map(|l| {
+                l.tokens
+                    .to_string()
+                    .replace(' ', "")
+                    .split('.')
+                    .map(ToString::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Some(ClassName {
+            package,
+            outer,
+            name,
+        })
+    }
+
+    fn path(&self) -> impl Iterator<Item = &String> {
+        self.outer.iter().chain(iter::once(&self.name))
+    }
+
+    fn classpath_path(&self) -> String {
+        let class = self.path().cloned().collect::<Vec<_>>().join("$");
+        if self.package.is_empty() {
+            class
+        } else {
+            format!("{}/{}", self.package.replace('.', "/"), class)
+        }
+    }
+
+    /// Name of the class in Java sources of the package `package`.
+    fn source_name(&self, package: &str) -> String {
+        let class = self.path().cloned().collect::<Vec<_>>().join(".");
+        if self.package.is_empty() || self.package == package {
+            class
+        } else {
+            format!("{}.{}", self.package, class)
+        }
+    }
+}
+
+struct NativeMethod {
+    name: String,
+    is_static: bool,
+    // Java type and name of each parameter
+    parameters: Vec<(String, String)>,
+    return_type: String,
+}
+
+/// Parameters of a `#[derive(NativeHandle)]` struct.
+#[derive(Clone)]
+struct NativeHandle {
+    field: String,
+    dispose: Option<String>,
+}
+
+impl NativeHandle {
+    fn from_attrs(attrs: &[Attribute]) -> Self {
+        let mut handle = NativeHandle {
+            field: "nativeHandle".into(),
+            dispose: None,
+        };
+
+        if let Some(attr) = attrs.iter().find(|a| a.path().is_ident("native_handle")) {
+            // Invalid options are reported by the derive macro
+            let _ = attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("field") {
+                    handle.field = meta.value()?.parse::<LitStr>()?.value();
+                } else if meta.path.is_ident("dispose") {
+                    handle.dispose = Some(meta.value()?.parse::<LitStr>()?.value());
+                }
+                Ok(())
+            });
+        }
+
+        handle
+    }
+}
+
+#[derive(Default)]
+struct ClassNode {
+    methods: Vec<NativeMethod>,
+    handle: Option<NativeHandle>,
+    nested: BTreeMap<String, ClassNode>,
+}
+
+/// Classes found so far, by package and name of their top-level class.
+#[derive(Default)]
+struct Classes {
+    top_level: BTreeMap<(String, String), ClassNode>,
+}
+
+fn has_attribute(attrs: &[Attribute], name: &str) -> bool {
+    attrs
+        .iter()
+        .any(|a| a.path().segments.last().is_some_and(|s| s.ident == name))
+}
+
+/// Every `#[package]` struct (with `true`) and enum (with `false`) of `items` and their submodules.
+fn collect_java_types(items: &[Item], types: &mut BTreeMap<String, (ClassName, bool)>) {
+    for item in items {
+        let (attrs, ident, is_struct) = match item {
+            Item::Struct(s) => (&s.attrs, &s.ident, true),
+            Item::Enum(e) => (&e.attrs, &e.ident, false),
+            Item::Mod(m) => {
+                if let Some((_, content)) = &m.content {
+                    collect_java_types(content, types);
+                }
+                continue;
+            }
+            _ => continue,
+        };
+
+        if let Some(class) = ClassName::from_attrs(attrs, ident.to_string()) {
+            types.insert(ident.to_string(), (class, is_struct));
+        }
+    }
+}
+
+/// Every `#[derive(NativeHandle)]` struct of `items` and their submodules.
+fn collect_native_handles(items: &[Item], handles: &mut BTreeMap<String, NativeHandle>) {
+    for item in items {
+        match item {
+            Item::Struct(s) if derives(&s.attrs, "NativeHandle") => {
+                handles.insert(s.ident.to_string(), NativeHandle::from_attrs(&s.attrs));
+            }
+            Item::Mod(m) => {
+                if let Some((_, content)) = &m.content {
+                    collect_native_handles(content, handles);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn derives(attrs: &[Attribute], name: &str) -> bool {
+    attrs
+        .iter()
+        .filter(|a| a.path().is_ident("derive"))
+        .any(|a| {
+            a.parse_args_with(Punctuated::<syn::Path, Token![,]>::parse_terminated)
+                .is_ok_and(|paths| {
+                    paths
+                        .iter()
+                        .any(|p| p.segments.last().is_some_and(|s| s.ident == name))
+                })
+        })
+}
+
+fn collect_impls<'a>(items: &'a [Item], impls: &mut Vec<&'a ItemImpl>) {
+    for item in items {
+        match item {
+            Item::Impl(i) if i.trait_.is_none() => impls.push(i),
+            Item::Mod(m) => {
+                if let Some((_, content)) = &m.content {
+                    collect_impls(content, impls);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Classes {
+    fn add_items(&mut self, items: &[Item]) -> Result<(), GenerateError> {
+        for item in items {
+            if let Item::Mod(m) = item {
+                if let Some((_, content)) = &m.content {
+                    if has_attribute(&m.attrs, "bridge") {
+                        self.add_bridge(content)?;
+                    } else {
+                        self.add_items(content)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn add_bridge(&mut self, items: &[Item]) -> Result<(), GenerateError> {
+        let mut java_types = BTreeMap::new();
+        collect_java_types(items, &mut java_types);
+
+        let mut handles = BTreeMap::new();
+        collect_native_handles(items, &mut handles);
+        for (name, handle) in handles {
+            if let Some((class, true)) = java_types.get(&name) {
+                self.class_node(class).handle = Some(handle);
+            }
+        }
+
+        let mut impls = Vec::new();
+        collect_impls(items, &mut impls);
+
+        for item_impl in impls {
+            let struct_name = match &*item_impl.self_ty {
+                Type::Path(TypePath { path, .. }) => match path.segments.last() {
+                    Some(s) => s.ident.to_string(),
+                    None => continue,
+                },
+                _ => continue,
+            };
+            let class = match java_types.get(&struct_name) {
+                Some((class, true)) => class,
+                _ => continue,
+            };
+
+            let bridge = Bridge {
+                java_types: &java_types,
+                class,
+            };
+            let methods = item_impl
+                .items
+                .iter()
+                .filter_map(|i| match i {
+                    ImplItem::Fn(f) if is_native(f) => Some(bridge.native_method(f)),
+                    _ => None,
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            if !methods.is_empty() {
+                self.class_node(class).methods.extend(methods);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn class_node(&mut self, class: &ClassName) -> &mut ClassNode {
+        let mut path = class.path();
+        let top_level = path.next().unwrap().clone();
+        let node = self
+            .top_level
+            .entry((class.package.clone(), top_level))
+            .or_default();
+
+        path.fold(node, |node, name| {
+            node.nested.entry(name.clone()).or_default()
+        })
+    }
+
+    fn into_java(self, library: &str) -> Vec<JavaClass> {
+        self.top_level
+            .into_iter()
+            .map(|((package, name), node)| {
+                let mut source = String::from("// Generated by robusta-build, do not edit.\n\n");
+                if !package.is_empty() {
+                    writeln!(source, "package {};\n", package).unwrap();
+                }
+                write_class(&mut source, library, &name, &node, 0);
+
+                let classpath_path = if package.is_empty() {
+                    name
+                } else {
+                    format!("{}/{}", package.replace('.', "/"), name)
+                };
+
+                JavaClass {
+                    classpath_path,
+                    source,
+                }
+            })
+            .collect()
+    }
+}
+
+fn write_class(out: &mut String, library: &str, name: &str, node: &ClassNode, depth: usize) {
+    let indent = "    ".repeat(depth);
+    let modifiers = if depth == 0 {
+        "public"
+    } else {
+        "public static"
+    };
+    let interfaces = match &node.handle {
+        Some(NativeHandle {
+            dispose: Some(dispose),
+            ..
+        }) if dispose == "close" => " implements AutoCloseable",
+        _ => "",
+    };
+    writeln!(
+        out,
+        "{}{} class {}{} {{",
+        indent, modifiers, name, interfaces
+    )
+    .unwrap();
+
+    let mut members: Vec<String> = Vec::new();
+    if !node.methods.is_empty() || node.handle.is_some() {
+        members.push(format!(
+            "{i}    static {{\n{i}        System.loadLibrary(\"{}\");\n{i}    }}\n",
+            library,
+            i = indent
+        ));
+    }
+
+    // Peers are created by the native library
+    if let Some(handle) = &node.handle {
+        members.push(format!("{}    private long {};\n", indent, handle.field));
+        members.push(format!(
+            "{i}    private {}() {{\n{i}    }}\n",
+            name,
+            i = indent
+        ));
+    }
+
+    for method in &node.methods {
+        let parameters = method
+            .parameters
+            .iter()
+            .map(|(ty, name)| format!("{} {}", ty, name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let modifiers = if method.is_static {
+            "public static native"
+        } else {
+            "public native"
+        };
+
+        members.push(format!(
+            "{}    {} {} {}({});\n",
+            indent, modifiers, method.return_type, method.name, parameters
+        ));
+    }
+
+    if let Some(dispose) = node.handle.as_ref().and_then(|h| h.dispose.as_ref()) {
+        members.push(format!("{}    public native void {}();\n", indent, dispose));
+    }
+
+    for (nested_name, nested) in &node.nested {
+        let mut nested_source = String::new();
+        write_class(&mut nested_source, library, nested_name, nested, depth + 1);
+        members.push(nested_source);
+    }
+
+    out.push_str(&members.join("\n"));
+    writeln!(out, "{}}}", indent).unwrap();
+}
+
+fn is_native(method: &ImplItemFn) -> bool {
+    method
+        .sig
+        .abi
+        .as_ref()
+        .and_then(|abi| abi.name.as_ref())
+        .is_some_and(|name| name.value() == "jni")
+}
+
+fn is_receiver(arg: &FnArg) -> bool {
+    match arg {
+        FnArg::Receiver(_) => true,
+        FnArg::Typed(t) => matches!(&*t.pat, Pat::Ident(p) if p.ident == "self"),
+    }
+}
+
+fn is_env(ty: &Type) -> bool {
+    match ty {
+        Type::Reference(TypeReference { elem, .. }) => match &**elem {
+            Type::Path(TypePath { path, .. }) => {
+                path.segments.last().is_some_and(|s| s.ident == "JNIEnv")
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+fn java_name(attrs: &[Attribute]) -> Option<String> {
+    attrs
+        .iter()
+        .find(|a| a.path().is_ident("java_name"))
+        .and_then(|a| match &a.meta.require_name_value().ok()?.value {
+            Expr::Lit(ExprLit {
+                lit: Lit::Str(s), ..
+            }) => Some(s.value()),
+            _ => None,
+        })
+}
+
+fn type_arguments(arguments: &PathArguments) -> Vec<&Type> {
+    match arguments {
+        PathArguments::AngleBracketed(a) => a
+            .args
+            .iter()
+            .filter_map(|a| match a {
+                GenericArgument::Type(t) => Some(t),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Type of the value of a `Result` (including aliases such as `JniResult`), or `ty` itself.
+fn result_value_type(ty: &Type) -> &Type {
+    if let Type::Path(TypePath { path, .. }) = ty {
+        if let Some(segment) = path.segments.last() {
+            if segment.ident.to_string().ends_with("Result") {
+                if let Some(value) = type_arguments(&segment.arguments).first() {
+                    return value;
+                }
+            }
+        }
+    }
+
+    ty
+}
+
+/// Types of a bridge module, seen from the class `class`.
+struct Bridge<'a> {
+    java_types: &'a BTreeMap<String, (ClassName, bool)>,
+    class: &'a ClassName,
+}
+
+impl Bridge<'_> {
+    fn unsupported(&self, method: &str, ty: &Type) -> GenerateError {
+        GenerateError::UnsupportedType {
+            class: self.class.classpath_path(),
+            method: method.to_string(),
+            ty: ty.to_token_stream().to_string(),
+        }
+    }
+
+    fn native_method(&self, method: &ImplItemFn) -> Result<NativeMethod, GenerateError> {
+        let name = java_name(&method.attrs).unwrap_or_else(|| method.sig.ident.to_string());
+        let is_static = !method.sig.inputs.iter().any(is_receiver);
+
+        let mut inputs = method
+            .sig
+            .inputs
+            .iter()
+            .filter_map(|i| match i {
+                FnArg::Typed(t) if !is_receiver(i) => Some(t),
+                _ => None,
+            })
+            .peekable();
+
+        // The environment is the first parameter after the receiver, if any
+        if inputs.peek().is_some_and(|t| is_env(&t.ty)) {
+            inputs.next();
+        }
+
+        let parameters = inputs
+            .enumerate()
+            .map(|(i, PatType { pat, ty, .. })| {
+                let ty = self
+                    .java_type(ty, false)
+                    .ok_or_else(|| self.unsupported(&name, ty))?;
+                let name = match &**pat {
+                    Pat::Ident(p) => to_camel_case(&p.ident.to_string()),
+                    _ => format!("arg{}", i),
+                };
+
+                Ok((ty, name))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let return_type = match &method.sig.output {
+            ReturnType::Default => "void".to_string(),
+            ReturnType::Type(_, ty) => match result_value_type(ty) {
+                Type::Tuple(t) if t.elems.is_empty() => "void".to_string(),
+                ty => self
+                    .java_type(ty, false)
+                    .ok_or_else(|| self.unsupported(&name, ty))?,
+            },
+        };
+
+        Ok(NativeMethod {
+            name,
+            is_static,
+            parameters,
+            return_type,
+        })
+    }
+
+    /// Java type of `ty`, as a wrapper class if `boxed` and `ty` is primitive.
+    fn java_type(&self, ty: &Type, boxed: bool) -> Option<String> {
+        let classes = |name: &str| {
+            self.java_types
+                .get(name)
+                .map(|(class, _)| class.classpath_path())
+        };
+        let ty = java_type(ty, &classes)?;
+
+        self.source_type(&if boxed { ty.boxed() } else { ty })
+    }
+
+    /// Name of `ty` in the Java source of the class, `None` if some of its type arguments are unknown.
+    fn source_type(&self, ty: &JavaType) -> Option<String> {
+        match ty {
+            JavaType::Void => Some("void".into()),
+            JavaType::Primitive(p) => Some(p.name().into()),
+            JavaType::Array(p) => Some(format!("{}[]", p.name())),
+            JavaType::Class {
+                classpath_path,
+                type_arguments,
+            } => {
+                let bridged = self
+                    .java_types
+                    .values()
+                    .find(|(class, _)| class.classpath_path() == *classpath_path);
+                let name = match (bridged, classpath_path.strip_prefix("java/lang/")) {
+                    (Some((class, _)), _) => class.source_name(&self.class.package),
+                    (None, Some(name)) if !name.contains('/') => name.to_string(),
+                    (None, _) => classpath_path.replace('/', "."),
+                };
+
+                if type_arguments.is_empty() {
+                    return Some(name);
+                }
+
+                let arguments = type_arguments
+                    .iter()
+                    .map(|a| self.source_type(a.as_ref()?))
+                    .collect::<Option<Vec<_>>>()?;
+
+                Some(format!("{}<{}>", name, arguments.join(", ")))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn generate(source: &str) -> Result<Vec<JavaClass>, GenerateError> {
+        let file = syn::parse_file(source).unwrap();
+        let mut classes = Classes::default();
+        classes.add_items(&file.items)?;
+
+        Ok(classes.into_java("native"))
+    }
+
+    #[test]
+    fn native_methods_are_declared() {
+        let classes = generate(include_str!("../../robusta-example/src/lib.rs")).unwrap();
+
+        assert_eq!(classes.len(), 1);
+        assert_eq!(
+            classes[0].classpath_path(),
+            "com/example/robusta/HelloWorld"
+        );
+        assert_eq!(
+            classes[0].source(),
+            r#"// Generated by robusta-build, do not edit.
+
+package com.example.robusta;
+
+public class HelloWorld {
+    static {
+        System.loadLibrary("native");
+    }
+
+    public static native java.util.ArrayList<String> special(java.util.ArrayList<Integer> input1, int input2);
+
+    public native int nativeFun(boolean staticCall);
+
+    public native int catchMe();
+
+    public native void setStringHelloWorld();
+}
+"#
+        );
+    }
+
+    #[test]
+    fn nested_classes_and_bridged_types() {
+        let classes = generate(
+            r#"
+            #[bridge]
+            mod jni {
+                #[package(com.example)]
+                struct Outer;
+
+                #[package(com.example)]
+                #[outer_class(Outer)]
+                struct Inner;
+
+                #[package(com.example.other)]
+                struct Other;
+
+                impl Inner {
+                    #[java_name = "make"]
+                    pub extern "jni" fn make_inner(env: &JNIEnv, other: Option<Other>, values: Box<[u16]>) -> JniResult<Option<i32>> {}
+                }
+
+                impl Outer {
+                    pub extern "jni" fn wrap(self, inner: Inner) -> HashMap<String, bool> {}
+                }
+            }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(classes.len(), 1);
+        assert_eq!(
+            classes[0].file_path(),
+            PathBuf::from("com/example/Outer.java")
+        );
+        assert_eq!(
+            classes[0].source(),
+            r#"// Generated by robusta-build, do not edit.
+
+package com.example;
+
+public class Outer {
+    static {
+        System.loadLibrary("native");
+    }
+
+    public native java.util.HashMap<String, Boolean> wrap(Outer.Inner inner);
+
+    public static class Inner {
+        static {
+            System.loadLibrary("native");
+        }
+
+        public static native Integer make(com.example.other.Other other, char[] values);
+    }
+}
+"#
+        );
+    }
+
+    #[test]
+    fn native_handle_classes() {
+        let classes = generate(
+            r#"
+            #[bridge]
+            mod jni {
+                #[derive(Signature, NativeHandle)]
+                #[package(com.example)]
+                #[native_handle(field = "handle", dispose = "close")]
+                struct Accumulator {
+                    values: Vec<i64>,
+                }
+
+                impl Accumulator {
+                    pub extern "jni" fn create() -> Accumulator {}
+
+                    pub extern "jni" fn merge(&mut self, other: &Accumulator) -> i64 {}
+                }
+            }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(classes.len(), 1);
+        assert_eq!(
+            classes[0].source(),
+            r#"// Generated by robusta-build, do not edit.
+
+package com.example;
+
+public class Accumulator implements AutoCloseable {
+    static {
+        System.loadLibrary("native");
+    }
+
+    private long handle;
+
+    private Accumulator() {
+    }
+
+    public static native Accumulator create();
+
+    public native long merge(Accumulator other);
+
+    public native void close();
+}
+"#
+        );
+    }
+
+    #[test]
+    fn u8_is_jboolean() {
+        let classes = generate(
+            r#"
+            #[bridge]
+            mod jni {
+                #[package()]
+                struct Flags;
+
+                impl<'env: 'borrow, 'borrow> Flags {
+                    pub extern "jni" fn check(flag: u8, flags: JavaSlice<'env, u8>, bytes: Box<[u8]>, buffer: &'borrow [u8]) -> Option<u8> {}
+                }
+            }
+            "#,
+        )
+        .unwrap();
+
+        assert!(classes[0].source().contains(
+            "public static native Boolean check(boolean flag, boolean[] flags, byte[] bytes, java.nio.ByteBuffer buffer);"
+        ));
+    }
+
+    #[test]
+    fn unsupported_types_are_reported() {
+        let result = generate(
+            r#"
+            #[bridge]
+            mod jni {
+                #[package()]
+                struct Foo;
+
+                impl Foo {
+                    pub extern "jni" fn foo(value: Unknown) {}
+                }
+            }
+            "#,
+        );
+
+        match result {
+            Err(GenerateError::UnsupportedType { class, method, ty }) => {
+                assert_eq!(class, "Foo");
+                assert_eq!(method, "foo");
+                assert_eq!(ty, "Unknown");
+            }
+            _ => panic!("unsupported type not reported"),
+        }
+    }
+}
@@ -0,0 +1,15 @@
+//! Build-time tooling for [robusta_jni](https://docs.rs/robusta_jni), to be used from build scripts.
+//!
+//! The [`java`] module generates the Java classes declaring the native methods of `#[bridge]` modules,
+//! so that they can't get out of sync with their Rust implementation.
+//!
+//! The [`bindings`] module goes the other way, generating `extern "java"` bindings of compiled classes read
+//! by the [`classfile`] module from directories and JARs.
+//!
+//! The [`types`] module maps Rust types to the Java types they are converted to, for these modules and the `#[bridge]` macro.
+//!
+
+pub mod bindings;
+pub mod classfile;
+pub mod java;
+pub mod types;
@@ -0,0 +1,118 @@
+//! Command line interface of robusta-build.
+
+use std::error::Error;
+use std::process::exit;
+
+use robusta_build::bindings::BindingGenerator;
+use robusta_build::classfile::Classpath;
+use robusta_build::java::JavaGenerator;
+
+const USAGE: &str = "\
+Usage:
+    robusta-build bindings --classpath <CLASSPATH> [--class <CLASS>]... [--package <PACKAGE>]... [--module <MODULE>] [--output <FILE>]
+    robusta-build java --library <LIBRARY> --source <FILE>... --out-dir <DIR>
+
+Commands:
+    bindings    Generate `extern \"java\"` bindings of compiled Java classes, on standard output unless `--output` is given
+    java        Generate the Java classes declaring the native methods of `#[bridge]` modules";
+
+/// Values of the options of a command, in order.
+struct Options(Vec<(String, String)>);
+
+impl Options {
+    fn parse(args: impl Iterator<Item = String>, known: &[&str]) -> Result<Self, String> {
+        let mut options = Vec::new();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let name = arg
+                .strip_prefix("--")
+                .filter(|name| known.contains(name))
+                .ok_or_else(|| format!("unexpected argument `{}`", arg))?;
+            let value = args
+                .next()
+                .ok_or_else(|| format!("missing value of `{}`", arg))?;
+            options.push((name.to_string(), value));
+        }
+
+        Ok(Options(options))
+    }
+
+    fn all<'a>(&'a self, name: &str) -> impl Iterator<Item = &'a str> {
+        let name = name.to_string();
+        self.0
+            .iter()
+            .filter(move |(n, _)| *n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn last(&self, name: &str) -> Option<&str> {
+        self.all(name).last()
+    }
+
+    fn required(&self, name: &str) -> Result<&str, String> {
+        self.last(name)
+            .ok_or_else(|| format!("missing `--{}`", name))
+    }
+}
+
+fn bindings(options: &Options) -> Result<(), Box<dyn Error>> {
+    let mut generator = BindingGenerator::new(Classpath::parse(options.required("classpath")?));
+    for class in options.all("class") {
+        generator = generator.class(class);
+    }
+    for package in options.all("package") {
+        generator = generator.package(package);
+    }
+    if let Some(module) = options.last("module") {
+        generator = generator.module(module);
+    }
+
+    match options.last("output") {
+        Some(output) => generator.generate(output)?,
+        None => print!("{}", generator.bindings()?),
+    }
+
+    Ok(())
+}
+
+fn java(options: &Options) -> Result<(), Box<dyn Error>> {
+    let mut generator = JavaGenerator::new(options.required("library")?);
+    for source in options.all("source") {
+        generator = generator.source(source);
+    }
+
+    for path in generator.generate(options.required("out-dir")?)? {
+        println!("{}", path.display());
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+
+    let result = match args.next().as_deref() {
+        Some("bindings") => {
+            Options::parse(args, &["classpath", "class", "package", "module", "output"])
+                .map_err(Into::into)
+                .and_then(|options| bindings(&options))
+        }
+        Some("java") => Options::parse(args, &["library", "source", "out-dir"])
+            .map_err(Into::into)
+            .and_then(|options| java(&options)),
+        Some("--help") | Some("-h") => {
+            println!("{}", USAGE);
+            return;
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            exit(2);
+        }
+    };
+
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        exit(1);
+    }
+}
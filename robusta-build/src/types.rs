@@ -0,0 +1,305 @@
+//! Java types of the Rust types used in `#[bridge]` modules.
+//!
+//! [`java_type`] infers the Java type a Rust type is converted to from its name, for the types of the
+//! [conversion table](https://github.com/giovanniberti/robusta#conversion-table) and the `#[package]` types of the bridge module.
+//! It's shared by the Java class generator of the [`java`](crate::java) module and by the `#[bridge]` macro, which uses it to
+//! name overloaded native methods and to check imported methods against compiled classes,
+//! so that they all agree with the `Signature` implementations of `robusta_jni`.
+//!
+
+use syn::{GenericArgument, PathArguments, Type, TypePath, TypeReference, TypeSlice, TypeTuple};
+
+/// Java primitive type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Primitive {
+    Boolean,
+    Byte,
+    Char,
+    Short,
+    Int,
+    Long,
+    Float,
+    Double,
+}
+
+impl Primitive {
+    /// Primitive type of a Rust primitive type or JNI type alias (e.g. `i32` or `jint`).
+    ///
+    /// Note that `u8` is `jboolean` and `u16` is `jchar`.
+    pub fn from_rust(ident: &str) -> Option<Self> {
+        match ident {
+            "bool" | "u8" | "jboolean" => Some(Primitive::Boolean),
+            "i8" | "jbyte" => Some(Primitive::Byte),
+            "char" | "u16" | "jchar" => Some(Primitive::Char),
+            "i16" | "jshort" => Some(Primitive::Short),
+            "i32" | "jint" => Some(Primitive::Int),
+            "i64" | "jlong" => Some(Primitive::Long),
+            "f32" | "jfloat" => Some(Primitive::Float),
+            "f64" | "jdouble" => Some(Primitive::Double),
+            _ => None,
+        }
+    }
+
+    /// Type descriptor (e.g. `I`).
+    pub fn descriptor(self) -> &'static str {
+        match self {
+            Primitive::Boolean => "Z",
+            Primitive::Byte => "B",
+            Primitive::Char => "C",
+            Primitive::Short => "S",
+            Primitive::Int => "I",
+            Primitive::Long => "J",
+            Primitive::Float => "F",
+            Primitive::Double => "D",
+        }
+    }
+
+    /// Name in Java sources (e.g. `int`).
+    pub fn name(self) -> &'static str {
+        match self {
+            Primitive::Boolean => "boolean",
+            Primitive::Byte => "byte",
+            Primitive::Char => "char",
+            Primitive::Short => "short",
+            Primitive::Int => "int",
+            Primitive::Long => "long",
+            Primitive::Float => "float",
+            Primitive::Double => "double",
+        }
+    }
+
+    /// Wrapper class, in classpath format (e.g. `java/lang/Integer`).
+    pub fn wrapper(self) -> &'static str {
+        match self {
+            Primitive::Boolean => "java/lang/Boolean",
+            Primitive::Byte => "java/lang/Byte",
+            Primitive::Char => "java/lang/Character",
+            Primitive::Short => "java/lang/Short",
+            Primitive::Int => "java/lang/Integer",
+            Primitive::Long => "java/lang/Long",
+            Primitive::Float => "java/lang/Float",
+            Primitive::Double => "java/lang/Double",
+        }
+    }
+}
+
+/// Java type of a Rust type.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum JavaType {
+    /// `void`, for `()`.
+    Void,
+    Primitive(Primitive),
+    /// Array of a primitive type.
+    Array(Primitive),
+    /// Class or interface.
+    Class {
+        /// Class in classpath format (e.g. `java/util/ArrayList`).
+        classpath_path: String,
+        /// Type arguments of generic collections, `None` for those that can't be inferred.
+        type_arguments: Vec<Option<JavaType>>,
+    },
+}
+
+impl JavaType {
+    fn class(classpath_path: impl Into<String>) -> Self {
+        JavaType::Class {
+            classpath_path: classpath_path.into(),
+            type_arguments: Vec::new(),
+        }
+    }
+
+    /// Type descriptor (e.g. `Ljava/util/ArrayList;`), without type arguments.
+    pub fn descriptor(&self) -> String {
+        match self {
+            JavaType::Void => "V".into(),
+            JavaType::Primitive(p) => p.descriptor().into(),
+            JavaType::Array(p) => format!("[{}", p.descriptor()),
+            JavaType::Class { classpath_path, .. } => format!("L{};", classpath_path),
+        }
+    }
+
+    /// Wrapper class of primitive types, the type itself otherwise.
+    pub fn boxed(self) -> Self {
+        match self {
+            JavaType::Primitive(p) => JavaType::class(p.wrapper()),
+            other => other,
+        }
+    }
+}
+
+fn type_arguments(arguments: &PathArguments) -> Vec<&Type> {
+    match arguments {
+        PathArguments::AngleBracketed(a) => a
+            .args
+            .iter()
+            .filter_map(|a| match a {
+                GenericArgument::Type(t) => Some(t),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn last_ident(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Path(TypePath { qself: None, path }) => Some(path.segments.last()?.ident.to_string()),
+        _ => None,
+    }
+}
+
+/// Java type of `ty` according to the conversions of `robusta_jni`, if it can be inferred from its name.
+///
+/// `classes` gives the classpath path (e.g. `com/example/robusta/HelloWorld`) of the `#[package]` types of the bridge module,
+/// by name. References to these types are converted like the types themselves, as are references to byte slices
+/// (to direct `ByteBuffer`s).
+pub fn java_type(ty: &Type, classes: &impl Fn(&str) -> Option<String>) -> Option<JavaType> {
+    match ty {
+        Type::Tuple(TypeTuple { elems, .. }) if elems.is_empty() => Some(JavaType::Void),
+        Type::Path(TypePath { qself: None, path }) => {
+            let segment = path.segments.last()?;
+            let ident = segment.ident.to_string();
+            let arguments = type_arguments(&segment.arguments);
+            let generic = |classpath_path: &str, count: usize| {
+                Some(JavaType::Class {
+                    classpath_path: classpath_path.into(),
+                    type_arguments: arguments
+                        .get(..count)?
+                        .iter()
+                        .map(|a| java_type(a, classes).map(JavaType::boxed))
+                        .collect(),
+                })
+            };
+
+            if let Some(primitive) = Primitive::from_rust(&ident) {
+                return Some(JavaType::Primitive(primitive));
+            }
+
+            match ident.as_str() {
+                "String" | "JString" => Some(JavaType::class("java/lang/String")),
+                "JObject" => Some(JavaType::class("java/lang/Object")),
+                "DirectByteBuffer" => Some(JavaType::class("java/nio/ByteBuffer")),
+                "Vec" => generic("java/util/ArrayList", 1),
+                "HashMap" => generic("java/util/HashMap", 2),
+                "BTreeMap" => generic("java/util/TreeMap", 2),
+                "HashSet" => generic("java/util/HashSet", 1),
+                "BTreeSet" => generic("java/util/TreeSet", 1),
+                "Option" => java_type(arguments.first()?, classes).map(JavaType::boxed),
+                // Boxed slices are copied from and to primitive arrays, with byte array conversions for `u8`
+                "Box" => match arguments.first()? {
+                    Type::Slice(TypeSlice { elem, .. }) => match last_ident(elem)?.as_str() {
+                        "u8" => Some(JavaType::Array(Primitive::Byte)),
+                        element => Primitive::from_rust(element).map(JavaType::Array),
+                    },
+                    _ => None,
+                },
+                "JavaSlice" | "JavaSliceMut" => {
+                    Primitive::from_rust(&last_ident(arguments.first()?)?).map(JavaType::Array)
+                }
+                "JavaCollection" => {
+                    let kind = match arguments.get(1) {
+                        Some(kind) => last_ident(kind)?,
+                        None => "ArrayList".to_string(),
+                    };
+                    let classpath_path = match kind.as_str() {
+                        "ArrayList" => "java/util/ArrayList",
+                        "LinkedList" => "java/util/LinkedList",
+                        "List" => "java/util/List",
+                        "Collection" => "java/util/Collection",
+                        "Iterable" => "java/lang/Iterable",
+                        _ => return None,
+                    };
+
+                    generic(classpath_path, 1)
+                }
+                _ => classes(&ident).map(JavaType::class),
+            }
+        }
+        Type::Reference(TypeReference { elem, .. }) => match &**elem {
+            // Borrowed byte slices are views of direct `ByteBuffer`s
+            Type::Slice(TypeSlice { elem, .. }) if last_ident(elem).as_deref() == Some("u8") => {
+                Some(JavaType::class("java/nio/ByteBuffer"))
+            }
+            // Borrowed native handles are passed as their Java peer
+            Type::Path(_) => classes(&last_ident(elem)?).map(JavaType::class),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use syn::parse_quote;
+
+    use super::*;
+
+    #[test]
+    fn descriptors_of_known_types() {
+        let classes = |name: &str| Some("com/example/User".to_string()).filter(|_| name == "User");
+
+        let cases: Vec<(Type, Option<&str>)> = vec![
+            (parse_quote! { () }, Some("V")),
+            (parse_quote! { i32 }, Some("I")),
+            (parse_quote! { u8 }, Some("Z")),
+            (parse_quote! { i8 }, Some("B")),
+            (parse_quote! { u16 }, Some("C")),
+            (parse_quote! { String }, Some("Ljava/lang/String;")),
+            (parse_quote! { Option<i64> }, Some("Ljava/lang/Long;")),
+            (
+                parse_quote! { Option<User<'env, 'borrow>> },
+                Some("Lcom/example/User;"),
+            ),
+            (parse_quote! { Box<[f32]> }, Some("[F")),
+            (parse_quote! { Box<[u8]> }, Some("[B")),
+            (parse_quote! { Box<[u16]> }, Some("[C")),
+            (parse_quote! { JavaSlice<'env, jint> }, Some("[I")),
+            (parse_quote! { JavaSlice<'env, u8> }, Some("[Z")),
+            (parse_quote! { JavaSliceMut<'env, u16> }, Some("[C")),
+            (parse_quote! { Vec<Unknown> }, Some("Ljava/util/ArrayList;")),
+            (
+                parse_quote! { JavaCollection<String, List> },
+                Some("Ljava/util/List;"),
+            ),
+            (
+                parse_quote! { User<'env, 'borrow> },
+                Some("Lcom/example/User;"),
+            ),
+            (
+                parse_quote! { &'borrow [u8] },
+                Some("Ljava/nio/ByteBuffer;"),
+            ),
+            (parse_quote! { &mut User }, Some("Lcom/example/User;")),
+            (parse_quote! { &String }, None),
+            (parse_quote! { Unknown }, None),
+        ];
+
+        for (ty, expected) in cases {
+            assert_eq!(
+                java_type(&ty, &classes).map(|t| t.descriptor()).as_deref(),
+                expected,
+                "{}",
+                quote::ToTokens::to_token_stream(&ty)
+            );
+        }
+    }
+
+    #[test]
+    fn type_arguments_are_boxed() {
+        let ty: Type = parse_quote! { HashMap<String, Vec<i32>> };
+
+        assert_eq!(
+            java_type(&ty, &|_| None),
+            Some(JavaType::Class {
+                classpath_path: "java/util/HashMap".into(),
+                type_arguments: vec![
+                    Some(JavaType::class("java/lang/String")),
+                    Some(JavaType::Class {
+                        classpath_path: "java/util/ArrayList".into(),
+                        type_arguments: vec![Some(JavaType::class("java/lang/Integer"))],
+                    }),
+                ],
+            })
+        );
+    }
+}
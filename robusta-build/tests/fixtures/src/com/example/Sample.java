@@ -0,0 +1,51 @@
+package com.example;
+
+import java.util.List;
+
+public class Sample implements Runnable {
+    public int count;
+    public static final String NAME = "sample";
+    private long hidden;
+
+    public Sample() {
+    }
+
+    public Sample(int count) {
+        this.count = count;
+    }
+
+    public static String describe(String label, long value) {
+        return label + value;
+    }
+
+    public int add(int a) {
+        return count + a;
+    }
+
+    public int add(int a, int b) {
+        return count + a + b;
+    }
+
+    public String getURL() {
+        return "";
+    }
+
+    public void type(Integer value, byte[] bytes) {
+    }
+
+    public List<String> names(List<String> names) {
+        return names;
+    }
+
+    public Nested nested() {
+        return new Nested();
+    }
+
+    @Override
+    public void run() {
+    }
+
+    public static class Nested {
+        public boolean flag;
+    }
+}
@@ -1,6 +1,17 @@
 use std::collections::HashMap;
 
-use crate::derive::utils::generic_params_to_args;
+use crate::derive::java_enum::{
+    enum_from_java_value, enum_into_java_value, enum_tryfrom_java_value, enum_tryinto_java_value,
+    get_enum_impl_components,
+};
+use crate::derive::utils::{
+    generic_params_to_args, get_field_expr, get_outer_class, is_cacheable, set_field_expr,
+    submit_verify_fields,
+};
+use crate::derive::value::{
+    get_value_impl_components, is_value_struct, value_from_java_value, value_into_java_value,
+    value_tryfrom_java_value, value_tryinto_java_value,
+};
 use crate::transformation::JavaPath;
 use proc_macro2::{Ident, TokenStream};
 use proc_macro_error::{abort, emit_error, emit_warning};
@@ -31,6 +42,24 @@ pub(crate) fn into_java_value_macro_derive(input: DeriveInput) -> TokenStream {
 }
 
 fn into_java_value_macro_derive_impl(input: DeriveInput) -> syn::Result<TokenStream> {
+    if let Data::Enum(data) = &input.data {
+        return Ok(enum_into_java_value(get_enum_impl_components(
+            "IntoJavaValue",
+            &input,
+            data,
+        )));
+    }
+
+    if let Data::Struct(data) = &input.data {
+        if is_value_struct(data) {
+            return Ok(value_into_java_value(get_value_impl_components(
+                "IntoJavaValue",
+                &input,
+                data,
+            )));
+        }
+    }
+
     let TraitAutoDeriveData {
         instance_field_type_assertion,
         impl_target,
@@ -81,6 +110,24 @@ pub(crate) fn tryinto_java_value_macro_derive(input: DeriveInput) -> TokenStream
 }
 
 fn tryinto_java_value_macro_derive_impl(input: DeriveInput) -> syn::Result<TokenStream> {
+    if let Data::Enum(data) = &input.data {
+        return Ok(enum_tryinto_java_value(get_enum_impl_components(
+            "TryIntoJavaValue",
+            &input,
+            data,
+        )));
+    }
+
+    if let Data::Struct(data) = &input.data {
+        if is_value_struct(data) {
+            return Ok(value_tryinto_java_value(get_value_impl_components(
+                "TryIntoJavaValue",
+                &input,
+                data,
+            )));
+        }
+    }
+
     let TraitAutoDeriveData {
         instance_field_type_assertion,
         impl_target,
@@ -131,6 +178,24 @@ pub(crate) fn from_java_value_macro_derive(input: DeriveInput) -> TokenStream {
 }
 
 fn from_java_value_macro_derive_impl(input: DeriveInput) -> syn::Result<TokenStream> {
+    if let Data::Enum(data) = &input.data {
+        return Ok(enum_from_java_value(get_enum_impl_components(
+            "FromJavaValue",
+            &input,
+            data,
+        )));
+    }
+
+    if let Data::Struct(data) = &input.data {
+        if is_value_struct(data) {
+            return Ok(value_from_java_value(get_value_impl_components(
+                "FromJavaValue",
+                &input,
+                data,
+            )));
+        }
+    }
+
     let TraitAutoDeriveData {
         instance_field_type_assertion,
         impl_target,
@@ -153,8 +218,9 @@ fn from_java_value_macro_derive_impl(input: DeriveInput) -> syn::Result<TokenStr
         let field_type_sig = quote_spanned! { field_type.span() =>
             <#field_type as Signature>::SIG_TYPE
         };
+        let get_field = get_field_expr(is_cacheable(&generics), quote! { source }, &classpath_path, &field_name, field_type_sig);
         quote_spanned! { f.span() =>
-            let #field_ident: #field_type = ::robusta_jni::convert::FromJavaValue::from(::core::convert::TryInto::try_into(::robusta_jni::convert::JValueWrapper::from(env.get_field(source, #field_name, #field_type_sig).unwrap())).unwrap(), env);
+            let #field_ident: #field_type = ::robusta_jni::convert::FromJavaValue::from(::core::convert::TryInto::try_into(::robusta_jni::convert::JValueWrapper::from(#get_field.unwrap())).unwrap(), env);
         }
     }).collect();
 
@@ -178,8 +244,15 @@ fn from_java_value_macro_derive_impl(input: DeriveInput) -> syn::Result<TokenStr
         })
         .collect();
 
+    let verify_fields = submit_verify_struct_fields(
+        &generics,
+        &classpath_path,
+        data_fields.iter().chain(&class_fields),
+    );
+
     Ok(quote! {
         #instance_field_type_assertion
+        #verify_fields
 
         #[automatically_derived]
         impl#generics ::robusta_jni::convert::FromJavaValue<'env, 'borrow> for #impl_target#generic_args {
@@ -208,6 +281,24 @@ pub(crate) fn tryfrom_java_value_macro_derive(input: DeriveInput) -> TokenStream
 }
 
 fn tryfrom_java_value_macro_derive_impl(input: DeriveInput) -> syn::Result<TokenStream> {
+    if let Data::Enum(data) = &input.data {
+        return Ok(enum_tryfrom_java_value(get_enum_impl_components(
+            "TryFromJavaValue",
+            &input,
+            data,
+        )));
+    }
+
+    if let Data::Struct(data) = &input.data {
+        if is_value_struct(data) {
+            return Ok(value_tryfrom_java_value(get_value_impl_components(
+                "TryFromJavaValue",
+                &input,
+                data,
+            )));
+        }
+    }
+
     let TraitAutoDeriveData {
         instance_field_type_assertion,
         impl_target,
@@ -230,8 +321,9 @@ fn tryfrom_java_value_macro_derive_impl(input: DeriveInput) -> syn::Result<Token
         let field_type_sig = quote_spanned! { field_type.span() =>
             <#field_type as Signature>::SIG_TYPE
         };
+        let get_field = get_field_expr(is_cacheable(&generics), quote! { source }, &classpath_path, &field_name, field_type_sig);
         quote_spanned! { f.span() =>
-            let #field_ident: #field_type = ::robusta_jni::convert::TryFromJavaValue::try_from(::core::convert::TryInto::try_into(::robusta_jni::convert::JValueWrapper::from(env.get_field(source, #field_name, #field_type_sig)?))?, env)?;
+            let #field_ident: #field_type = ::robusta_jni::convert::TryFromJavaValue::try_from(::core::convert::TryInto::try_into(::robusta_jni::convert::JValueWrapper::from(#get_field?))?, env)?;
         }
     }).collect();
 
@@ -252,8 +344,15 @@ fn tryfrom_java_value_macro_derive_impl(input: DeriveInput) -> syn::Result<Token
         }
     }).collect();
 
+    let verify_fields = submit_verify_struct_fields(
+        &generics,
+        &classpath_path,
+        data_fields.iter().chain(&class_fields),
+    );
+
     Ok(quote! {
         #instance_field_type_assertion
+        #verify_fields
 
         #[automatically_derived]
         impl#generics ::robusta_jni::convert::TryFromJavaValue<'env, 'borrow> for #impl_target#generic_args {
@@ -273,6 +372,105 @@ fn tryfrom_java_value_macro_derive_impl(input: DeriveInput) -> syn::Result<Token
     })
 }
 
+pub(crate) fn write_back_macro_derive(input: DeriveInput) -> TokenStream {
+    let input_span = input.span();
+    match write_back_macro_derive_impl(input) {
+        Ok(t) => t,
+        Err(_) => quote_spanned! { input_span => },
+    }
+}
+
+fn write_back_macro_derive_impl(input: DeriveInput) -> syn::Result<TokenStream> {
+    let TraitAutoDeriveData {
+        instance_field_type_assertion,
+        impl_target,
+        classpath_path,
+        generics,
+        instance_ident,
+        generic_args,
+        data_fields,
+        ..
+    } = get_trait_impl_components("WriteBack", input);
+
+    let data_fields_idents: Vec<_> = data_fields
+        .iter()
+        .map(|f| f.ident.as_ref().unwrap())
+        .collect();
+    let data_fields_types: Vec<_> = data_fields.iter().map(|f| &f.ty).collect();
+    let data_fields_clone: Vec<_> = data_fields
+        .iter()
+        .map(|f| {
+            let field_ident = f.ident.as_ref().unwrap();
+            quote_spanned! { f.ty.span() => ::std::clone::Clone::clone(&self.#field_ident) }
+        })
+        .collect();
+    let data_fields_env_set: Vec<_> = data_fields.iter().enumerate().map(|(i, f)| {
+        let index = syn::Index::from(i);
+        let field_ident = f.ident.as_ref().unwrap();
+        let field_name = field_ident.to_string();
+        let field_type = &f.ty;
+        let field_type_sig = quote_spanned! { field_type.span() =>
+            <#field_type as ::robusta_jni::convert::Signature>::SIG_TYPE
+        };
+        let set_field = set_field_expr(
+            is_cacheable(&generics),
+            quote! { #instance_ident.as_obj() },
+            &classpath_path,
+            &field_name,
+            field_type_sig,
+            quote_spanned! { field_type.span() => ::std::convert::Into::into(<#field_type as ::robusta_jni::convert::TryIntoJavaValue>::try_into(#field_ident, env)?) },
+        );
+        quote_spanned! { f.span() =>
+            if ::std::cmp::PartialEq::ne(&#field_ident, &snapshot.#index) {
+                #set_field?;
+            }
+        }
+    }).collect();
+
+    let verify_fields = submit_verify_struct_fields(&generics, &classpath_path, &data_fields);
+
+    Ok(quote! {
+        #instance_field_type_assertion
+        #verify_fields
+
+        #[automatically_derived]
+        impl#generics ::robusta_jni::convert::WriteBack<'env> for #impl_target#generic_args {
+            type Snapshot = (#(#data_fields_types,)*);
+
+            fn snapshot(&self) -> Self::Snapshot {
+                (#(#data_fields_clone,)*)
+            }
+
+            fn write_back(self, snapshot: Self::Snapshot, env: &::robusta_jni::jni::JNIEnv<'env>) -> ::robusta_jni::jni::errors::Result<()> {
+                let Self { #instance_ident, #(#data_fields_idents,)* .. } = self;
+
+                #(#data_fields_env_set)*
+
+                Ok(())
+            }
+        }
+    })
+}
+
+/// Add the fields read or written by a conversion to the registry checked by `robusta_jni::verify`,
+/// unless their signatures may depend on type parameters.
+fn submit_verify_struct_fields<'a>(
+    generics: &Generics,
+    classpath_path: &str,
+    fields: impl IntoIterator<Item = &'a Field>,
+) -> TokenStream {
+    if !is_cacheable(generics) {
+        return TokenStream::new();
+    }
+
+    submit_verify_fields(
+        classpath_path,
+        fields
+            .into_iter()
+            .map(|f| (f.ident.as_ref().unwrap().to_string(), &f.ty)),
+    )
+}
+
 fn get_trait_impl_components(trait_name: &str, input: DeriveInput) -> TraitAutoDeriveData {
     let input_span = input.span();
     let input_ident = &input.ident;
@@ -286,18 +484,11 @@ fn get_trait_impl_components(trait_name: &str, input: DeriveInput) -> TraitAutoD
                 abort!(input_span, "missing `#[package]` attribute")
             }
 
+            let outer_class = get_outer_class(&input.attrs);
             let classpath_path = package_attr
                 .unwrap()
                 .parse_args()
-                .map(|p: JavaPath| p.to_classpath_path())
-                .map(|s| {
-                    let mut s = s.clone();
-                    if !s.is_empty() {
-                        s.push('/');
-                    }
-                    s.push_str(&input_ident.to_string());
-                    s
-                })
+                .map(|p: JavaPath| p.class_path(outer_class.as_ref(), &input_ident.to_string()))
                 .unwrap_or_else(|_| {
                     emit_error!(package_attr, "invalid Java class path");
                     "".to_string()
@@ -358,7 +549,7 @@ fn get_trait_impl_components(trait_name: &str, input: DeriveInput) -> TraitAutoD
                 )
             }
 
-            let instance_field_data = instance_fields.get(0);
+            let instance_field_data = instance_fields.first();
 
             match instance_field_data {
                 None => abort!(input_span, "missing `#[instance] field attribute"),
@@ -427,7 +618,7 @@ fn get_trait_impl_components(trait_name: &str, input: DeriveInput) -> TraitAutoD
         }
         _ => abort!(
             input,
-            "`{}` auto-derive implemented for structs only",
+            "`{}` auto-derive implemented for structs and fieldless enums only",
             trait_name
         ),
     }
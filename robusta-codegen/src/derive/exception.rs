@@ -0,0 +1,157 @@
+use darling::util::Flag;
+use darling::FromMeta;
+use proc_macro2::TokenStream;
+use proc_macro_error::emit_error;
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{Attribute, Data, DeriveInput, Fields, Member};
+
+use crate::transformation::JavaPath;
+
+/// Parameters of the `#[exception(...)]` attribute on error types and their variants.
+#[derive(Default, FromMeta)]
+#[darling(default)]
+struct ExceptionParams {
+    class: Option<JavaPath>,
+    message: Option<String>,
+}
+
+/// Parameters of the `#[exception(...)]` attribute on fields.
+#[derive(Default, FromMeta)]
+#[darling(default)]
+struct ExceptionFieldParams {
+    cause: Flag,
+}
+
+fn get_exception_params<T: FromMeta + Default>(attrs: &[Attribute]) -> T {
+    attrs
+        .iter()
+        .find(|a| a.path().is_ident("exception"))
+        .and_then(|a| {
+            T::from_meta(&a.meta)
+                .map_err(|e| emit_error!(a, "invalid `exception` attribute options ({})", e))
+                .ok()
+        })
+        .unwrap_or_default()
+}
+
+/// Field marked as the cause of the exception, if any.
+fn get_cause(fields: &Fields) -> Option<Member> {
+    let mut causes = fields
+        .iter()
+        .enumerate()
+        .filter(|(_, f)| {
+            get_exception_params::<ExceptionFieldParams>(&f.attrs)
+                .cause
+                .is_present()
+        })
+        .map(|(i, f)| match &f.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(i.into()),
+        });
+
+    let cause = causes.next();
+    if let Some(other) = causes.next() {
+        emit_error!(other, "only one field can be the cause of the exception");
+    }
+
+    cause
+}
+
+pub(crate) fn into_java_exception_macro_derive(input: DeriveInput) -> TokenStream {
+    let input_span = input.span();
+    let input_ident = &input.ident;
+    let default_params: ExceptionParams = get_exception_params(&input.attrs);
+
+    // Pattern matching each variant (or the struct itself), with its parameters and cause
+    let cases: Vec<(TokenStream, ExceptionParams, Option<Member>)> = match &input.data {
+        Data::Enum(data) => data
+            .variants
+            .iter()
+            .map(|v| {
+                let variant = &v.ident;
+                (
+                    quote_spanned! { v.span() => Self::#variant },
+                    get_exception_params(&v.attrs),
+                    get_cause(&v.fields),
+                )
+            })
+            .collect(),
+        Data::Struct(data) => vec![(
+            quote_spanned! { input_span => Self },
+            ExceptionParams::default(),
+            get_cause(&data.fields),
+        )],
+        Data::Union(_) => {
+            emit_error!(
+                input_span,
+                "`IntoJavaException` auto-derive not supported on unions"
+            );
+            vec![]
+        }
+    };
+
+    let default_class = default_params
+        .class
+        .as_ref()
+        .map(JavaPath::to_classpath_path)
+        .unwrap_or_else(|| "java/lang/RuntimeException".into());
+
+    let class_arms = cases.iter().map(|(pattern, params, _)| {
+        let class = params
+            .class
+            .as_ref()
+            .map(JavaPath::to_classpath_path)
+            .unwrap_or_else(|| default_class.clone());
+        quote! { #pattern { .. } => #class }
+    });
+
+    let message_arms = cases.iter().map(|(pattern, params, _)| {
+        match params.message.as_ref().or(default_params.message.as_ref()) {
+            Some(message) => {
+                quote! { #pattern { .. } => ::std::string::ToString::to_string(#message) }
+            }
+            None => quote! { #pattern { .. } => ::std::string::ToString::to_string(self) },
+        }
+    });
+
+    let cause_arms = cases.iter().filter_map(|(pattern, _, cause)| {
+        cause.as_ref().map(|cause| {
+            quote! {
+                #pattern { #cause: cause, .. } => ::robusta_jni::exception::IntoJavaException::to_throwable(cause, env).map(Some)
+            }
+        })
+    });
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    quote! {
+        #[automatically_derived]
+        impl #impl_generics ::robusta_jni::exception::IntoJavaException for #input_ident #ty_generics #where_clause {
+            fn exception_class(&self) -> ::std::borrow::Cow<'_, str> {
+                let class: &'static str = match self {
+                    #(#class_arms,)*
+                };
+
+                ::std::borrow::Cow::Borrowed(class)
+            }
+
+            fn exception_message(&self) -> ::std::string::String {
+                match self {
+                    #(#message_arms,)*
+                }
+            }
+
+            #[allow(unreachable_patterns)]
+            fn exception_cause<'env>(
+                &self,
+                env: &::robusta_jni::jni::JNIEnv<'env>,
+            ) -> ::robusta_jni::jni::errors::Result<::std::option::Option<::robusta_jni::jni::objects::JThrowable<'env>>> {
+                match self {
+                    #(#cause_arms,)*
+                    _ => Ok(None),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use darling::util::Flag;
+use darling::FromMeta;
+use proc_macro2::TokenStream;
+use proc_macro_error::{abort, emit_error};
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{Attribute, Data, DeriveInput};
+
+use crate::derive::utils::get_outer_class;
+use crate::transformation::JavaPath;
+use crate::utils::{submit_verify_members, verify_member};
+
+/// Parameters of the `#[native_handle(...)]` attribute.
+#[derive(Default, FromMeta)]
+#[darling(default)]
+pub(crate) struct NativeHandleParams {
+    /// Name of the `long` field of the peer, `nativeHandle` by default.
+    field: Option<String>,
+    /// Register peers with a `java.lang.ref.Cleaner`.
+    pub(crate) cleaner: Flag,
+    /// Name of the generated native method closing the peer, if any.
+    pub(crate) dispose: Option<String>,
+}
+
+impl NativeHandleParams {
+    pub(crate) fn field(&self) -> &str {
+        self.field.as_deref().unwrap_or("nativeHandle")
+    }
+}
+
+pub(crate) fn get_native_handle_params(attrs: &[Attribute]) -> NativeHandleParams {
+    attrs
+        .iter()
+        .find(|a| a.path().is_ident("native_handle"))
+        .and_then(|a| {
+            NativeHandleParams::from_meta(&a.meta)
+                .map_err(|e| emit_error!(a, "invalid `native_handle` attribute options ({})", e))
+                .ok()
+        })
+        .unwrap_or_default()
+}
+
+pub(crate) fn native_handle_macro_derive(input: DeriveInput) -> TokenStream {
+    let input_span = input.span();
+    let input_ident = &input.ident;
+
+    if !matches!(input.data, Data::Struct(_)) {
+        abort!(
+            input_span,
+            "`NativeHandle` auto-derive implemented for structs only"
+        );
+    }
+
+    if !input.generics.params.is_empty() {
+        emit_error!(
+            input.generics,
+            "`NativeHandle` auto-derive is not supported on generic structs"
+        );
+    }
+
+    let package_attr = input.attrs.iter().find(|a| a.path().is_ident("package"));
+    let outer_class = get_outer_class(&input.attrs);
+    let classpath_path = match package_attr {
+        None => abort!(input_span, "missing `#[package]` attribute"),
+        Some(attr) => attr
+            .parse_args()
+            .map(|p: JavaPath| p.class_path(outer_class.as_ref(), &input_ident.to_string()))
+            .unwrap_or_else(|_| {
+                emit_error!(attr, "invalid Java class path");
+                "".to_string()
+            }),
+    };
+
+    let params = get_native_handle_params(&input.attrs);
+    let field = params.field();
+    let cleaner = params.cleaner.is_present();
+
+    let verify_members = submit_verify_members(&[
+        verify_member("Field", &classpath_path, field, quote! { "J", }),
+        verify_member("Constructor", &classpath_path, "<init>", quote! { "()V", }),
+    ]);
+
+    quote! {
+        #verify_members
+
+        #[automatically_derived]
+        impl ::robusta_jni::handle::NativeHandle for #input_ident {
+            const FIELD: &'static str = #field;
+            const CLEANER: bool = #cleaner;
+
+            fn field_cache() -> &'static ::robusta_jni::cache::CachedField {
+                static FIELD: ::robusta_jni::cache::CachedField = ::robusta_jni::cache::CachedField::new();
+                &FIELD
+            }
+        }
+
+        #[automatically_derived]
+        impl<'env> ::robusta_jni::convert::IntoJavaValue<'env> for #input_ident {
+            type Target = ::robusta_jni::jni::objects::JObject<'env>;
+
+            fn into(self, env: &::robusta_jni::jni::JNIEnv<'env>) -> Self::Target {
+                static CONSTRUCTOR: ::robusta_jni::cache::CachedConstructor = ::robusta_jni::cache::CachedConstructor::new();
+
+                let peer = unsafe { CONSTRUCTOR.new_object(env, #classpath_path, || "()V".to_string(), &[]) }.unwrap();
+                ::robusta_jni::handle::attach(env, peer, self).unwrap();
+                peer
+            }
+        }
+
+        #[automatically_derived]
+        impl<'env> ::robusta_jni::convert::TryIntoJavaValue<'env> for #input_ident {
+            type Target = ::robusta_jni::jni::objects::JObject<'env>;
+
+            fn try_into(self, env: &::robusta_jni::jni::JNIEnv<'env>) -> ::robusta_jni::jni::errors::Result<Self::Target> {
+                static CONSTRUCTOR: ::robusta_jni::cache::CachedConstructor = ::robusta_jni::cache::CachedConstructor::new();
+
+                let peer = unsafe { CONSTRUCTOR.new_object(env, #classpath_path, || "()V".to_string(), &[]) }?;
+                ::robusta_jni::handle::attach(env, peer, self)?;
+                Ok(peer)
+            }
+        }
+
+        #[automatically_derived]
+        impl<'env: 'borrow, 'borrow> ::robusta_jni::convert::FromJavaValue<'env, 'borrow> for #input_ident {
+            type Source = ::robusta_jni::jni::objects::JObject<'env>;
+
+            fn from(source: Self::Source, env: &'borrow ::robusta_jni::jni::JNIEnv<'env>) -> Self {
+                ::robusta_jni::handle::detach(env, source).unwrap()
+            }
+        }
+
+        #[automatically_derived]
+        impl<'env: 'borrow, 'borrow> ::robusta_jni::convert::TryFromJavaValue<'env, 'borrow> for #input_ident {
+            type Source = ::robusta_jni::jni::objects::JObject<'env>;
+
+            fn try_from(source: Self::Source, env: &'borrow ::robusta_jni::jni::JNIEnv<'env>) -> ::robusta_jni::jni::errors::Result<Self> {
+                ::robusta_jni::handle::detach(env, source)
+            }
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use proc_macro2::{Ident, TokenStream};
+use proc_macro_error::{abort, emit_error};
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{DataEnum, DeriveInput, Fields};
+
+use crate::derive::utils::{get_java_name, get_outer_class};
+use crate::transformation::JavaPath;
+use crate::utils::{submit_verify_members, verify_member};
+
+/// Data needed to derive conversions for a fieldless enum mapped to a Java enum.
+pub(crate) struct EnumAutoDeriveData {
+    impl_target: Ident,
+    classpath_path: String,
+    signature: String,
+    variants: Vec<Ident>,
+    constants: Vec<String>,
+}
+
+pub(crate) fn get_enum_impl_components(
+    trait_name: &str,
+    input: &DeriveInput,
+    data: &DataEnum,
+) -> EnumAutoDeriveData {
+    let input_span = input.span();
+    let input_ident = &input.ident;
+
+    let package_attr = input
+        .attrs
+        .iter()
+        .find(|a| a.path().get_ident().map(ToString::to_string).as_deref() == Some("package"));
+
+    let outer_class = get_outer_class(&input.attrs);
+    let classpath_path = match package_attr {
+        None => abort!(input_span, "missing `#[package]` attribute"),
+        Some(attr) => attr
+            .parse_args()
+            .map(|p: JavaPath| p.class_path(outer_class.as_ref(), &input_ident.to_string()))
+            .unwrap_or_else(|_| {
+                emit_error!(attr, "invalid Java class path");
+                "".to_string()
+            }),
+    };
+
+    if !input.generics.params.is_empty() {
+        emit_error!(
+            input.generics,
+            "`{}` auto-derive is not supported on generic enums",
+            trait_name
+        );
+    }
+
+    let (variants, constants) = data
+        .variants
+        .iter()
+        .map(|v| {
+            if !matches!(v.fields, Fields::Unit) {
+                emit_error!(
+                    v,
+                    "`{}` auto-derive implemented for fieldless enums only",
+                    trait_name
+                );
+            }
+
+            let constant = get_java_name(&v.attrs).unwrap_or_else(|| v.ident.to_string());
+            (v.ident.clone(), constant)
+        })
+        .unzip();
+
+    EnumAutoDeriveData {
+        impl_target: input_ident.clone(),
+        signature: format!("L{};", classpath_path),
+        classpath_path,
+        variants,
+        constants,
+    }
+}
+
+impl EnumAutoDeriveData {
+    /// Index of each variant in `variants` and `constants`.
+    fn indices(&self) -> Vec<usize> {
+        (0..self.variants.len()).collect()
+    }
+}
+
+/// Add the enum constants to the registry checked by `robusta_jni::verify`.
+fn submit_verify_constants(
+    classpath_path: &str,
+    signature: &str,
+    constants: &[String],
+) -> TokenStream {
+    let members: Vec<_> = constants
+        .iter()
+        .map(|c| verify_member("StaticField", classpath_path, c, quote! { #signature, }))
+        .collect();
+
+    submit_verify_members(&members)
+}
+
+pub(crate) fn enum_into_java_value(data: EnumAutoDeriveData) -> TokenStream {
+    let indices = data.indices();
+    let EnumAutoDeriveData {
+        impl_target,
+        classpath_path,
+        signature,
+        variants,
+        constants,
+    } = data;
+    let verify_constants = submit_verify_constants(&classpath_path, &signature, &constants);
+
+    quote! {
+        #verify_constants
+
+        #[automatically_derived]
+        impl<'env> ::robusta_jni::convert::IntoJavaValue<'env> for #impl_target {
+            type Target = ::robusta_jni::jni::objects::JObject<'env>;
+            const KEEPS_LOCAL_REF: bool = false;
+
+            fn into(self, env: &::robusta_jni::jni::JNIEnv<'env>) -> Self::Target {
+                static CONSTANTS: ::robusta_jni::cache::CachedEnumConstants = ::robusta_jni::cache::CachedEnumConstants::new();
+
+                let index = match self {
+                    #(#impl_target::#variants => #indices),*
+                };
+
+                CONSTANTS.get(env, #classpath_path, #signature, &[#(#constants),*], index).unwrap()
+            }
+        }
+    }
+}
+
+pub(crate) fn enum_tryinto_java_value(data: EnumAutoDeriveData) -> TokenStream {
+    let indices = data.indices();
+    let EnumAutoDeriveData {
+        impl_target,
+        classpath_path,
+        signature,
+        variants,
+        constants,
+    } = data;
+    let verify_constants = submit_verify_constants(&classpath_path, &signature, &constants);
+
+    quote! {
+        #verify_constants
+
+        #[automatically_derived]
+        impl<'env> ::robusta_jni::convert::TryIntoJavaValue<'env> for #impl_target {
+            type Target = ::robusta_jni::jni::objects::JObject<'env>;
+            const KEEPS_LOCAL_REF: bool = false;
+
+            fn try_into(self, env: &::robusta_jni::jni::JNIEnv<'env>) -> ::robusta_jni::jni::errors::Result<Self::Target> {
+                static CONSTANTS: ::robusta_jni::cache::CachedEnumConstants = ::robusta_jni::cache::CachedEnumConstants::new();
+
+                let index = match self {
+                    #(#impl_target::#variants => #indices),*
+                };
+
+                CONSTANTS.get(env, #classpath_path, #signature, &[#(#constants),*], index)
+            }
+        }
+    }
+}
+
+pub(crate) fn enum_from_java_value(data: EnumAutoDeriveData) -> TokenStream {
+    let indices = data.indices();
+    let EnumAutoDeriveData {
+        impl_target,
+        classpath_path,
+        signature,
+        variants,
+        constants,
+    } = data;
+
+    quote! {
+        #[automatically_derived]
+        impl<'env: 'borrow, 'borrow> ::robusta_jni::convert::FromJavaValue<'env, 'borrow> for #impl_target {
+            type Source = ::robusta_jni::jni::objects::JObject<'env>;
+            const KEEPS_LOCAL_REF: bool = false;
+
+            fn from(source: Self::Source, env: &'borrow ::robusta_jni::jni::JNIEnv<'env>) -> Self {
+                static CONSTANTS: ::robusta_jni::cache::CachedEnumConstants = ::robusta_jni::cache::CachedEnumConstants::new();
+
+                match CONSTANTS.index_of(env, source, #classpath_path, #signature, &[#(#constants),*]).unwrap() {
+                    #(#indices => #impl_target::#variants,)*
+                    _ => unreachable!(),
+                }
+            }
+        }
+    }
+}
+
+pub(crate) fn enum_tryfrom_java_value(data: EnumAutoDeriveData) -> TokenStream {
+    let indices = data.indices();
+    let EnumAutoDeriveData {
+        impl_target,
+        classpath_path,
+        signature,
+        variants,
+        constants,
+    } = data;
+
+    quote! {
+        #[automatically_derived]
+        impl<'env: 'borrow, 'borrow> ::robusta_jni::convert::TryFromJavaValue<'env, 'borrow> for #impl_target {
+            type Source = ::robusta_jni::jni::objects::JObject<'env>;
+            const KEEPS_LOCAL_REF: bool = false;
+
+            fn try_from(source: Self::Source, env: &'borrow ::robusta_jni::jni::JNIEnv<'env>) -> ::robusta_jni::jni::errors::Result<Self> {
+                static CONSTANTS: ::robusta_jni::cache::CachedEnumConstants = ::robusta_jni::cache::CachedEnumConstants::new();
+
+                match CONSTANTS.index_of(env, source, #classpath_path, #signature, &[#(#constants),*])? {
+                    #(#indices => Ok(#impl_target::#variants),)*
+                    _ => unreachable!(),
+                }
+            }
+        }
+    }
+}
@@ -1,3 +1,7 @@
 pub(crate) mod convert;
+pub(crate) mod exception;
+pub(crate) mod handle;
+mod java_enum;
 pub(crate) mod signature;
-mod utils;
+pub(crate) mod utils;
+mod value;
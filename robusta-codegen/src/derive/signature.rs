@@ -2,11 +2,11 @@ use proc_macro2::TokenStream;
 use proc_macro_error::abort;
 use quote::{quote, quote_spanned};
 use syn::spanned::Spanned;
-use syn::{Data, DataStruct, DeriveInput};
+use syn::{Data, DataEnum, DataStruct, DeriveInput};
 
 use crate::transformation::JavaPath;
 
-use super::utils::generic_params_to_args;
+use super::utils::{generic_params_to_args, get_outer_class};
 
 pub(crate) fn signature_macro_derive(input: DeriveInput) -> TokenStream {
     let input_span = input.span();
@@ -20,7 +20,7 @@ fn signature_macro_derive_impl(input: DeriveInput) -> syn::Result<TokenStream> {
     let input_span = input.span();
 
     match input.data {
-        Data::Struct(DataStruct { .. }) => {
+        Data::Struct(DataStruct { .. }) | Data::Enum(DataEnum { .. }) => {
             let package_attr = input.attrs.iter().find(|a| {
                 a.path().get_ident().map(ToString::to_string).as_deref() == Some("package")
             });
@@ -30,21 +30,11 @@ fn signature_macro_derive_impl(input: DeriveInput) -> syn::Result<TokenStream> {
                 Some(attr) => {
                     let struct_name = input.ident;
                     let package = attr.parse_args::<JavaPath>()?;
-                    let package_str = {
-                        let mut s = package.to_classpath_path();
-                        if !s.is_empty() {
-                            s.push('/')
-                        }
-                        s
-                    };
-
-                    let signature = [
-                        "L",
-                        package_str.as_str(),
-                        struct_name.to_string().as_str(),
-                        ";",
-                    ]
-                    .join("");
+                    let outer_class = get_outer_class(&input.attrs);
+                    let signature = format!(
+                        "L{};",
+                        package.class_path(outer_class.as_ref(), &struct_name.to_string())
+                    );
                     let generics = input.generics.clone();
                     let generic_args = generic_params_to_args(input.generics);
 
@@ -69,7 +59,7 @@ fn signature_macro_derive_impl(input: DeriveInput) -> syn::Result<TokenStream> {
         }
         _ => abort!(
             input_span,
-            "`Signature` auto-derive implemented for structs only"
+            "`Signature` auto-derive implemented for structs and enums only"
         ),
     }
 }
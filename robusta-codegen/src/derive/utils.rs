@@ -1,11 +1,18 @@
+use proc_macro2::TokenStream;
+use proc_macro_error::emit_error;
+use quote::quote;
 use syn::parse_quote;
 use syn::punctuated::Punctuated;
 use syn::spanned::Spanned;
 use syn::Token;
 use syn::{
-    AngleBracketedGenericArguments, ConstParam, GenericArgument, GenericParam, Generics, TypeParam,
+    AngleBracketedGenericArguments, Attribute, ConstParam, Expr, ExprLit, GenericArgument,
+    GenericParam, Generics, Lit, Type, TypeParam,
 };
 
+use crate::transformation::JavaPath;
+use crate::utils::{submit_verify_members, to_static, verify_member};
+
 pub(crate) fn generic_params_to_args(generics: Generics) -> AngleBracketedGenericArguments {
     let args: Punctuated<GenericArgument, Token![,]> = generics
         .params
@@ -32,3 +39,99 @@ pub(crate) fn generic_params_to_args(generics: Generics) -> AngleBracketedGeneri
             .unwrap_or_else(|| Token![>](generics.span())),
     }
 }
+
+/// Parse a `#[java_name = "..."]` attribute.
+pub(crate) fn get_java_name(attrs: &[Attribute]) -> Option<String> {
+    let attr = attrs
+        .iter()
+        .find(|a| a.path().get_ident().map(ToString::to_string).as_deref() == Some("java_name"))?;
+
+    match &attr.meta.require_name_value().map(|m| &m.value) {
+        Ok(Expr::Lit(ExprLit {
+            lit: Lit::Str(s), ..
+        })) => Some(s.value()),
+        _ => {
+            emit_error!(attr, "expected `#[java_name = \"...\"]`");
+            None
+        }
+    }
+}
+
+/// Parse a `#[outer_class(...)]` attribute, naming the class enclosing a nested class (e.g. `Outer` or `Outer.Middle`).
+pub(crate) fn get_outer_class(attrs: &[Attribute]) -> Option<JavaPath> {
+    let attr = attrs.iter().find(|a| {
+        a.path().get_ident().map(ToString::to_string).as_deref() == Some("outer_class")
+    })?;
+
+    attr.parse_args()
+        .map_err(|_| emit_error!(attr, "invalid Java class name"))
+        .ok()
+}
+
+/// Whether member lookups can be cached per call site, i.e. the generated code doesn't depend on type parameters.
+/// Call site caches are `static`s, shared by all instantiations of a generic impl.
+pub(crate) fn is_cacheable(generics: &Generics) -> bool {
+    generics
+        .params
+        .iter()
+        .all(|p| matches!(p, GenericParam::Lifetime(_)))
+}
+
+/// Expression reading field `name` of `obj`, looking it up only once if `cacheable`.
+pub(crate) fn get_field_expr(
+    cacheable: bool,
+    obj: TokenStream,
+    classpath_path: &str,
+    name: &str,
+    signature: TokenStream,
+) -> TokenStream {
+    if cacheable {
+        quote! {{
+            static FIELD: ::robusta_jni::cache::CachedField = ::robusta_jni::cache::CachedField::new();
+            FIELD.get(env, #obj, #classpath_path, #name, #signature)
+        }}
+    } else {
+        quote! { env.get_field(#obj, #name, #signature) }
+    }
+}
+
+/// Expression setting field `name` of `obj` to `value`, looking it up only once if `cacheable`.
+pub(crate) fn set_field_expr(
+    cacheable: bool,
+    obj: TokenStream,
+    classpath_path: &str,
+    name: &str,
+    signature: TokenStream,
+    value: TokenStream,
+) -> TokenStream {
+    if cacheable {
+        quote! {{
+            static FIELD: ::robusta_jni::cache::CachedField = ::robusta_jni::cache::CachedField::new();
+            let value = #value;
+            // The value is converted to the type of the field
+            unsafe { FIELD.set(env, #obj, #classpath_path, #name, #signature, value) }
+        }}
+    } else {
+        quote! { env.set_field(#obj, #name, #signature, #value) }
+    }
+}
+
+/// Add the fields of `classpath_path` read or written by a conversion to the registry checked by `robusta_jni::verify`.
+pub(crate) fn submit_verify_fields<'a>(
+    classpath_path: &str,
+    fields: impl IntoIterator<Item = (String, &'a Type)>,
+) -> TokenStream {
+    let members: Vec<_> = fields
+        .into_iter()
+        .map(|(name, ty)| {
+            verify_member(
+                "Field",
+                classpath_path,
+                &name,
+                quote! { <#ty as ::robusta_jni::convert::Signature>::SIG_TYPE, },
+            )
+        })
+        .collect();
+
+    to_static(submit_verify_members(&members), None)
+}
@@ -0,0 +1,370 @@
+use proc_macro2::{Ident, TokenStream};
+use proc_macro_error::{abort, emit_error};
+use quote::{quote, quote_spanned};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{DataStruct, DeriveInput, Fields, Token, Type};
+
+use crate::derive::utils::{
+    get_field_expr, get_java_name, get_outer_class, set_field_expr, submit_verify_fields,
+};
+use crate::transformation::JavaPath;
+use crate::utils::{submit_verify_members, verify_member};
+
+/// How a value struct is turned into a new Java object.
+enum Construction {
+    /// Call the constructor taking the listed fields, in order.
+    Constructor(Vec<usize>),
+    /// `AllocObject` without running any constructor, then set every field.
+    AllocObject,
+}
+
+/// Data needed to derive conversions for an owned struct converted by value, i.e. without an `#[instance]` field.
+pub(crate) struct ValueAutoDeriveData {
+    impl_target: Ident,
+    classpath_path: String,
+    idents: Vec<Ident>,
+    types: Vec<Type>,
+    java_names: Vec<String>,
+    construction: Construction,
+}
+
+/// Whether the struct is converted by value, i.e. doesn't keep a reference to a Java instance.
+pub(crate) fn is_value_struct(data: &DataStruct) -> bool {
+    !data.fields.iter().any(|f| {
+        f.attrs
+            .iter()
+            .any(|a| a.path().get_ident().map(ToString::to_string).as_deref() == Some("instance"))
+    })
+}
+
+pub(crate) fn get_value_impl_components(
+    trait_name: &str,
+    input: &DeriveInput,
+    data: &DataStruct,
+) -> ValueAutoDeriveData {
+    let input_span = input.span();
+    let input_ident = &input.ident;
+
+    let package_attr = input
+        .attrs
+        .iter()
+        .find(|a| a.path().get_ident().map(ToString::to_string).as_deref() == Some("package"));
+
+    let outer_class = get_outer_class(&input.attrs);
+    let classpath_path = match package_attr {
+        None => abort!(input_span, "missing `#[package]` attribute"),
+        Some(attr) => attr
+            .parse_args()
+            .map(|p: JavaPath| p.class_path(outer_class.as_ref(), &input_ident.to_string()))
+            .unwrap_or_else(|_| {
+                emit_error!(attr, "invalid Java class path");
+                "".to_string()
+            }),
+    };
+
+    if !input.generics.params.is_empty() {
+        emit_error!(input.generics, "`{}` auto-derive without an `#[instance]` field is not supported on generic structs", trait_name;
+            help = "add an `#[instance]` field to keep a reference to the Java object instead");
+    }
+
+    let fields = match &data.fields {
+        Fields::Named(f) => &f.named,
+        _ => abort!(
+            input_span,
+            "`{}` auto-derive without an `#[instance]` field implemented for structs with named fields only",
+            trait_name
+        ),
+    };
+
+    for f in fields {
+        if let Some(a) = f.attrs.iter().find(|a| a.path().is_ident("field")) {
+            emit_error!(a, "`#[field]` requires an `#[instance]` field";
+                help = "plain fields are already read from the Java field with the same name");
+        }
+    }
+
+    let idents: Vec<Ident> = fields.iter().map(|f| f.ident.clone().unwrap()).collect();
+    let types: Vec<Type> = fields.iter().map(|f| f.ty.clone()).collect();
+    let java_names: Vec<String> = fields
+        .iter()
+        .map(|f| get_java_name(&f.attrs).unwrap_or_else(|| f.ident.as_ref().unwrap().to_string()))
+        .collect();
+
+    let construction = match input
+        .attrs
+        .iter()
+        .find(|a| a.path().is_ident("constructor"))
+    {
+        None => Construction::AllocObject,
+        Some(attr) if attr.meta.require_path_only().is_ok() => {
+            Construction::Constructor((0..idents.len()).collect())
+        }
+        Some(attr) => {
+            match attr.parse_args_with(Punctuated::<Ident, Token![,]>::parse_terminated) {
+                Ok(params) => Construction::Constructor(
+                    params
+                        .iter()
+                        .filter_map(|p| {
+                            let position = idents.iter().position(|i| i == p);
+                            if position.is_none() {
+                                emit_error!(p, "unknown field `{}`", p);
+                            }
+                            position
+                        })
+                        .collect(),
+                ),
+                Err(e) => {
+                    emit_error!(e.span(), "expected a list of field names");
+                    Construction::AllocObject
+                }
+            }
+        }
+    };
+
+    ValueAutoDeriveData {
+        impl_target: input_ident.clone(),
+        classpath_path,
+        idents,
+        types,
+        java_names,
+        construction,
+    }
+}
+
+/// Generate the body of a conversion to a new Java object.
+/// `convert` maps a field access expression and its type to a [`JValue`](jni::objects::JValue) expression, `unwrap` is appended to fallible JNI calls.
+fn new_object_body(
+    data: &ValueAutoDeriveData,
+    convert: impl Fn(TokenStream, &Type) -> TokenStream,
+    unwrap: TokenStream,
+) -> TokenStream {
+    let ValueAutoDeriveData {
+        classpath_path,
+        idents,
+        types,
+        java_names,
+        construction,
+        ..
+    } = data;
+
+    match construction {
+        Construction::Constructor(params) => {
+            let param_types: Vec<_> = params.iter().map(|&i| &types[i]).collect();
+            let param_values: Vec<_> = params
+                .iter()
+                .map(|&i| {
+                    let ident = &idents[i];
+                    convert(quote! { self.#ident }, &types[i])
+                })
+                .collect();
+
+            quote! {
+                static CONSTRUCTOR: ::robusta_jni::cache::CachedConstructor = ::robusta_jni::cache::CachedConstructor::new();
+
+                let args: &[::robusta_jni::jni::objects::JValue] = &[#(#param_values),*];
+                let constructor_signature = || [
+                    "(",
+                    #(<#param_types as ::robusta_jni::convert::Signature>::SIG_TYPE,)*
+                    ")V",
+                ].concat();
+
+                // The arguments are converted to the types of the signature
+                unsafe { CONSTRUCTOR.new_object(env, #classpath_path, constructor_signature, args) }#unwrap
+            }
+        }
+        Construction::AllocObject => {
+            let set_fields = idents
+                .iter()
+                .zip(types)
+                .zip(java_names)
+                .map(|((ident, ty), name)| {
+                    let value = convert(quote! { self.#ident }, ty);
+                    let set_field = set_field_expr(
+                        true,
+                        quote! { obj },
+                        classpath_path,
+                        name,
+                        quote! { <#ty as ::robusta_jni::convert::Signature>::SIG_TYPE },
+                        value,
+                    );
+                    quote_spanned! { ident.span() =>
+                        #set_field#unwrap;
+                    }
+                });
+
+            quote! {
+                static CLASS: ::robusta_jni::cache::CachedClass = ::robusta_jni::cache::CachedClass::new();
+
+                let obj = env.alloc_object(CLASS.get(env, #classpath_path)#unwrap)#unwrap;
+                #(#set_fields)*
+                obj
+            }
+        }
+    }
+}
+
+/// Add the constructor or fields used to create a new Java object to the registry checked by `robusta_jni::verify`.
+fn submit_verify_new_object(data: &ValueAutoDeriveData) -> TokenStream {
+    match &data.construction {
+        Construction::Constructor(params) => {
+            let param_types = params.iter().map(|&i| &data.types[i]);
+            let member = verify_member(
+                "Constructor",
+                &data.classpath_path,
+                "<init>",
+                quote! { "(", #(<#param_types as ::robusta_jni::convert::Signature>::SIG_TYPE,)* ")V", },
+            );
+
+            submit_verify_members(&[member])
+        }
+        Construction::AllocObject => submit_verify_fields(
+            &data.classpath_path,
+            data.java_names.iter().cloned().zip(&data.types),
+        ),
+    }
+}
+
+pub(crate) fn value_into_java_value(data: ValueAutoDeriveData) -> TokenStream {
+    let impl_target = &data.impl_target;
+    let verify_members = submit_verify_new_object(&data);
+    let body = new_object_body(
+        &data,
+        |value, ty| quote_spanned! { ty.span() => ::std::convert::Into::into(<#ty as ::robusta_jni::convert::IntoJavaValue>::into(#value, env)) },
+        quote! { .unwrap() },
+    );
+
+    quote! {
+        #verify_members
+
+        #[automatically_derived]
+        impl<'env> ::robusta_jni::convert::IntoJavaValue<'env> for #impl_target {
+            type Target = ::robusta_jni::jni::objects::JObject<'env>;
+            const KEEPS_LOCAL_REF: bool = false;
+
+            fn into(self, env: &::robusta_jni::jni::JNIEnv<'env>) -> Self::Target {
+                #body
+            }
+        }
+    }
+}
+
+pub(crate) fn value_tryinto_java_value(data: ValueAutoDeriveData) -> TokenStream {
+    let impl_target = &data.impl_target;
+    let verify_members = submit_verify_new_object(&data);
+    let body = new_object_body(
+        &data,
+        |value, ty| quote_spanned! { ty.span() => ::std::convert::Into::into(<#ty as ::robusta_jni::convert::TryIntoJavaValue>::try_into(#value, env)?) },
+        quote! { ? },
+    );
+
+    quote! {
+        #verify_members
+
+        #[automatically_derived]
+        impl<'env> ::robusta_jni::convert::TryIntoJavaValue<'env> for #impl_target {
+            type Target = ::robusta_jni::jni::objects::JObject<'env>;
+            const KEEPS_LOCAL_REF: bool = false;
+
+            fn try_into(self, env: &::robusta_jni::jni::JNIEnv<'env>) -> ::robusta_jni::jni::errors::Result<Self::Target> {
+                Ok({ #body })
+            }
+        }
+    }
+}
+
+pub(crate) fn value_from_java_value(data: ValueAutoDeriveData) -> TokenStream {
+    let ValueAutoDeriveData {
+        impl_target,
+        classpath_path,
+        idents,
+        types,
+        java_names,
+        ..
+    } = data;
+
+    let verify_fields =
+        submit_verify_fields(&classpath_path, java_names.iter().cloned().zip(&types));
+    let get_fields = types.iter().zip(&java_names).map(|(ty, name)| {
+        get_field_expr(
+            true,
+            quote! { source },
+            &classpath_path,
+            name,
+            quote! { <#ty as ::robusta_jni::convert::Signature>::SIG_TYPE },
+        )
+    });
+
+    quote! {
+        #verify_fields
+
+        #[automatically_derived]
+        impl<'env: 'borrow, 'borrow> ::robusta_jni::convert::FromJavaValue<'env, 'borrow> for #impl_target {
+            type Source = ::robusta_jni::jni::objects::JObject<'env>;
+            const KEEPS_LOCAL_REF: bool = false;
+
+            fn from(source: Self::Source, env: &'borrow ::robusta_jni::jni::JNIEnv<'env>) -> Self {
+                #(
+                    let #idents: #types = ::robusta_jni::convert::FromJavaValue::from(
+                        ::core::convert::TryInto::try_into(::robusta_jni::convert::JValueWrapper::from(
+                            #get_fields.unwrap()
+                        )).unwrap(),
+                        env,
+                    );
+                )*
+
+                Self {
+                    #(#idents),*
+                }
+            }
+        }
+    }
+}
+
+pub(crate) fn value_tryfrom_java_value(data: ValueAutoDeriveData) -> TokenStream {
+    let ValueAutoDeriveData {
+        impl_target,
+        classpath_path,
+        idents,
+        types,
+        java_names,
+        ..
+    } = data;
+
+    let verify_fields =
+        submit_verify_fields(&classpath_path, java_names.iter().cloned().zip(&types));
+    let get_fields = types.iter().zip(&java_names).map(|(ty, name)| {
+        get_field_expr(
+            true,
+            quote! { source },
+            &classpath_path,
+            name,
+            quote! { <#ty as ::robusta_jni::convert::Signature>::SIG_TYPE },
+        )
+    });
+
+    quote! {
+        #verify_fields
+
+        #[automatically_derived]
+        impl<'env: 'borrow, 'borrow> ::robusta_jni::convert::TryFromJavaValue<'env, 'borrow> for #impl_target {
+            type Source = ::robusta_jni::jni::objects::JObject<'env>;
+            const KEEPS_LOCAL_REF: bool = false;
+
+            fn try_from(source: Self::Source, env: &'borrow ::robusta_jni::jni::JNIEnv<'env>) -> ::robusta_jni::jni::errors::Result<Self> {
+                #(
+                    let #idents: #types = ::robusta_jni::convert::TryFromJavaValue::try_from(
+                        ::core::convert::TryInto::try_into(::robusta_jni::convert::JValueWrapper::from(
+                            #get_fields?
+                        ))?,
+                        env,
+                    )?;
+                )*
+
+                Ok(Self {
+                    #(#idents),*
+                })
+            }
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use darling::ast::NestedMeta;
+use darling::FromMeta;
 use proc_macro::TokenStream;
 
 use proc_macro_error::proc_macro_error;
@@ -7,9 +9,11 @@ use validation::JNIBridgeModule;
 
 use crate::derive::convert::{
     from_java_value_macro_derive, into_java_value_macro_derive, tryfrom_java_value_macro_derive,
-    tryinto_java_value_macro_derive,
+    tryinto_java_value_macro_derive, write_back_macro_derive,
 };
-use crate::transformation::ModTransformer;
+use crate::derive::exception::into_java_exception_macro_derive;
+use crate::derive::handle::native_handle_macro_derive;
+use crate::transformation::{BridgeParams, ModTransformer};
 use derive::signature::signature_macro_derive;
 
 mod derive;
@@ -19,17 +23,24 @@ mod validation;
 
 #[proc_macro_error]
 #[proc_macro_attribute]
-pub fn bridge(_args: TokenStream, raw_input: TokenStream) -> TokenStream {
+pub fn bridge(args: TokenStream, raw_input: TokenStream) -> TokenStream {
+    let params = match NestedMeta::parse_meta_list(args.into())
+        .map_err(darling::Error::from)
+        .and_then(|args| BridgeParams::from_list(&args))
+    {
+        Ok(params) => params,
+        Err(e) => return e.write_errors().into(),
+    };
     let module_data = parse_macro_input!(raw_input as JNIBridgeModule);
 
-    let mut transformer = ModTransformer::new(module_data);
+    let mut transformer = ModTransformer::new(module_data, params);
     let tokens = transformer.transform_module();
 
     tokens.into()
 }
 
 #[proc_macro_error]
-#[proc_macro_derive(Signature, attributes(package))]
+#[proc_macro_derive(Signature, attributes(package, outer_class))]
 pub fn signature_derive(raw_input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(raw_input as DeriveInput);
 
@@ -37,7 +48,10 @@ pub fn signature_derive(raw_input: TokenStream) -> TokenStream {
 }
 
 #[proc_macro_error]
-#[proc_macro_derive(IntoJavaValue, attributes(package, instance, field))]
+#[proc_macro_derive(
+    IntoJavaValue,
+    attributes(package, outer_class, instance, field, java_name, constructor)
+)]
 pub fn into_java_value_derive(raw_input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(raw_input as DeriveInput);
 
@@ -45,7 +59,10 @@ pub fn into_java_value_derive(raw_input: TokenStream) -> TokenStream {
 }
 
 #[proc_macro_error]
-#[proc_macro_derive(TryIntoJavaValue, attributes(package, instance, field))]
+#[proc_macro_derive(
+    TryIntoJavaValue,
+    attributes(package, outer_class, instance, field, java_name, constructor)
+)]
 pub fn tryinto_java_value_derive(raw_input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(raw_input as DeriveInput);
 
@@ -53,7 +70,10 @@ pub fn tryinto_java_value_derive(raw_input: TokenStream) -> TokenStream {
 }
 
 #[proc_macro_error]
-#[proc_macro_derive(FromJavaValue, attributes(package, instance, field))]
+#[proc_macro_derive(
+    FromJavaValue,
+    attributes(package, outer_class, instance, field, java_name, constructor)
+)]
 pub fn from_java_value_derive(raw_input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(raw_input as DeriveInput);
 
@@ -61,9 +81,39 @@ pub fn from_java_value_derive(raw_input: TokenStream) -> TokenStream {
 }
 
 #[proc_macro_error]
-#[proc_macro_derive(TryFromJavaValue, attributes(package, instance, field))]
+#[proc_macro_derive(
+    TryFromJavaValue,
+    attributes(package, outer_class, instance, field, java_name, constructor)
+)]
 pub fn tryfrom_java_value_derive(raw_input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(raw_input as DeriveInput);
 
     tryfrom_java_value_macro_derive(input).into()
 }
+
+#[proc_macro_error]
+#[proc_macro_derive(
+    WriteBack,
+    attributes(package, outer_class, instance, field, write_back)
+)]
+pub fn write_back_derive(raw_input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(raw_input as DeriveInput);
+
+    write_back_macro_derive(input).into()
+}
+
+#[proc_macro_error]
+#[proc_macro_derive(IntoJavaException, attributes(exception))]
+pub fn into_java_exception_derive(raw_input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(raw_input as DeriveInput);
+
+    into_java_exception_macro_derive(input).into()
+}
+
+#[proc_macro_error]
+#[proc_macro_derive(NativeHandle, attributes(package, outer_class, native_handle))]
+pub fn native_handle_derive(raw_input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(raw_input as DeriveInput);
+
+    native_handle_macro_derive(input).into()
+}
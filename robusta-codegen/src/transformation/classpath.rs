@@ -0,0 +1,482 @@
+//! Verification of imported methods and fields against compiled classes.
+//!
+//! When the `ROBUSTA_CLASSPATH` environment variable is set (in the format of `CLASSPATH`, relative paths being resolved
+//! from the crate directory), the classes of `#[package]` structs are looked up in it, and `extern "java"` methods and
+//! `#[field]`s which don't match a member of their class are reported as compile errors.
+//!
+//! Java types are inferred from Rust types when possible (see [`type_descriptor`](crate::transformation::utils::type_descriptor)): parameters and return values of other types
+//! match any Java type. Members inherited from classes that aren't in the classpath can't be checked, so no error is reported
+//! for them, except for the members of `java.lang.Object`.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::rc::Rc;
+
+use proc_macro2::Span;
+use proc_macro_error::emit_error;
+use quote::quote;
+use robusta_build::classfile::{split_method_descriptor, ClassFile, Classpath, Member, ACC_PUBLIC};
+
+pub(crate) const CLASSPATH_VAR: &str = "ROBUSTA_CLASSPATH";
+
+/// Public methods of `java.lang.Object`, which are inherited by every class.
+const OBJECT_METHODS: &[(&str, &str)] = &[
+    ("equals", "(Ljava/lang/Object;)Z"),
+    ("getClass", "()Ljava/lang/Class;"),
+    ("hashCode", "()I"),
+    ("notify", "()V"),
+    ("notifyAll", "()V"),
+    ("toString", "()Ljava/lang/String;"),
+    ("wait", "()V"),
+    ("wait", "(J)V"),
+    ("wait", "(JI)V"),
+];
+
+/// Method imported with `extern "java"`, as seen from Rust.
+pub(crate) struct ImportedMethod<'a> {
+    pub(crate) java_name: &'a str,
+    pub(crate) is_constructor: bool,
+    pub(crate) is_static: bool,
+    // descriptors of the parameters and of the return value, `None` if they can't be inferred
+    pub(crate) parameters: Vec<Option<String>>,
+    pub(crate) return_type: Option<String>,
+}
+
+/// Declaration which doesn't match the compiled class.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Mismatch {
+    message: String,
+    note: Option<String>,
+}
+
+impl Mismatch {
+    fn new(message: String) -> Self {
+        Mismatch {
+            message,
+            note: None,
+        }
+    }
+
+    fn note(self, note: impl Into<String>) -> Self {
+        Mismatch {
+            note: Some(note.into()),
+            ..self
+        }
+    }
+
+    /// Report the mismatch as a compile error.
+    pub(crate) fn emit(&self, span: Span) {
+        emit_error!(span, "{}", self.message; note =? self.note.as_ref());
+    }
+}
+
+/// Descriptor with `?` for types that can't be inferred (e.g. `(I?)V`).
+fn partial_descriptor(parameters: &[Option<String>], return_type: &Option<String>) -> String {
+    let parameters: String = parameters
+        .iter()
+        .map(|p| p.as_deref().unwrap_or("?"))
+        .collect();
+
+    format!("({}){}", parameters, return_type.as_deref().unwrap_or("?"))
+}
+
+/// Whether the inferred type `inferred` (if any) is `descriptor`.
+fn type_matches(inferred: &Option<String>, descriptor: &str) -> bool {
+    inferred.as_deref().is_none_or(|i| i == descriptor)
+}
+
+/// Members of a class with a given name, found in its hierarchy.
+struct Lookup {
+    members: Vec<Member>,
+    // whether every class of the hierarchy which can declare abstract methods could be read
+    complete: bool,
+    // interfaces of concrete classes which couldn't be read, and may only add default methods
+    unread_interfaces: Vec<String>,
+}
+
+pub(crate) struct Verifier {
+    classpath: Classpath,
+    classes: RefCell<BTreeMap<String, Option<Rc<ClassFile>>>>,
+}
+
+impl Verifier {
+    /// Verifier of the classpath of `ROBUSTA_CLASSPATH`, if set.
+    pub(crate) fn from_env() -> Option<Self> {
+        let classpath = std::env::var(CLASSPATH_VAR).ok()?;
+        let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+
+        let classpath = Classpath::parse(&classpath)
+            .entries()
+            .iter()
+            .fold(Classpath::new(), |classpath, entry| {
+                classpath.entry(Path::new(&manifest_dir).join(entry))
+            });
+
+        Some(Verifier::new(classpath))
+    }
+
+    pub(crate) fn new(classpath: Classpath) -> Self {
+        Verifier {
+            classpath,
+            classes: RefCell::new(BTreeMap::new()),
+        }
+    }
+
+    /// Class `classpath_path`, if it's in the classpath.
+    fn class(&self, classpath_path: &str) -> Result<Option<Rc<ClassFile>>, Mismatch> {
+        if let Some(class) = self.classes.borrow().get(classpath_path) {
+            return Ok(class.clone());
+        }
+
+        let class = self
+            .classpath
+            .load(classpath_path)
+            .map_err(|e| Mismatch::new(e.to_string()))?
+            .map(Rc::new);
+        self.classes
+            .borrow_mut()
+            .insert(classpath_path.to_string(), class.clone());
+
+        Ok(class)
+    }
+
+    /// Class `classpath_path`, which has to be in the classpath.
+    fn require_class(&self, classpath_path: &str) -> Result<Rc<ClassFile>, Mismatch> {
+        self.class(classpath_path)?.ok_or_else(|| {
+            Mismatch::new(format!(
+                "can't find class `{}` in `{}`",
+                classpath_path.replace('/', "."),
+                CLASSPATH_VAR
+            ))
+        })
+    }
+
+    /// Methods or fields named `name` of the class `classpath_path` and of its superclasses, and of its interfaces for methods.
+    fn lookup(&self, classpath_path: &str, name: &str, methods: bool) -> Result<Lookup, Mismatch> {
+        let mut lookup = Lookup {
+            members: Vec::new(),
+            complete: true,
+            unread_interfaces: Vec::new(),
+        };
+        // Classes to visit, and whether they are interfaces implemented by a concrete class
+        let mut pending = vec![(classpath_path.to_string(), false)];
+        let mut visited = Vec::new();
+
+        while let Some((class_name, implemented)) = pending.pop() {
+            if visited.contains(&class_name) {
+                continue;
+            }
+
+            let class = match self.class(&class_name)? {
+                Some(class) => class,
+                None if class_name == "java/lang/Object" => {
+                    if methods {
+                        lookup.members.extend(
+                            OBJECT_METHODS.iter().filter(|(n, _)| *n == name).map(
+                                |(n, descriptor)| Member {
+                                    access_flags: ACC_PUBLIC,
+                                    name: n.to_string(),
+                                    descriptor: descriptor.to_string(),
+                                },
+                            ),
+                        );
+                    }
+                    continue;
+                }
+                // Every abstract method of the interfaces of a concrete class is declared by the class or its superclasses
+                None if implemented => {
+                    lookup.unread_interfaces.push(class_name);
+                    continue;
+                }
+                None => {
+                    lookup.complete = false;
+                    continue;
+                }
+            };
+
+            let members = if methods {
+                &class.methods
+            } else {
+                &class.fields
+            };
+            lookup
+                .members
+                .extend(members.iter().filter(|m| m.name == name).cloned());
+
+            pending.extend(class.super_class.iter().map(|c| (c.clone(), implemented)));
+            if methods {
+                let implemented = implemented || !class.is_abstract();
+                pending.extend(class.interfaces.iter().map(|i| (i.clone(), implemented)));
+            }
+            visited.push(class_name);
+        }
+
+        Ok(lookup)
+    }
+
+    /// Check that `method` matches a method of the class `classpath_path`.
+    pub(crate) fn verify_method(
+        &self,
+        classpath_path: &str,
+        method: &ImportedMethod,
+    ) -> Result<(), Mismatch> {
+        let class = self.require_class(classpath_path)?;
+        let class_name = classpath_path.replace('/', ".");
+
+        // Constructors aren't inherited
+        let Lookup {
+            members,
+            complete,
+            unread_interfaces,
+        } = if method.is_constructor {
+            Lookup {
+                members: class.methods.clone(),
+                complete: true,
+                unread_interfaces: Vec::new(),
+            }
+        } else {
+            self.lookup(classpath_path, method.java_name, true)?
+        };
+        let member_name = if method.is_constructor {
+            "<init>"
+        } else {
+            method.java_name
+        };
+        let candidates: Vec<&Member> = members.iter().filter(|m| m.name == member_name).collect();
+
+        if candidates.is_empty() {
+            return if method.is_constructor {
+                Err(Mismatch::new(format!(
+                    "class `{}` has no constructor",
+                    class_name
+                )))
+            } else if complete {
+                let mismatch = Mismatch::new(format!(
+                    "can't find method `{}` in class `{}`",
+                    method.java_name, class_name
+                ));
+                if unread_interfaces.is_empty() {
+                    Err(mismatch)
+                } else {
+                    Err(mismatch.note(format!(
+                        "default methods of `{}` can't be checked, add them to `{}`",
+                        unread_interfaces.join("`, `").replace('/', "."),
+                        CLASSPATH_VAR
+                    )))
+                }
+            } else {
+                Ok(())
+            };
+        }
+
+        let same_kind: Vec<&Member> = candidates
+            .iter()
+            .copied()
+            .filter(|m| method.is_constructor || m.is_static() == method.is_static)
+            .collect();
+        if same_kind.is_empty() {
+            return if !complete {
+                Ok(())
+            } else if method.is_static {
+                Err(Mismatch::new(format!(
+                    "`{}` is an instance method of class `{}`",
+                    method.java_name, class_name
+                ))
+                .note("add a `&self` receiver"))
+            } else {
+                Err(Mismatch::new(format!(
+                    "`{}` is a static method of class `{}`",
+                    method.java_name, class_name
+                ))
+                .note("remove the receiver"))
+            };
+        }
+
+        let matches = same_kind.iter().any(|m| {
+            split_method_descriptor(&m.descriptor).is_some_and(|(parameters, return_type)| {
+                parameters.len() == method.parameters.len()
+                    && parameters
+                        .iter()
+                        .zip(&method.parameters)
+                        .all(|(p, inferred)| type_matches(inferred, p))
+                    && type_matches(&method.return_type, return_type)
+            })
+        });
+
+        if matches || !(complete || method.is_constructor) {
+            return Ok(());
+        }
+
+        let member = if method.is_constructor {
+            "constructor".to_string()
+        } else {
+            format!("method `{}`", method.java_name)
+        };
+        let available: Vec<&str> = same_kind.iter().map(|m| m.descriptor.as_str()).collect();
+
+        Err(Mismatch::new(format!(
+            "no {} of class `{}` has a signature matching `{}`",
+            member,
+            class_name,
+            partial_descriptor(&method.parameters, &method.return_type)
+        ))
+        .note(format!("available signatures: {}", available.join(", "))))
+    }
+
+    /// Check that `name` is an instance field of the class `classpath_path`, of type `inferred` if known.
+    pub(crate) fn verify_field(
+        &self,
+        classpath_path: &str,
+        name: &str,
+        inferred: Option<String>,
+    ) -> Result<(), Mismatch> {
+        self.require_class(classpath_path)?;
+
+        let class_name = classpath_path.replace('/', ".");
+        let Lookup {
+            members, complete, ..
+        } = self.lookup(classpath_path, name, false)?;
+
+        match members.iter().find(|f| !f.is_static()) {
+            None if complete => {
+                let message = if members.is_empty() {
+                    "can't find field"
+                } else {
+                    "can't find instance field"
+                };
+                Err(Mismatch::new(format!(
+                    "{} `{}` in class `{}`",
+                    message, name, class_name
+                )))
+            }
+            Some(field) if !type_matches(&inferred, &field.descriptor) => {
+                Err(Mismatch::new(format!(
+                    "field `{}` of class `{}` has type `{}`, not `{}`",
+                    name,
+                    class_name,
+                    field.descriptor,
+                    inferred.unwrap_or_default()
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Item making Cargo rebuild the crate when `ROBUSTA_CLASSPATH` changes.
+pub(crate) fn track_classpath_var() -> proc_macro2::TokenStream {
+    quote! {
+        const _: ::std::option::Option<&str> = ::std::option_env!("ROBUSTA_CLASSPATH");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn verifier() -> Verifier {
+        Verifier::new(
+            Classpath::new().entry(
+                Path::new(env!("CARGO_MANIFEST_DIR"))
+                    .join("../robusta-build/tests/fixtures/sample.jar"),
+            ),
+        )
+    }
+
+    fn method<'a>(
+        java_name: &'a str,
+        is_static: bool,
+        parameters: &[&str],
+        return_type: &str,
+    ) -> ImportedMethod<'a> {
+        let descriptor = |d: &str| Some(d.to_string()).filter(|d| d != "?");
+
+        ImportedMethod {
+            java_name,
+            is_constructor: java_name == "new",
+            is_static,
+            parameters: parameters.iter().map(|p| descriptor(p)).collect(),
+            return_type: descriptor(return_type),
+        }
+    }
+
+    #[test]
+    fn matching_methods_are_accepted() {
+        let verifier = verifier();
+        let class = "com/example/Sample";
+
+        for method in [
+            method("new", true, &[], "V"),
+            method("new", true, &["I"], "V"),
+            method(
+                "describe",
+                true,
+                &["Ljava/lang/String;", "J"],
+                "Ljava/lang/String;",
+            ),
+            method("add", false, &["I", "I"], "I"),
+            method("names", false, &["?"], "?"),
+            method("nested", false, &[], "Lcom/example/Sample$Nested;"),
+            // Inherited from `java.lang.Object`, which isn't in the classpath
+            method("hashCode", false, &[], "I"),
+        ] {
+            assert_eq!(
+                verifier.verify_method(class, &method),
+                Ok(()),
+                "{}",
+                method.java_name
+            );
+        }
+
+        assert_eq!(
+            verifier.verify_field(class, "count", Some("I".into())),
+            Ok(())
+        );
+        assert_eq!(
+            verifier.verify_field("com/example/Sample$Nested", "flag", None),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn mismatches_are_reported() {
+        let verifier = verifier();
+        let class = "com/example/Sample";
+        let message = |result: Result<(), Mismatch>| result.expect_err("no mismatch").message;
+
+        assert_eq!(
+            verifier.verify_method(class, &method("ad", false, &["I"], "I")),
+            Err(Mismatch::new("can't find method `ad` in class `com.example.Sample`".into())
+                .note("default methods of `java.lang.Runnable` can't be checked, add them to `ROBUSTA_CLASSPATH`"))
+        );
+        assert_eq!(
+            message(verifier.verify_method(class, &method("describe", false, &["?", "?"], "?"))),
+            "`describe` is a static method of class `com.example.Sample`"
+        );
+        assert_eq!(
+            message(verifier.verify_method(class, &method("add", false, &["J"], "I"))),
+            "no method `add` of class `com.example.Sample` has a signature matching `(J)I`"
+        );
+        assert_eq!(
+            message(verifier.verify_method(class, &method("new", true, &["Z"], "V"))),
+            "no constructor of class `com.example.Sample` has a signature matching `(Z)V`"
+        );
+        assert_eq!(
+            message(verifier.verify_method("com/example/Missing", &method("run", false, &[], "V"))),
+            "can't find class `com.example.Missing` in `ROBUSTA_CLASSPATH`"
+        );
+        assert_eq!(
+            message(verifier.verify_field(class, "counter", None)),
+            "can't find field `counter` in class `com.example.Sample`"
+        );
+        assert_eq!(
+            message(verifier.verify_field(class, "NAME", None)),
+            "can't find instance field `NAME` in class `com.example.Sample`"
+        );
+        assert_eq!(
+            message(verifier.verify_field(class, "count", Some("J".into()))),
+            "field `count` of class `com.example.Sample` has type `I`, not `J`"
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::{BTreeMap, BTreeSet};
+
 use crate::transformation::JavaPath;
 use syn::{LifetimeParam, Path};
 
@@ -7,4 +9,27 @@ pub(crate) struct StructContext {
     pub(crate) struct_name: String,
     pub(crate) struct_lifetimes: Vec<LifetimeParam>,
     pub(crate) package: Option<JavaPath>,
+    // enclosing class of a nested class
+    pub(crate) outer_class: Option<JavaPath>,
+    pub(crate) write_back: bool,
+    pub(crate) register_natives: bool,
+    // function of the bridge module capturing the Java VM and class loaders on the first call, relative to the struct's module;
+    // `None` if the bridge module generates a `JNI_OnLoad` capturing them
+    pub(crate) capture: Option<Path>,
+    // Java names shared by several exported methods of the struct
+    pub(crate) overloaded_methods: BTreeSet<String>,
+    // classpath path of every `package`-annotated type of the bridge module, by name
+    pub(crate) java_classes: BTreeMap<String, String>,
+    // structs of the bridge module deriving `NativeHandle`, whose references are borrowed from their Java peer
+    pub(crate) native_handles: BTreeSet<String>,
+}
+
+impl StructContext {
+    /// Classpath path of the Java class of the struct (e.g. `com/example/Outer$Inner`).
+    pub(crate) fn classpath_path(&self) -> String {
+        match &self.package {
+            Some(package) => package.class_path(self.outer_class.as_ref(), &self.struct_name),
+            None => self.struct_name.clone(),
+        }
+    }
 }
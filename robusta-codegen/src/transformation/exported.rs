@@ -1,28 +1,44 @@
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
 
-use proc_macro2::Ident;
+use proc_macro2::{Ident, TokenStream};
 use proc_macro_error::{emit_error, emit_warning};
-use quote::ToTokens;
+use quote::{quote, quote_spanned, ToTokens};
 use syn::fold::Fold;
 use syn::punctuated::Punctuated;
 use syn::spanned::Spanned;
 use syn::token::Extern;
 use syn::Lifetime;
 use syn::Token;
-use syn::{parse_quote, GenericParam, Generics, LifetimeParam, TypeTuple};
+use syn::{parse_quote, GenericParam, Generics, LifetimeParam, TypeReference, TypeTuple};
 use syn::{
-    Abi, Block, Expr, FnArg, ImplItemFn, LitStr, Pat, PatIdent, PatType, ReturnType, Signature,
-    Type, Visibility,
+    Abi, Block, Expr, FnArg, GenericArgument, ImplItemFn, LitStr, Pat, PatIdent, PatType,
+    PathArguments, ReturnType, Signature, Type, TypePath, Visibility,
 };
 
 use crate::transformation::context::StructContext;
-use crate::transformation::utils::get_call_type;
-use crate::transformation::{CallType, FreestandingTransformer, SafeParams};
+use crate::transformation::mangling::mangle;
+use crate::transformation::utils::{
+    get_call_type, get_catch_panic, get_java_method_name, get_write_back, type_descriptor,
+};
+use crate::transformation::{
+    CallType, CatchPanicParams, FreestandingTransformer, JavaPath, SafeParams,
+};
 use crate::utils::{get_abi, get_env_arg, is_self_method};
 use std::iter::FromIterator;
 
+/// An exported method to register with `RegisterNatives`.
+pub(crate) struct NativeMethod {
+    /// Name of the Java method
+    pub(crate) name: String,
+    /// Expression evaluating to the Java signature of the method
+    pub(crate) signature: TokenStream,
+    /// Name of the generated JNI function
+    pub(crate) function: Ident,
+}
+
 pub struct ExportedMethodTransformer<'ctx> {
     pub(crate) struct_context: &'ctx StructContext,
+    pub(crate) natives: Vec<NativeMethod>,
 }
 
 impl<'ctx> Fold for ExportedMethodTransformer<'ctx> {
@@ -34,9 +50,31 @@ impl<'ctx> Fold for ExportedMethodTransformer<'ctx> {
                     .map(|c| c.call_type)
                     .unwrap_or(CallType::Safe(None));
 
-                let mut jni_method_transformer =
-                    ExternJNIMethodTransformer::new(self.struct_context, call_type_attribute);
-                jni_method_transformer.fold_impl_item_fn(node)
+                let catch_panic = get_catch_panic(&node).map(|c| c.params).unwrap_or_default();
+
+                let name =
+                    get_java_method_name(&node).unwrap_or_else(|| node.sig.ident.to_string());
+                let signature = JNISignature::new(
+                    node.sig.clone(),
+                    self.struct_context,
+                    call_type_attribute.clone(),
+                )
+                .java_signature;
+
+                let mut jni_method_transformer = ExternJNIMethodTransformer::new(
+                    self.struct_context,
+                    call_type_attribute,
+                    catch_panic,
+                );
+                let transformed = jni_method_transformer.fold_impl_item_fn(node);
+
+                self.natives.push(NativeMethod {
+                    name,
+                    signature,
+                    function: transformed.sig.ident.clone(),
+                });
+
+                transformed
             }
             _ => node,
         }
@@ -46,29 +84,140 @@ impl<'ctx> Fold for ExportedMethodTransformer<'ctx> {
 struct ExternJNIMethodTransformer<'ctx> {
     struct_context: &'ctx StructContext,
     call_type: CallType,
+    catch_panic: CatchPanicParams,
 }
 
 impl<'ctx> ExternJNIMethodTransformer<'ctx> {
-    fn new(struct_context: &'ctx StructContext, call_type: CallType) -> Self {
+    fn new(
+        struct_context: &'ctx StructContext,
+        call_type: CallType,
+        catch_panic: CatchPanicParams,
+    ) -> Self {
         ExternJNIMethodTransformer {
             struct_context,
             call_type,
+            catch_panic,
         }
     }
 }
 
+impl<'ctx> ExternJNIMethodTransformer<'ctx> {
+    /// Java parameter types of an overloaded method, along with their signature guessed from their name.
+    fn overload_arguments(&self, signature: &Signature) -> Vec<(Type, String)> {
+        let (signature, _) = get_env_arg(signature.clone());
+
+        signature
+            .inputs
+            .iter()
+            .filter_map(|i| match i {
+                FnArg::Typed(PatType { pat, .. })
+                    if matches!(&**pat, Pat::Ident(PatIdent { ident, .. }) if ident == "self") =>
+                {
+                    None
+                }
+                FnArg::Typed(PatType { ty, .. }) => Some(StaticLifetimes.fold_type((**ty).clone())),
+                FnArg::Receiver(_) => None,
+            })
+            .filter_map(|ty| {
+                match type_descriptor(&ty, &self.struct_context.java_classes) {
+                    Some(signature) => Some((ty, signature)),
+                    None => {
+                        emit_error!(ty, "can't determine the Java signature of this type to name an overloaded JNI method";
+                            help = "use `#[bridge(register_natives)]` to register overloaded methods by signature instead");
+                        None
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
 impl<'ctx> Fold for ExternJNIMethodTransformer<'ctx> {
     fn fold_impl_item_fn(&mut self, node: ImplItemFn) -> ImplItemFn {
+        let java_name = get_java_method_name(&node).unwrap_or_else(|| node.sig.ident.to_string());
+        let overload_arguments = if !self.struct_context.register_natives
+            && self.struct_context.overloaded_methods.contains(&java_name)
+        {
+            Some(self.overload_arguments(&node.sig))
+        } else {
+            None
+        };
+
         let jni_signature = JNISignature::new(
             node.sig.clone(),
-            &self.struct_context,
+            self.struct_context,
             self.call_type.clone(),
         );
 
         let transformed_jni_signature = jni_signature.transformed_signature();
-        let method_call = jni_signature.signature_call();
 
-        let new_block: Block = match &self.call_type {
+        let write_back_attribute = get_write_back(&node);
+        let write_back = match (jni_signature.receiver_kind, write_back_attribute) {
+            (Some(ReceiverKind::Mutable), Some(_)) => true,
+            (Some(ReceiverKind::Mutable), None) => self.struct_context.write_back,
+            (_, Some(attr)) => {
+                emit_error!(attr, "`#[write_back]` requires a `&mut self` receiver";
+                    help = "methods taking `self` by value consume the receiver, so their changes can't be written back");
+                false
+            }
+            (Some(ReceiverKind::Owned { mutable: true }), None)
+                if self.struct_context.write_back =>
+            {
+                emit_warning!(node.sig, "changes to a `mut self` receiver are not written back to the Java instance";
+                    help = "take `&mut self` instead");
+                false
+            }
+            _ => false,
+        };
+
+        let method_call = if write_back {
+            jni_signature.signature_call_with_receiver(parse_quote! { &mut receiver })
+        } else {
+            jni_signature.signature_call()
+        };
+
+        // Failed calls aren't written back: they may have left the receiver half-updated
+        let write_back_condition = quote_spanned! { node.span() =>
+            #[allow(unused_imports)]
+            use ::robusta_jni::convert::write_back::{Fallible as _, Infallible as _};
+            let succeeded = (&&::robusta_jni::convert::write_back::Outcome(&result)).is_ok()
+                && !env.exception_check().unwrap_or(true);
+        };
+
+        let call_block: Block = match &self.call_type {
+            CallType::Unchecked { .. } if write_back => {
+                parse_quote_spanned! { node.span() => {
+                    let mut receiver = ::robusta_jni::convert::FromJavaValue::from(receiver, &env);
+                    let snapshot = ::robusta_jni::convert::WriteBack::snapshot(&receiver);
+                    #[allow(clippy::let_unit_value)]
+                    let result = #method_call;
+
+                    #write_back_condition
+                    let written_back = if succeeded {
+                        ::robusta_jni::convert::WriteBack::write_back(receiver, snapshot, &env)
+                    } else {
+                        Ok(())
+                    };
+
+                    match written_back {
+                        Ok(()) => ::robusta_jni::convert::IntoJavaValue::into(result, &env),
+                        Err(e) => {
+                            // The exception may have been thrown already, e.g. by a field conversion
+                            if !env.exception_check().unwrap_or(false) {
+                                let r = env.throw_new("java/lang/RuntimeException", format!("Write back error. Cause: {}", e));
+
+                                if let Err(e) = r {
+                                    println!("Error while throwing Java exception: {}", e);
+                                }
+                            }
+
+                            // The return value is ignored by the JVM while an exception is pending
+                            unsafe { ::std::mem::zeroed() }
+                        }
+                    }
+                }}
+            }
+
             CallType::Unchecked { .. } => {
                 parse_quote_spanned! { node.span() => {
                     ::robusta_jni::convert::IntoJavaValue::into(#method_call, &env)
@@ -139,34 +288,102 @@ impl<'ctx> Fold for ExternJNIMethodTransformer<'ctx> {
                     "java.lang.RuntimeException".parse().unwrap(),
                     "JNI call error!",
                 );
-                let (exception_class, message) = match exception_details {
+                let (exception_class, message, wrap_pending) = match exception_details {
                     Some(SafeParams {
                         exception_class,
                         message,
+                        wrap_pending,
                     }) => {
                         let exception_class_result =
                             exception_class.as_ref().unwrap_or(&default_exception_class);
                         let message_result = message.as_deref().unwrap_or(default_message);
 
-                        (exception_class_result, message_result)
+                        (
+                            exception_class_result,
+                            message_result,
+                            wrap_pending.is_present(),
+                        )
                     }
-                    None => (&default_exception_class, default_message),
+                    None => (&default_exception_class, default_message, false),
                 };
 
                 let exception_classpath_path = exception_class.to_classpath_path();
 
-                parse_quote_spanned! { node.span() => {
-                    #outer_signature {
-                        ::robusta_jni::convert::TryIntoJavaValue::try_into(#method_call, &env)
+                // A pending exception, e.g. thrown by a Java method called through an imported method, is the actual cause of the error:
+                // by default it propagates unchanged
+                let wrap_pending_exception = if wrap_pending {
+                    quote_spanned! { node.span() =>
+                        if let Err(e) = ::robusta_jni::exception::wrap_pending(&env, #exception_classpath_path, #message) {
+                            println!("Error while throwing Java exception: {}", e);
+                        }
                     }
+                } else {
+                    TokenStream::new()
+                };
+
+                // Errors not implementing `IntoJavaException` are thrown as a `RuntimeException`, see `robusta_jni::exception::dispatch`
+                let convert_call = quote_spanned! { node.span() =>
+                    (&&::robusta_jni::exception::dispatch::ReturnValue(&result)).return_kind().try_into(result, &env)
+                };
+                let returns_error = matches!(&node.sig.output, ReturnType::Type(_, ty) if matches!(&**ty, Type::Path(p) if result_ok_type(p).is_some()));
+                let convert_result = if wrap_pending && returns_error {
+                    // The exception thrown for a returned error is the one chosen by its `IntoJavaException` implementation,
+                    // so it's not wrapped
+                    quote_spanned! { node.span() =>
+                        #[allow(unused_imports)]
+                        use ::robusta_jni::exception::dispatch::{ErrorKind as _, ValueKind as _};
+                        let is_err = result.is_err();
+                        match #convert_call {
+                            Err(::robusta_jni::jni::errors::Error::JavaException) if is_err => Ok(unsafe { ::std::mem::zeroed() }),
+                            converted => converted,
+                        }
+                    }
+                } else {
+                    quote_spanned! { node.span() =>
+                        #[allow(unused_imports)]
+                        use ::robusta_jni::exception::dispatch::{ErrorKind as _, ValueKind as _};
+                        #convert_call
+                    }
+                };
+
+                let outer_block: Block = if write_back {
+                    parse_quote_spanned! { node.span() => {
+                        let mut receiver = ::robusta_jni::convert::TryFromJavaValue::try_from(receiver, &env)?;
+                        let snapshot = ::robusta_jni::convert::WriteBack::snapshot(&receiver);
+                        #[allow(clippy::let_unit_value)]
+                        let result = #method_call;
+
+                        #write_back_condition
+                        if succeeded {
+                            ::robusta_jni::convert::WriteBack::write_back(receiver, snapshot, &env)?;
+                        }
+
+                        #convert_result
+                    }}
+                } else {
+                    parse_quote_spanned! { node.span() => {
+                        #[allow(clippy::let_unit_value)]
+                        let result = #method_call;
+
+                        #convert_result
+                    }}
+                };
+
+                parse_quote_spanned! { node.span() => {
+                    #outer_signature #outer_block
 
                     match outer(#outer_call_inputs) {
                         Ok(result) => result,
                         Err(e) => {
-                            let r = env.throw_new(#exception_classpath_path, format!("{}. Cause: {}", #message, e));
+                            #wrap_pending_exception
+
+                            // The exception may have been thrown already, e.g. by an `IntoJavaException` error
+                            if !env.exception_check().unwrap_or(false) {
+                                let r = env.throw_new(#exception_classpath_path, format!("{}. Cause: {}", #message, e));
 
-                            if let Err(e) = r {
-                                println!("Error while throwing Java exception: {}", e);
+                                if let Err(e) = r {
+                                    println!("Error while throwing Java exception: {}", e);
+                                }
                             }
 
                             /* We never hand out Rust references and the object returned is ignored
@@ -182,14 +399,76 @@ impl<'ctx> Fold for ExternJNIMethodTransformer<'ctx> {
             }
         };
 
-        let no_mangle = parse_quote! { #[no_mangle] };
+        // Capture the Java VM and the class loaders of the bridge module for threads spawned from Rust,
+        // see `robusta_jni::vm` and `robusta_jni::class_loader`. A generated `JNI_OnLoad` captures them already.
+        let call_block: Block = match &self.struct_context.capture {
+            None => call_block,
+            Some(capture) => parse_quote_spanned! { node.span() => {
+                #capture(&env);
+
+                #call_block
+            }},
+        };
+
+        let new_block: Block = if self.catch_panic.disable.is_present() {
+            call_block
+        } else {
+            let default_exception_class: JavaPath = "java.lang.Error".parse().unwrap();
+            let exception_classpath_path = self
+                .catch_panic
+                .exception_class
+                .as_ref()
+                .unwrap_or(&default_exception_class)
+                .to_classpath_path();
+            let capture_backtrace = self.catch_panic.backtrace.is_present();
+
+            parse_quote_spanned! { node.span() => {
+                match ::robusta_jni::panic::catch_unwind(#capture_backtrace, || #call_block) {
+                    Ok(result) => result,
+                    Err(panic) => {
+                        ::robusta_jni::panic::throw_panic(&env, #exception_classpath_path, panic);
+
+                        // Same as above: the return value is ignored by the JVM while an exception is pending
+                        unsafe { ::std::mem::zeroed() }
+                    }
+                }
+            }}
+        };
+
+        // The overloaded JNI name is built from signatures guessed at expansion time, so they are checked against the actual ones
+        let new_block: Block = match &overload_arguments {
+            Some(arguments) => {
+                let signature_checks = arguments.iter().map(|(ty, signature)| {
+                    quote_spanned! { ty.span() =>
+                        const _: () = assert!(
+                            ::robusta_jni::convert::signature_eq(<#ty as ::robusta_jni::convert::Signature>::SIG_TYPE, #signature),
+                            concat!("the Java signature of `", stringify!(#ty), "` doesn't match the one used in the overloaded JNI method name (", #signature, ")")
+                        );
+                    }
+                });
+
+                parse_quote_spanned! { node.span() => {
+                    #(#signature_checks)*
+
+                    #new_block
+                }}
+            }
+            None => new_block,
+        };
+
         let impl_item_attributes = {
             let mut attributes = node.attrs.clone();
-            attributes.push(no_mangle);
+            // Registered methods are looked up by `JNI_OnLoad`, not by symbol name
+            if !self.struct_context.register_natives {
+                attributes.push(parse_quote! { #[no_mangle] });
+            }
 
             let discarded_known_attributes: HashSet<&str> = {
                 let mut h = HashSet::new();
                 h.insert("call_type");
+                h.insert("catch_panic");
+                h.insert("write_back");
+                h.insert("java_name");
                 h
             };
 
@@ -207,43 +486,47 @@ impl<'ctx> Fold for ExternJNIMethodTransformer<'ctx> {
             attrs: impl_item_attributes,
             vis: Visibility::Public(Token![pub](node_span)),
             defaultness: node.defaultness,
-            sig: self.fold_signature(node.sig),
+            sig: self.transform_signature(node.sig, &java_name, overload_arguments.as_deref()),
             block: new_block,
         }
     }
+}
 
+impl<'ctx> ExternJNIMethodTransformer<'ctx> {
     /// Transform original signature in JNI-ready one, including JClass and JNIEnv parameters into the function signature.
-    fn fold_signature(&mut self, node: Signature) -> Signature {
+    ///
+    /// Overloaded methods get the long JNI name, suffixed with the signature of their `overload_arguments`.
+    fn transform_signature(
+        &mut self,
+        node: Signature,
+        java_name: &str,
+        overload_arguments: Option<&[(Type, String)]>,
+    ) -> Signature {
         let jni_signature =
-            JNISignature::new(node.clone(), &self.struct_context, self.call_type.clone());
+            JNISignature::new(node.clone(), self.struct_context, self.call_type.clone());
 
         let mut sig = jni_signature.transformed_signature;
 
-        if sig.ident.to_string().contains('_') {
-            emit_error!(sig.ident, "JNI methods cannot contain `_` character");
-        }
-
-        let jni_method_name = {
-            let snake_case_package = self
-                .struct_context
-                .package
-                .as_ref()
-                .map(|s| s.to_snake_case())
-                .unwrap_or_else(|| "".into());
-
-            [
-                "Java",
-                &snake_case_package,
-                &self.struct_context.struct_name,
-                &sig.ident.to_string(),
-            ]
-            .iter()
-            .filter(|s| !s.is_empty())
-            .map(|s| s.to_owned())
-            .collect::<Vec<_>>()
-            .join("_")
+        // Registered methods are not looked up by name, so the Rust name is enough to tell overloads apart
+        let method_name = if self.struct_context.register_natives {
+            mangle(&sig.ident.to_string())
+        } else {
+            match overload_arguments {
+                Some(arguments) => {
+                    let arguments_signature: String =
+                        arguments.iter().map(|(_, s)| s.as_str()).collect();
+                    format!("{}__{}", mangle(java_name), mangle(&arguments_signature))
+                }
+                None => mangle(java_name),
+            }
         };
 
+        let jni_method_name = format!(
+            "Java_{}_{}",
+            mangle(&self.struct_context.classpath_path()),
+            method_name
+        );
+
         sig.inputs = {
             let mut res = Punctuated::new();
             res.push(parse_quote!(env: ::robusta_jni::jni::JNIEnv<'env>));
@@ -266,37 +549,175 @@ impl<'ctx> Fold for ExternJNIMethodTransformer<'ctx> {
     }
 }
 
+/// Native handle borrowed from its Java peer by a `&self`/`&mut self` receiver or a `&T`/`&mut T` parameter.
+#[derive(Clone)]
+struct HandleBorrow {
+    handle_type: Type,
+    mutable: bool,
+}
+
+impl HandleBorrow {
+    fn of(ty: &Type, native_handles: &BTreeSet<String>) -> Option<HandleBorrow> {
+        match ty {
+            Type::Reference(TypeReference {
+                elem, mutability, ..
+            }) => match &**elem {
+                Type::Path(p)
+                    if p.qself.is_none()
+                        && p.path
+                            .segments
+                            .last()
+                            .is_some_and(|s| native_handles.contains(&s.ident.to_string())) =>
+                {
+                    Some(HandleBorrow {
+                        handle_type: (**elem).clone(),
+                        mutable: mutability.is_some(),
+                    })
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
 #[cfg(test)]
+#[allow(clippy::items_after_test_module)]
 mod test {
     use std::str::FromStr;
 
     use proc_macro2::TokenStream;
+    use syn::punctuated::Punctuated;
+    use syn::visit::{self, Visit};
+    use syn::{ExprCall, ExprIf, ExprLit, ExprMethodCall, Lit, Macro, Token};
+
+    use darling::util::Flag;
 
     use super::*;
-    use crate::transformation::JavaPath;
+
+    /// Exported method transformation of a struct without package, to be customized by each test.
+    struct Setup {
+        context: StructContext,
+        call_type: CallType,
+        catch_panic: CatchPanicParams,
+    }
+
+    impl Setup {
+        fn new(struct_name: &str) -> Self {
+            Setup {
+                context: StructContext {
+                    struct_type: syn::parse_str(struct_name).unwrap(),
+                    struct_name: struct_name.into(),
+                    struct_lifetimes: vec![],
+                    package: None,
+                    outer_class: None,
+                    write_back: false,
+                    register_natives: false,
+                    capture: Some(parse_quote! { self::__robusta_capture }),
+                    overloaded_methods: Default::default(),
+                    java_classes: Default::default(),
+                    native_handles: Default::default(),
+                },
+                call_type: CallType::Safe(None),
+                catch_panic: CatchPanicParams::default(),
+            }
+        }
+
+        fn package(mut self, package: &str) -> Self {
+            self.context.package = Some(JavaPath::from_str(package).unwrap());
+            self
+        }
+
+        fn call_type(mut self, call_type: CallType) -> Self {
+            self.call_type = call_type;
+            self
+        }
+
+        fn transform(&self, method: ImplItemFn) -> ImplItemFn {
+            let mut transformer = ExternJNIMethodTransformer {
+                struct_context: &self.context,
+                call_type: self.call_type.clone(),
+                catch_panic: self.catch_panic.clone(),
+            };
+
+            transformer.fold_impl_item_fn(method)
+        }
+    }
+
+    /// Function calls (by path, e.g. `::robusta_jni::panic::catch_unwind`) and method calls (by name, e.g. `throw_new`)
+    /// of a generated block, with their arguments, in order.
+    #[derive(Default)]
+    struct Calls(Vec<(String, Vec<Expr>)>);
+
+    impl Calls {
+        fn of(block: &Block) -> Self {
+            let mut calls = Calls::default();
+            calls.visit_block(block);
+            calls
+        }
+
+        fn contains(&self, name: &str) -> bool {
+            self.0.iter().any(|(n, _)| n == name)
+        }
+
+        /// Arguments of the first call to `name`.
+        fn args(&self, name: &str) -> &[Expr] {
+            self.0
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, args)| args.as_slice())
+                .unwrap_or_else(|| panic!("no call to `{}`", name))
+        }
+    }
+
+    impl<'ast> Visit<'ast> for Calls {
+        fn visit_expr_call(&mut self, call: &'ast ExprCall) {
+            if let Expr::Path(p) = &*call.func {
+                let path = p.path.to_token_stream().to_string().replace(' ', "");
+                self.0.push((path, call.args.iter().cloned().collect()));
+            }
+            visit::visit_expr_call(self, call);
+        }
+
+        fn visit_expr_method_call(&mut self, call: &'ast ExprMethodCall) {
+            self.0
+                .push((call.method.to_string(), call.args.iter().cloned().collect()));
+            visit::visit_expr_method_call(self, call);
+        }
+
+        // The arguments of assertions are expressions too
+        fn visit_macro(&mut self, mac: &'ast Macro) {
+            if mac.path.is_ident("assert") {
+                let args = mac
+                    .parse_body_with(Punctuated::<Expr, Token![,]>::parse_terminated)
+                    .unwrap();
+                args.iter().for_each(|arg| self.visit_expr(arg));
+            }
+        }
+    }
+
+    fn str_lit(expr: &Expr) -> String {
+        match expr {
+            Expr::Lit(ExprLit {
+                lit: Lit::Str(s), ..
+            }) => s.value(),
+            _ => panic!("not a string literal: {}", expr.to_token_stream()),
+        }
+    }
 
     fn setup_package(
         package: Option<JavaPath>,
         struct_name: String,
         method_name: String,
     ) -> ImplItemFn {
-        let struct_name_token_stream = TokenStream::from_str(&struct_name).unwrap();
         let method_name_token_stream = TokenStream::from_str(&method_name).unwrap();
 
         let method: ImplItemFn =
             parse_quote! { pub extern "jni" fn #method_name_token_stream() {} };
-        let struct_context = StructContext {
-            struct_type: parse_quote! { #struct_name_token_stream },
-            struct_name,
-            struct_lifetimes: vec![],
-            package,
-        };
-        let mut transformer = ExternJNIMethodTransformer {
-            struct_context: &struct_context,
-            call_type: CallType::Safe(None),
-        };
+        let mut setup = Setup::new(&struct_name);
+        setup.context.package = package;
 
-        transformer.fold_impl_item_method(method)
+        setup.transform(method)
     }
 
     #[test]
@@ -324,6 +745,168 @@ mod test {
         );
     }
 
+    #[test]
+    fn jni_method_name_is_mangled() {
+        let output = setup_package(
+            Some(JavaPath::from_str("com.my_app").unwrap()),
+            "Foo".into(),
+            "get_value".into(),
+        );
+        assert_eq!(
+            output.sig.ident.to_string(),
+            "Java_com_my_1app_Foo_get_1value"
+        );
+
+        let output = setup_package(None, "Foo".into(), "caf\u{e9}".into());
+        assert_eq!(output.sig.ident.to_string(), "Java_Foo_caf_000e9");
+    }
+
+    #[test]
+    fn nested_class_jni_method_name() {
+        let mut setup = Setup::new("Inner").package("com.bar");
+        setup.context.outer_class = Some(JavaPath::from_str("Outer").unwrap());
+        assert_eq!(setup.context.classpath_path(), "com/bar/Outer$Inner");
+
+        let output = setup.transform(parse_quote! { pub extern "jni" fn foo() {} });
+        assert_eq!(
+            output.sig.ident.to_string(),
+            "Java_com_bar_Outer_00024Inner_foo"
+        );
+    }
+
+    #[test]
+    fn jni_method_captures_vm_with_function_of_module() {
+        let mut setup = Setup::new("Foo");
+        setup.context.capture = Some(parse_quote! { self::super::__robusta_capture });
+
+        let output = setup.transform(parse_quote! { pub extern "jni" fn foo() {} });
+        let env: Expr = parse_quote! { &env };
+        assert_eq!(
+            Calls::of(&output.block).args("self::super::__robusta_capture"),
+            &[env]
+        );
+    }
+
+    #[test]
+    fn jni_method_catches_panics_by_default() {
+        let output = setup_package(None, "Foo".into(), "foo".into());
+        let calls = Calls::of(&output.block);
+
+        assert!(calls.contains("::robusta_jni::panic::catch_unwind"));
+        assert_eq!(
+            str_lit(&calls.args("::robusta_jni::panic::throw_panic")[1]),
+            "java/lang/Error"
+        );
+    }
+
+    #[test]
+    fn jni_method_catches_panics_with_custom_exception() {
+        let mut setup = Setup::new("Foo");
+        setup.catch_panic.exception_class =
+            Some(JavaPath::from_str("java.lang.IllegalStateException").unwrap());
+        let output = setup.transform(parse_quote! { pub extern "jni" fn foo() {} });
+
+        assert_eq!(
+            str_lit(&Calls::of(&output.block).args("::robusta_jni::panic::throw_panic")[1]),
+            "java/lang/IllegalStateException"
+        );
+    }
+
+    #[test]
+    fn jni_method_panic_catching_can_be_disabled() {
+        let mut setup = Setup::new("Foo");
+        setup.catch_panic.disable = Flag::present();
+        let output = setup.transform(parse_quote! { pub extern "jni" fn foo() {} });
+
+        assert!(!Calls::of(&output.block).contains("::robusta_jni::panic::catch_unwind"));
+    }
+
+    fn setup_with_safe_params(safe_params: SafeParams) -> ImplItemFn {
+        Setup::new("Foo")
+            .call_type(CallType::Safe(Some(safe_params)))
+            .transform(parse_quote! { pub extern "jni" fn foo() -> ::robusta_jni::jni::errors::Result<i32> { Ok(0) } })
+    }
+
+    #[test]
+    fn jni_method_lets_pending_exceptions_propagate_by_default() {
+        let output = setup_with_safe_params(SafeParams::default());
+        let calls = Calls::of(&output.block);
+
+        assert!(calls.contains("exception_check"));
+        assert!(!calls.contains("::robusta_jni::exception::wrap_pending"));
+    }
+
+    #[test]
+    fn jni_method_wraps_pending_exceptions() {
+        let output = setup_with_safe_params(SafeParams {
+            exception_class: Some(JavaPath::from_str("java.lang.IllegalStateException").unwrap()),
+            wrap_pending: Flag::present(),
+            ..Default::default()
+        });
+
+        assert_eq!(
+            str_lit(&Calls::of(&output.block).args("::robusta_jni::exception::wrap_pending")[1]),
+            "java/lang/IllegalStateException"
+        );
+    }
+
+    #[test]
+    fn jni_method_doesnt_wrap_exceptions_of_returned_errors() {
+        let setup = Setup::new("Foo").call_type(CallType::Safe(Some(SafeParams {
+            wrap_pending: Flag::present(),
+            ..Default::default()
+        })));
+
+        let output = setup.transform(parse_quote! {
+            pub extern "jni" fn foo() -> Result<i32, Error> {}
+        });
+        let calls = Calls::of(&output.block);
+        assert!(calls.contains("::robusta_jni::exception::wrap_pending"));
+        assert!(calls.contains("is_err"));
+
+        let output = setup.transform(parse_quote! {
+            pub extern "jni" fn foo() -> ::robusta_jni::jni::errors::Result<i32> {}
+        });
+        assert!(!Calls::of(&output.block).contains("is_err"));
+    }
+
+    #[test]
+    fn jni_method_returns_ok_type_of_result() {
+        let output = Setup::new("Foo").transform(
+            parse_quote! { pub extern "jni" fn foo() -> Result<i32, ConfigError> { Ok(0) } },
+        );
+
+        let return_type: ReturnType =
+            parse_quote! { -> <i32 as ::robusta_jni::convert::TryIntoJavaValue<'env>>::Target };
+        assert_eq!(
+            output.sig.output.to_token_stream().to_string(),
+            return_type.to_token_stream().to_string()
+        );
+        assert!(
+            Calls::of(&output.block).contains("::robusta_jni::exception::dispatch::ReturnValue")
+        );
+    }
+
+    #[test]
+    fn jni_method_captures_vm_inside_panic_guard() {
+        let output = setup_package(None, "Foo".into(), "foo".into());
+        let calls = Calls::of(&output.block);
+        let guarded = &calls.args("::robusta_jni::panic::catch_unwind")[1];
+
+        let mut guarded_calls = Calls::default();
+        guarded_calls.visit_expr(guarded);
+        assert!(guarded_calls.contains("self::__robusta_capture"));
+    }
+
+    #[test]
+    fn jni_method_skips_capture_with_on_load() {
+        let mut setup = Setup::new("Foo");
+        setup.context.capture = None;
+        let output = setup.transform(parse_quote! { pub extern "jni" fn foo() {} });
+
+        assert!(!Calls::of(&output.block).contains("self::__robusta_capture"));
+    }
+
     #[test]
     fn jni_method_has_no_mangle() {
         let output = setup_package(None, "Foo".into(), "foo".into());
@@ -337,28 +920,82 @@ mod test {
         assert_eq!(output.sig.abi.unwrap().name.unwrap().value(), "system")
     }
 
+    #[test]
+    fn overloaded_jni_method_has_long_name() {
+        let mut setup = Setup::new("Foo").package("com.bar");
+        setup.context.overloaded_methods = std::iter::once("foo".to_string()).collect();
+
+        let output = setup.transform(parse_quote! {
+            #[java_name = "foo"]
+            pub extern "jni" fn fooWithString(env: &JNIEnv, v: i32, s: Option<String>) {}
+        });
+        assert_eq!(
+            output.sig.ident.to_string(),
+            "Java_com_bar_Foo_foo__ILjava_lang_String_2"
+        );
+        assert!(output.attrs.iter().all(|a| !a.path().is_ident("java_name")));
+
+        assert!(Calls::of(&output.block).contains("::robusta_jni::convert::signature_eq"));
+    }
+
+    fn setup_registered(method: ImplItemFn) -> (ImplItemFn, Vec<NativeMethod>) {
+        let mut setup = Setup::new("Foo<'env, 'borrow>").package("com.bar.quux");
+        setup.context.struct_name = "Foo".into();
+        setup.context.register_natives = true;
+        setup.context.capture = None;
+        let mut transformer = ExportedMethodTransformer {
+            struct_context: &setup.context,
+            natives: Vec::new(),
+        };
+
+        let output = transformer.fold_impl_item_fn(method);
+        (output, transformer.natives)
+    }
+
+    #[test]
+    fn registered_jni_method_has_no_no_mangle() {
+        let (output, natives) = setup_registered(parse_quote! { pub extern "jni" fn foo() {} });
+        let no_mangle = parse_quote! { #[no_mangle] };
+        assert!(!output.attrs.contains(&no_mangle));
+
+        match natives.as_slice() {
+            [NativeMethod { name, function, .. }] => {
+                assert_eq!(name, "foo");
+                assert_eq!(function, &output.sig.ident);
+            }
+            _ => panic!("expected one native method"),
+        }
+    }
+
+    #[test]
+    fn registered_jni_method_signature() {
+        let (_, natives) = setup_registered(parse_quote! {
+            pub extern "jni" fn foo(&self, env: &JNIEnv, v: i32, s: AutoLocal<'env, 'borrow>) -> JniResult<String> {}
+        });
+
+        let expected: TokenStream = parse_quote! {
+            ["(",
+             <i32 as ::robusta_jni::convert::Signature>::SIG_TYPE,
+             <AutoLocal<'static, 'static> as ::robusta_jni::convert::Signature>::SIG_TYPE,
+             ")",
+             <JniResult<String> as ::robusta_jni::convert::Signature>::SIG_TYPE].concat()
+        };
+        assert_eq!(natives[0].signature.to_string(), expected.to_string());
+
+        let (_, natives) = setup_registered(parse_quote! { pub extern "jni" fn foo() {} });
+        let expected: TokenStream = parse_quote! { ["(", ")", "V"].concat() };
+        assert_eq!(natives[0].signature.to_string(), expected.to_string());
+    }
+
     fn setup_with_params(params: TokenStream, struct_name: String) -> ImplItemFn {
-        let package = None;
         let method_name = "foo".to_string();
-        let struct_name_token_stream = TokenStream::from_str(&struct_name).unwrap();
         let method_name_token_stream = TokenStream::from_str(&method_name).unwrap();
 
         let method: ImplItemFn = parse_quote! {
             pub extern "jni" fn #method_name_token_stream(#params) -> i32 {}
         };
 
-        let struct_context = StructContext {
-            struct_type: parse_quote! { #struct_name_token_stream },
-            struct_name,
-            struct_lifetimes: vec![],
-            package,
-        };
-        let mut transformer = ExternJNIMethodTransformer {
-            struct_context: &struct_context,
-            call_type: CallType::Safe(None),
-        };
-
-        transformer.fold_impl_item_method(method)
+        Setup::new(&struct_name).transform(method)
     }
 
     #[test]
@@ -399,7 +1036,7 @@ mod test {
                 );
             }
 
-            _ => assert!(false),
+            _ => panic!("unexpected JNI method parameters"),
         }
     }
 
@@ -444,15 +1081,194 @@ mod test {
                 );
             }
 
-            _ => assert!(false),
+            _ => panic!("unexpected JNI method parameters"),
+        }
+    }
+
+    fn setup_with_write_back(struct_write_back: bool) -> Setup {
+        let mut setup = Setup::new("Foo");
+        setup.context.write_back = struct_write_back;
+        setup
+    }
+
+    #[test]
+    fn borrowed_receiver_is_converted_from_owned_value() {
+        let output = setup_with_write_back(false)
+            .transform(parse_quote! { pub extern "jni" fn foo(&mut self) {} });
+
+        let self_conv_type: Type = parse_quote! { <Foo as ::robusta_jni::convert::TryFromJavaValue<'env, 'borrow>>::Source };
+        match output.sig.inputs.iter().nth(1) {
+            Some(FnArg::Typed(PatType { ty, .. })) => assert_eq!(
+                ty.to_token_stream().to_string(),
+                self_conv_type.to_token_stream().to_string()
+            ),
+            _ => panic!("unexpected JNI method parameters"),
+        }
+
+        let calls = Calls::of(&output.block);
+        assert_eq!(
+            calls.args("::robusta_jni::convert::TryFromJavaValue::try_from")[0],
+            parse_quote! { receiver }
+        );
+        assert!(!calls.contains("::robusta_jni::convert::WriteBack::write_back"));
+    }
+
+    #[test]
+    fn write_back_method_attribute() {
+        let output = setup_with_write_back(false)
+            .transform(parse_quote! { #[write_back] pub extern "jni" fn foo(&mut self) {} });
+
+        assert!(Calls::of(&output.block).contains("::robusta_jni::convert::WriteBack::write_back"));
+        assert!(output
+            .attrs
+            .iter()
+            .all(|a| !a.path().is_ident("write_back")));
+    }
+
+    /// Branch of the `if succeeded` expression of a generated block.
+    fn succeeded_branch(block: &Block) -> Block {
+        struct SucceededBranch(Option<Block>);
+
+        impl<'ast> Visit<'ast> for SucceededBranch {
+            fn visit_expr_if(&mut self, expr: &'ast ExprIf) {
+                if *expr.cond == parse_quote! { succeeded } {
+                    self.0 = Some(expr.then_branch.clone());
+                }
+                visit::visit_expr_if(self, expr);
+            }
+        }
+
+        let mut branch = SucceededBranch(None);
+        branch.visit_block(block);
+        branch.0.expect("no `if succeeded` branch")
+    }
+
+    #[test]
+    fn write_back_only_after_success() {
+        for call_type in [
+            CallType::Safe(None),
+            CallType::Unchecked(Default::default()),
+        ] {
+            let output = setup_with_write_back(false)
+                .call_type(call_type)
+                .transform(parse_quote! { #[write_back] pub extern "jni" fn foo(&mut self) -> Result<(), Error> {} });
+            let calls = Calls::of(&output.block);
+
+            assert!(calls.contains("::robusta_jni::convert::write_back::Outcome"));
+            assert!(calls.contains("exception_check"));
+            assert!(Calls::of(&succeeded_branch(&output.block))
+                .contains("::robusta_jni::convert::WriteBack::write_back"));
         }
     }
+
+    #[test]
+    fn write_back_compares_with_snapshot() {
+        let output = setup_with_write_back(false)
+            .transform(parse_quote! { #[write_back] pub extern "jni" fn foo(&mut self) {} });
+        let calls = Calls::of(&output.block);
+
+        let snapshot_args: Vec<Expr> = vec![parse_quote! { &receiver }];
+        assert_eq!(
+            calls.args("::robusta_jni::convert::WriteBack::snapshot"),
+            snapshot_args.as_slice()
+        );
+        let write_back_args: Vec<Expr> = vec![
+            parse_quote! { receiver },
+            parse_quote! { snapshot },
+            parse_quote! { &env },
+        ];
+        assert_eq!(
+            calls.args("::robusta_jni::convert::WriteBack::write_back"),
+            write_back_args.as_slice()
+        );
+    }
+
+    #[test]
+    fn unchecked_write_back_errors_are_thrown() {
+        let output = setup_with_write_back(false)
+            .call_type(CallType::Unchecked(Default::default()))
+            .transform(parse_quote! { #[write_back] pub extern "jni" fn foo(&mut self) {} });
+        let calls = Calls::of(&output.block);
+
+        assert!(calls.contains("::robusta_jni::convert::WriteBack::write_back"));
+        assert_eq!(
+            str_lit(&calls.args("throw_new")[0]),
+            "java/lang/RuntimeException"
+        );
+        assert!(!calls.contains("unwrap"));
+    }
+
+    #[test]
+    fn write_back_struct_attribute() {
+        let setup = setup_with_write_back(true);
+
+        let output = setup.transform(parse_quote! { pub extern "jni" fn foo(&mut self) {} });
+        assert!(Calls::of(&output.block).contains("::robusta_jni::convert::WriteBack::write_back"));
+
+        let output = setup.transform(parse_quote! { pub extern "jni" fn foo(&self) {} });
+        let calls = Calls::of(&output.block);
+        assert!(!calls.contains("::robusta_jni::convert::WriteBack::snapshot"));
+        assert!(!calls.contains("::robusta_jni::convert::WriteBack::write_back"));
+    }
+
+    #[test]
+    fn native_handles_are_borrowed_from_peer() {
+        let mut setup = Setup::new("Foo");
+        setup.context.native_handles = ["Foo".to_string(), "Bar".to_string()].into();
+        let output = setup.transform(
+            parse_quote! { pub extern "jni" fn foo(&mut self, bar: &Bar, owned: Bar) {} },
+        );
+
+        let conv_types: Vec<String> = output
+            .sig
+            .inputs
+            .iter()
+            .skip(1)
+            .map(|i| match i {
+                FnArg::Typed(PatType { ty, .. }) => ty.to_token_stream().to_string(),
+                _ => panic!("unexpected JNI method parameters"),
+            })
+            .collect();
+        let expected: Vec<Type> = vec![
+            parse_quote! { <Foo as ::robusta_jni::convert::TryFromJavaValue<'env, 'borrow>>::Source },
+            parse_quote! { <Bar as ::robusta_jni::convert::TryFromJavaValue<'env, 'borrow>>::Source },
+            parse_quote! { <Bar as ::robusta_jni::convert::TryFromJavaValue<'env, 'borrow>>::Source },
+        ];
+        assert_eq!(
+            conv_types,
+            expected
+                .iter()
+                .map(|t| t.to_token_stream().to_string())
+                .collect::<Vec<_>>()
+        );
+
+        let calls = Calls::of(&output.block);
+        let receiver_args: Vec<Expr> = vec![parse_quote! { &env }, parse_quote! { receiver }];
+        assert_eq!(
+            calls.args("::robusta_jni::handle::borrow::<Foo>"),
+            receiver_args.as_slice()
+        );
+        let bar_args: Vec<Expr> = vec![parse_quote! { &env }, parse_quote! { bar }];
+        assert_eq!(
+            calls.args("::robusta_jni::handle::borrow::<Bar>"),
+            bar_args.as_slice()
+        );
+        assert_eq!(
+            calls.args("::robusta_jni::convert::TryFromJavaValue::try_from")[0],
+            parse_quote! { owned }
+        );
+    }
 }
 
 struct JNISignatureTransformer {
     struct_freestanding_transformer: FreestandingTransformer,
     struct_lifetimes: Vec<LifetimeParam>,
     call_type: CallType,
+    native_handles: BTreeSet<String>,
+    // handle borrowed by each input, in order
+    handle_borrows: Vec<Option<HandleBorrow>>,
+    // whether each input is a mutable (`JavaSliceMut`) or shared (`JavaSlice`) array view, in order
+    array_borrows: Vec<Option<bool>>,
 }
 
 impl JNISignatureTransformer {
@@ -460,11 +1276,15 @@ impl JNISignatureTransformer {
         struct_freestanding_transformer: FreestandingTransformer,
         struct_lifetimes: Vec<LifetimeParam>,
         call_type: CallType,
+        native_handles: BTreeSet<String>,
     ) -> Self {
         JNISignatureTransformer {
             struct_freestanding_transformer,
             struct_lifetimes,
             call_type,
+            native_handles,
+            handle_borrows: Vec::new(),
+            array_borrows: Vec::new(),
         }
     }
 
@@ -553,10 +1373,21 @@ impl JNISignatureTransformer {
 
 impl Fold for JNISignatureTransformer {
     fn fold_fn_arg(&mut self, arg: FnArg) -> FnArg {
+        let borrowed_receiver = matches!(&arg, FnArg::Receiver(r) if r.reference.is_some());
+
         match self.struct_freestanding_transformer.fold_fn_arg(arg) {
             FnArg::Receiver(_) => panic!("Bug -- please report to library author. Found receiver input after freestanding conversion"),
             FnArg::Typed(mut t) => {
-                let original_input_type = t.ty;
+                let handle_borrow = HandleBorrow::of(&t.ty, &self.native_handles);
+
+                // `&self` and `&mut self` receivers are converted to an owned value, which is then borrowed by the method call.
+                // Native handles are borrowed from their Java peer instead
+                let original_input_type = match *t.ty {
+                    Type::Reference(r) if borrowed_receiver || handle_borrow.is_some() => r.elem,
+                    ty => Box::new(ty),
+                };
+                self.handle_borrows.push(handle_borrow);
+                self.array_borrows.push(array_borrow(&original_input_type));
 
                 let jni_conversion_type: Type = match self.call_type {
                     CallType::Safe(_) => parse_quote_spanned! { original_input_type.span() => <#original_input_type as ::robusta_jni::convert::TryFromJavaValue<'env, 'borrow>>::Source },
@@ -586,10 +1417,17 @@ impl Fold for JNISignatureTransformer {
                     parse_quote_spanned! { p.span() => <#p as ::robusta_jni::convert::IntoJavaValue<'env>>::Target },
                 ),
 
-                (Type::Path(p), CallType::Safe(_)) => ReturnType::Type(
-                    *arrow,
-                    parse_quote_spanned! { p.span() => <#p as ::robusta_jni::convert::TryIntoJavaValue<'env>>::Target },
-                ),
+                // `Result<T, E>` is returned as `T`, including when `E` doesn't implement `IntoJavaException`
+                (Type::Path(p), CallType::Safe(_)) => match result_ok_type(p) {
+                    Some(t) => ReturnType::Type(
+                        *arrow,
+                        parse_quote_spanned! { p.span() => <#t as ::robusta_jni::convert::TryIntoJavaValue<'env>>::Target },
+                    ),
+                    None => ReturnType::Type(
+                        *arrow,
+                        parse_quote_spanned! { p.span() => <#p as ::robusta_jni::convert::TryIntoJavaValue<'env>>::Target },
+                    ),
+                },
 
                 (Type::Reference(r), CallType::Unchecked { .. }) => ReturnType::Type(
                     *arrow,
@@ -628,12 +1466,118 @@ impl Fold for JNISignatureTransformer {
     }
 }
 
+/// How the receiver of a `self` method is passed to the method.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ReceiverKind {
+    /// `self` or `mut self`
+    Owned { mutable: bool },
+    /// `&self`
+    Shared,
+    /// `&mut self`
+    Mutable,
+}
+
+impl ReceiverKind {
+    fn of(signature: &Signature) -> Option<ReceiverKind> {
+        signature.inputs.iter().find_map(|i| match i {
+            FnArg::Receiver(r) => Some(match (&r.reference, &r.mutability) {
+                (Some(_), Some(_)) => ReceiverKind::Mutable,
+                (Some(_), None) => ReceiverKind::Shared,
+                (None, mutability) => ReceiverKind::Owned {
+                    mutable: mutability.is_some(),
+                },
+            }),
+            FnArg::Typed(t) => match &*t.pat {
+                Pat::Ident(PatIdent {
+                    ident, mutability, ..
+                }) if ident == "self" => Some(ReceiverKind::Owned {
+                    mutable: mutability.is_some(),
+                }),
+                _ => None,
+            },
+        })
+    }
+}
+
+/// Whether `ty` is a mutable (`JavaSliceMut`) or shared (`JavaSlice`) view of a Java array.
+fn array_borrow(ty: &Type) -> Option<bool> {
+    match ty {
+        Type::Path(TypePath { qself: None, path }) => match path.segments.last() {
+            Some(s) if s.ident == "JavaSliceMut" => Some(true),
+            Some(s) if s.ident == "JavaSlice" => Some(false),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// `T` if `path` is a `Result<T, E>`.
+fn result_ok_type(path: &TypePath) -> Option<&Type> {
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Result" {
+        return None;
+    }
+
+    match &segment.arguments {
+        PathArguments::AngleBracketed(arguments) if arguments.args.len() == 2 => {
+            match arguments.args.first() {
+                Some(GenericArgument::Type(t)) => Some(t),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Replaces every lifetime with `'static`, so that a type can be named outside of the generics of its method.
+struct StaticLifetimes;
+
+impl Fold for StaticLifetimes {
+    fn fold_lifetime(&mut self, lifetime: Lifetime) -> Lifetime {
+        Lifetime::new("'static", lifetime.span())
+    }
+}
+
+/// Expression building the Java signature of a method from the `Signature` of its parameter and return types.
+/// `signature` must not include the `env` parameter.
+fn java_signature(signature: &Signature) -> TokenStream {
+    let input_types: Vec<Type> = signature
+        .inputs
+        .iter()
+        .filter_map(|i| match i {
+            FnArg::Typed(PatType { pat, .. })
+                if matches!(&**pat, Pat::Ident(PatIdent { ident, .. }) if ident == "self") =>
+            {
+                None
+            }
+            FnArg::Typed(PatType { ty, .. }) => Some(StaticLifetimes.fold_type((**ty).clone())),
+            FnArg::Receiver(_) => None,
+        })
+        .collect();
+
+    let output_signature = match &signature.output {
+        ReturnType::Default => quote! { "V" },
+        ReturnType::Type(_, ty) => {
+            let ty = StaticLifetimes.fold_type((**ty).clone());
+            quote_spanned! { ty.span() => <#ty as ::robusta_jni::convert::Signature>::SIG_TYPE }
+        }
+    };
+
+    quote_spanned! { signature.span() =>
+        ["(", #(<#input_types as ::robusta_jni::convert::Signature>::SIG_TYPE,)* ")", #output_signature].concat()
+    }
+}
+
 struct JNISignature {
     transformed_signature: Signature,
+    java_signature: TokenStream,
     call_type: CallType,
     struct_name: String,
     self_method: bool,
+    receiver_kind: Option<ReceiverKind>,
     env_arg: Option<FnArg>,
+    handle_borrows: Vec<Option<HandleBorrow>>,
+    array_borrows: Vec<Option<bool>>,
 }
 
 impl JNISignature {
@@ -648,19 +1592,26 @@ impl JNISignature {
             freestanding_transformer,
             struct_context.struct_lifetimes.clone(),
             call_type.clone(),
+            struct_context.native_handles.clone(),
         );
 
         let self_method = is_self_method(&signature);
+        let receiver_kind = ReceiverKind::of(&signature);
         let (transformed_signature, env_arg) = get_env_arg(signature);
+        let java_signature = java_signature(&transformed_signature);
 
         let transformed_signature = jni_signature_transformer.fold_signature(transformed_signature);
 
         JNISignature {
             transformed_signature,
+            java_signature,
             call_type,
             struct_name: struct_context.struct_name.clone(),
             self_method,
+            receiver_kind,
             env_arg,
+            handle_borrows: jni_signature_transformer.handle_borrows,
+            array_borrows: jni_signature_transformer.array_borrows,
         }
     }
 
@@ -673,18 +1624,51 @@ impl JNISignature {
     }
 
     fn signature_call(&self) -> Expr {
+        self.method_call(None)
+    }
+
+    /// Call the method passing an already converted receiver.
+    fn signature_call_with_receiver(&self, receiver: Expr) -> Expr {
+        self.method_call(Some(receiver))
+    }
+
+    fn method_call(&self, mut receiver: Option<Expr>) -> Expr {
         let method_call_inputs: Punctuated<Expr, Token![,]> = {
             let mut result: Vec<_> = self.args_iter()
-                .map(|p| {
+                .enumerate()
+                .map(|(idx, p)| {
                     match p.pat.as_ref() {
                         Pat::Ident(PatIdent { ident, .. }) => {
+                            let is_receiver = self.self_method && idx == 0;
+                            if let (true, Some(r)) = (is_receiver, receiver.take()) {
+                                return r;
+                            }
+
+                            if let Some(HandleBorrow { handle_type, mutable }) = &self.handle_borrows[idx] {
+                                let borrowed: Expr = match self.call_type {
+                                    CallType::Safe(_) => parse_quote_spanned! { ident.span() => ::robusta_jni::handle::borrow::<#handle_type>(&env, #ident)? },
+                                    CallType::Unchecked { .. } => parse_quote_spanned! { ident.span() => ::robusta_jni::handle::borrow::<#handle_type>(&env, #ident).unwrap() },
+                                };
+
+                                return if *mutable {
+                                    parse_quote_spanned! { ident.span() => &mut *#borrowed }
+                                } else {
+                                    parse_quote_spanned! { ident.span() => &*#borrowed }
+                                };
+                            }
+
                             let input_param: Expr = {
                                 match self.call_type {
                                     CallType::Safe(_) => parse_quote_spanned! { ident.span() => ::robusta_jni::convert::TryFromJavaValue::try_from(#ident, &env)? },
                                     CallType::Unchecked { .. } => parse_quote_spanned! { ident.span() => ::robusta_jni::convert::FromJavaValue::from(#ident, &env) }
                                 }
                             };
-                            input_param
+
+                            match self.receiver_kind {
+                                Some(ReceiverKind::Shared) if is_receiver => parse_quote_spanned! { ident.span() => &#input_param },
+                                Some(ReceiverKind::Mutable) if is_receiver => parse_quote_spanned! { ident.span() => &mut #input_param },
+                                _ => input_param,
+                            }
                         }
                         _ => panic!("Bug -- please report to library author. Found non-ident FnArg pattern")
                     }
@@ -697,15 +1681,41 @@ impl JNISignature {
                 result.insert(idx, parse_quote_spanned!(env_span => &env));
             }
 
-            Punctuated::from_iter(result.into_iter())
+            Punctuated::from_iter(result)
         };
 
         let signature_span = self.transformed_signature.span();
         let struct_name = Ident::new(&self.struct_name, signature_span);
         let method_name = self.transformed_signature.ident.clone();
 
-        parse_quote_spanned! { signature_span =>
-            #struct_name::#method_name(#method_call_inputs)
+        // Views of the same pinned array would alias, see `robusta_jni::convert::slice`
+        let arrays: Vec<(&Ident, bool)> = self
+            .args_iter()
+            .zip(&self.array_borrows)
+            .filter_map(|(p, borrow)| match (p.pat.as_ref(), borrow) {
+                (Pat::Ident(PatIdent { ident, .. }), Some(mutable)) => Some((ident, *mutable)),
+                _ => None,
+            })
+            .collect();
+
+        if arrays.len() > 1 && arrays.iter().any(|(_, mutable)| *mutable) {
+            let (idents, mutable): (Vec<_>, Vec<_>) = arrays.into_iter().unzip();
+            let check = quote_spanned! { signature_span =>
+                ::robusta_jni::convert::check_distinct_arrays(&env, &[#((#idents, #mutable)),*])
+            };
+            let check = match self.call_type {
+                CallType::Safe(_) => quote_spanned! { signature_span => #check? },
+                CallType::Unchecked { .. } => quote_spanned! { signature_span => #check.unwrap() },
+            };
+
+            parse_quote_spanned! { signature_span => {
+                #check;
+                #struct_name::#method_name(#method_call_inputs)
+            }}
+        } else {
+            parse_quote_spanned! { signature_span =>
+                #struct_name::#method_name(#method_call_inputs)
+            }
         }
     }
 
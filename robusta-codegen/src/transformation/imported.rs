@@ -1,20 +1,24 @@
 use inflector::cases::camelcase::to_camel_case;
-use proc_macro2::{TokenStream, TokenTree};
+use proc_macro2::{Span, TokenStream, TokenTree};
 use proc_macro_error::{abort, emit_error, emit_warning};
-use quote::{quote_spanned, ToTokens};
+use quote::{quote, quote_spanned, ToTokens};
 use syn::fold::Fold;
 use syn::spanned::Spanned;
-use syn::{parse_quote, GenericArgument, PathArguments, Type, TypePath};
-use syn::{FnArg, ImplItemFn, Lit, Pat, PatIdent, ReturnType, Signature};
+use syn::{parse_quote, GenericArgument, GenericParam, PathArguments, Type, TypePath};
+use syn::{FnArg, ImplItemFn, Lit, LitStr, Pat, PatIdent, ReturnType, Signature};
 
+use crate::transformation::classpath::{ImportedMethod, Verifier};
 use crate::transformation::context::StructContext;
-use crate::transformation::utils::get_call_type;
+use crate::transformation::utils::{get_call_type, get_java_method_name, type_descriptor};
 use crate::transformation::{CallType, CallTypeAttribute, SafeParams};
-use crate::utils::{get_abi, get_class_arg_if_any, get_env_arg, is_self_method};
+use crate::utils::{get_abi, get_class_arg_if_any, get_env_arg, is_self_method, verify_member};
 use std::collections::HashSet;
 
 pub struct ImportedMethodTransformer<'ctx> {
     pub(crate) struct_context: &'ctx StructContext,
+    pub(crate) verifier: Option<&'ctx Verifier>,
+    // entries of the registry checked by `robusta_jni::verify`, see `verify_member`
+    pub(crate) members: Vec<TokenStream>,
 }
 
 impl<'ctx> Fold for ImportedMethodTransformer<'ctx> {
@@ -58,6 +62,8 @@ impl<'ctx> Fold for ImportedMethodTransformer<'ctx> {
                     let discarded_known_attributes: HashSet<&str> = {
                         let mut h = HashSet::new();
                         h.insert("call_type");
+                        h.insert("java_name");
+                        h.insert("output_type");
 
                         if is_constructor {
                             h.insert("constructor");
@@ -118,36 +124,32 @@ impl<'ctx> Fold for ImportedMethodTransformer<'ctx> {
                     .unwrap_or(&CallType::Safe(None));
 
                 if let Some(CallTypeAttribute { attr, .. }) = &call_type_attribute {
-                    if let CallType::Safe(Some(params)) = call_type {
-                        if let SafeParams {
+                    if let CallType::Safe(Some(
+                        SafeParams {
                             message: Some(_), ..
                         }
                         | SafeParams {
                             exception_class: Some(_),
                             ..
-                        } = params
-                        {
-                            abort!(attr, "can't have exception message or exception class for imported methods")
+                        },
+                    )) = call_type
+                    {
+                        abort!(
+                            attr,
+                            "can't have exception message or exception class for imported methods"
+                        )
+                    }
+
+                    if let CallType::Safe(Some(SafeParams { wrap_pending, .. })) = call_type {
+                        if wrap_pending.is_present() {
+                            abort!(attr, "can't wrap pending exceptions in imported methods")
                         }
                     }
                 }
 
-                let jni_package_path = self
-                    .struct_context
-                    .package
-                    .as_ref()
-                    .map(|p| p.to_string())
-                    .filter(|s| !s.is_empty())
-                    .unwrap_or_else(|| "".into())
-                    .replace('.', "/");
-
-                let java_class_path = [jni_package_path, self.struct_context.struct_name.clone()]
-                    .iter()
-                    .filter(|s| !s.is_empty())
-                    .map(|s| s.to_owned())
-                    .collect::<Vec<_>>()
-                    .join("/");
-                let java_method_name = to_camel_case(&signature.ident.to_string());
+                let java_class_path = self.struct_context.classpath_path();
+                let java_method_name = get_java_method_name(&node)
+                    .unwrap_or_else(|| to_camel_case(&signature.ident.to_string()));
 
                 let input_types_conversions = signature
                     .inputs
@@ -199,11 +201,76 @@ impl<'ctx> Fold for ImportedMethodTransformer<'ctx> {
                     }
                 };
 
+                let override_output_type = node
+                    .attrs
+                    .iter()
+                    .find(|a| a.path().is_ident("output_type"))
+                    .map(|a| match a.parse_args::<LitStr>() {
+                        Ok(literal) => literal,
+                        Err(_) => abort!(a, "Missing argument for `#[output_type]`"),
+                    });
+
+                if let Some(verifier) = self.verifier {
+                    let java_classes = &self.struct_context.java_classes;
+                    let parameters = signature
+                        .inputs
+                        .iter()
+                        .filter_map(|i| match i {
+                            FnArg::Typed(t) => match &*t.pat {
+                                Pat::Ident(PatIdent { ident, .. }) if ident == "self" => None,
+                                _ => Some(t),
+                            },
+                            FnArg::Receiver(_) => None,
+                        })
+                        .map(
+                            |t| match t.attrs.iter().find(|a| a.path().is_ident("input_type")) {
+                                Some(a) => a.parse_args::<LitStr>().ok().map(|l| l.value()),
+                                None => type_descriptor(&t.ty, java_classes),
+                            },
+                        )
+                        .collect();
+                    let return_type = match &signature.output {
+                        _ if is_constructor => Some("V".to_string()),
+                        _ if override_output_type.is_some() => {
+                            override_output_type.as_ref().map(LitStr::value)
+                        }
+                        ReturnType::Default => Some("V".to_string()),
+                        ReturnType::Type(_, ty) => match (call_type, &**ty) {
+                            (CallType::Safe(_), Type::Path(TypePath { path, .. })) => path
+                                .segments
+                                .last()
+                                .and_then(|s| match &s.arguments {
+                                    PathArguments::AngleBracketed(a) => a.args.first(),
+                                    _ => None,
+                                })
+                                .and_then(|a| match a {
+                                    GenericArgument::Type(t) => type_descriptor(t, java_classes),
+                                    _ => None,
+                                }),
+                            (CallType::Safe(_), _) => None,
+                            (CallType::Unchecked(_), ty) => type_descriptor(ty, java_classes),
+                        },
+                    };
+
+                    let method = ImportedMethod {
+                        java_name: &java_method_name,
+                        is_constructor,
+                        is_static: !self_method,
+                        parameters,
+                        return_type,
+                    };
+                    if let Err(mismatch) = verifier.verify_method(&java_class_path, &method) {
+                        mismatch.emit(node.sig.ident.span());
+                    }
+                }
+
                 let output_conversion = match signature.output {
                     ReturnType::Default => quote_spanned!(signature.output.span() => ),
                     ReturnType::Type(_arrow, ref ty) => {
                         if is_constructor {
                             quote_spanned! { output_type_span => "V" }
+                        } else if let Some(override_output_type) = &override_output_type {
+                            quote_spanned! { output_type_span => #override_output_type }
                         } else {
                             match call_type {
                                 CallType::Safe(_) => {
@@ -287,11 +354,13 @@ impl<'ctx> Fold for ImportedMethodTransformer<'ctx> {
                         if is_constructor {
                             quote_spanned! { output_type_span =>
                                 res.and_then(|v| ::robusta_jni::convert::TryFromJavaValue::try_from(v, &env))
+                                   .map_err(|e| ::robusta_jni::exception::FromJniError::from_jni_error(env, e))
                             }
                         } else {
                             quote_spanned! { output_type_span =>
                                 res.and_then(|v| ::std::convert::TryInto::try_into(::robusta_jni::convert::JValueWrapper::from(v)))
                                    .and_then(|v| ::robusta_jni::convert::TryFromJavaValue::try_from(v, &env))
+                                   .map_err(|e| ::robusta_jni::exception::FromJniError::from_jni_error(env, e))
                             }
                         }
                     }
@@ -320,6 +389,42 @@ impl<'ctx> Fold for ImportedMethodTransformer<'ctx> {
                     _ => panic!("Bug -- please report to library author. Expected env parameter, found receiver")
                 };
 
+                // Call sites are cached in a `static`, which is shared by all instantiations of a generic method:
+                // if the Java signature may depend on type parameters it has to be built on every call
+                let cacheable = node
+                    .sig
+                    .generics
+                    .params
+                    .iter()
+                    .all(|p| matches!(p, GenericParam::Lifetime(_)))
+                    && self.struct_context.struct_type.segments.iter().all(|s| {
+                        match &s.arguments {
+                            PathArguments::AngleBracketed(a) => a
+                                .args
+                                .iter()
+                                .all(|a| matches!(a, GenericArgument::Lifetime(_))),
+                            _ => true,
+                        }
+                    });
+
+                // Methods looked up through a class argument may belong to any class
+                if cacheable && class_ref_arg.is_none() {
+                    let (kind, name) = if is_constructor {
+                        ("Constructor", "<init>")
+                    } else if self_method {
+                        ("Method", java_method_name.as_str())
+                    } else {
+                        ("StaticMethod", java_method_name.as_str())
+                    };
+
+                    self.members.push(verify_member(
+                        kind,
+                        &java_class_path,
+                        name,
+                        quote! { "(", #input_types_conversions ")", #output_conversion },
+                    ));
+                }
+
                 let sig_discarded_known_attributes: HashSet<&str> = {
                     let mut h = HashSet::new();
                     h.insert("input_type");
@@ -350,15 +455,11 @@ impl<'ctx> Fold for ImportedMethodTransformer<'ctx> {
                                 .clone()
                                 .into_iter()
                                 .filter(|a| {
-                                    !a.path()
-                                        .segments
-                                        .iter()
-                                        .find(|s| {
-                                            sig_discarded_known_attributes
-                                                .iter()
-                                                .any(|d| s.ident.to_string().contains(d))
-                                        })
-                                        .is_some()
+                                    !a.path().segments.iter().any(|s| {
+                                        sig_discarded_known_attributes
+                                            .iter()
+                                            .any(|d| s.ident.to_string().contains(d))
+                                    })
                                 })
                                 .collect()
                         }
@@ -371,89 +472,75 @@ impl<'ctx> Fold for ImportedMethodTransformer<'ctx> {
                         abi: None,
                         ..original_signature
                     },
-                    block: if self_method {
-                        let self_span = node.sig.inputs.iter().next().unwrap().span();
-                        match call_type {
-                            CallType::Safe(_) => {
-                                parse_quote_spanned! { self_span => {
-                                    let env: &'_ ::robusta_jni::jni::JNIEnv<'_> = #env_ident;
-                                    let res = env.call_method(::robusta_jni::convert::JavaValue::autobox(::robusta_jni::convert::TryIntoJavaValue::try_into(self, &env)?, &env), #java_method_name, #java_signature, &[#input_conversions]);
-                                    #return_expr
-                                }}
-                            }
-                            CallType::Unchecked(_) => {
-                                parse_quote_spanned! { self_span => {
-                                    let env: &'_ ::robusta_jni::jni::JNIEnv<'_> = #env_ident;
-                                    let res = env.call_method(::robusta_jni::convert::JavaValue::autobox(::robusta_jni::convert::IntoJavaValue::into(self, &env), &env), #java_method_name, #java_signature, &[#input_conversions]).unwrap();
-                                    #return_expr
+                    block: {
+                        let block_span = if self_method {
+                            node.sig.inputs.iter().next().unwrap().span()
+                        } else {
+                            Span::call_site()
+                        };
+
+                        let call = if self_method {
+                            let receiver = match call_type {
+                                CallType::Safe(_) => {
+                                    quote_spanned! { block_span => ::robusta_jni::convert::JavaValue::autobox(::robusta_jni::convert::TryIntoJavaValue::try_into(self, &env)?, &env) }
+                                }
+                                CallType::Unchecked(_) => {
+                                    quote_spanned! { block_span => ::robusta_jni::convert::JavaValue::autobox(::robusta_jni::convert::IntoJavaValue::into(self, &env), &env) }
+                                }
+                            };
+
+                            if cacheable {
+                                quote_spanned! { block_span => {
+                                    static METHOD: ::robusta_jni::cache::CachedMethod = ::robusta_jni::cache::CachedMethod::new();
+                                    let args = [#input_conversions];
+                                    // The arguments are converted to the types of the signature
+                                    unsafe { METHOD.call(env, #receiver, #java_class_path, #java_method_name, || #java_signature, &args) }
                                 }}
+                            } else {
+                                quote_spanned! { block_span => env.call_method(#receiver, #java_method_name, #java_signature, &[#input_conversions]) }
                             }
-                        }
-                    } else {
-                        match call_type {
-                            CallType::Safe(_) => {
-                                if is_constructor {
-                                    if let Some(class_arg_ident) = class_arg_ident {
-                                        parse_quote! {{
-                                            let env: &'_ ::robusta_jni::jni::JNIEnv<'_> = #env_ident;
-                                            let res = env.new_object(#class_arg_ident, #java_signature, &[#input_conversions]);
-                                            #return_expr
-                                        }}
-                                    } else {
-                                        parse_quote! {{
-                                            let env: &'_ ::robusta_jni::jni::JNIEnv<'_> = #env_ident;
-                                            let res = env.new_object(#java_class_path, #java_signature, &[#input_conversions]);
-                                            #return_expr
-                                        }}
-                                    }
-                                } else {
-                                    if let Some(class_arg_ident) = class_arg_ident {
-                                        parse_quote! {{
-                                            let env: &'_ ::robusta_jni::jni::JNIEnv<'_> = #env_ident;
-                                            let res = env.call_static_method(#class_arg_ident, #java_method_name, #java_signature, &[#input_conversions]);
-                                            #return_expr
-                                        }}
-                                    } else {
-                                        parse_quote! {{
-                                            let env: &'_ ::robusta_jni::jni::JNIEnv<'_> = #env_ident;
-                                            let res = env.call_static_method(#java_class_path, #java_method_name, #java_signature, &[#input_conversions]);
-                                            #return_expr
-                                        }}
-                                    }
+                        } else if is_constructor {
+                            match class_arg_ident {
+                                Some(class_arg_ident) => {
+                                    quote_spanned! { block_span => env.new_object(#class_arg_ident, #java_signature, &[#input_conversions]) }
+                                }
+                                None if cacheable => quote_spanned! { block_span => {
+                                    static CONSTRUCTOR: ::robusta_jni::cache::CachedConstructor = ::robusta_jni::cache::CachedConstructor::new();
+                                    let args = [#input_conversions];
+                                    // The arguments are converted to the types of the signature
+                                    unsafe { CONSTRUCTOR.new_object(env, #java_class_path, || #java_signature, &args) }
+                                }},
+                                None => {
+                                    quote_spanned! { block_span => ::robusta_jni::class_loader::find_class(env, #java_class_path).and_then(|class| env.new_object(class, #java_signature, &[#input_conversions])) }
                                 }
                             }
-                            CallType::Unchecked(_) => {
-                                if is_constructor {
-                                    if let Some(class_arg_ident) = class_arg_ident {
-                                        parse_quote! {{
-                                            let env: &'_ ::robusta_jni::jni::JNIEnv<'_> = #env_ident;
-                                            let res = env.new_object(#class_arg_ident, #java_signature, &[#input_conversions]).unwrap();
-                                            #return_expr
-                                        }}
-                                    } else {
-                                        parse_quote! {{
-                                            let env: &'_ ::robusta_jni::jni::JNIEnv<'_> = #env_ident;
-                                            let res = env.new_object(#java_class_path, #java_signature, &[#input_conversions]).unwrap();
-                                            #return_expr
-                                        }}
-                                    }
-                                } else {
-                                    if let Some(class_arg_ident) = class_arg_ident {
-                                        parse_quote! {{
-                                            let env: &'_ ::robusta_jni::jni::JNIEnv<'_> = #env_ident;
-                                            let res = env.call_static_method(#class_arg_ident, #java_method_name, #java_signature, &[#input_conversions]).unwrap();
-                                            #return_expr
-                                        }}
-                                    } else {
-                                        parse_quote! {{
-                                            let env: &'_ ::robusta_jni::jni::JNIEnv<'_> = #env_ident;
-                                            let res = env.call_static_method(#java_class_path, #java_method_name, #java_signature, &[#input_conversions]).unwrap();
-                                            #return_expr
-                                        }}
-                                    }
+                        } else {
+                            match class_arg_ident {
+                                Some(class_arg_ident) => {
+                                    quote_spanned! { block_span => env.call_static_method(#class_arg_ident, #java_method_name, #java_signature, &[#input_conversions]) }
+                                }
+                                None if cacheable => quote_spanned! { block_span => {
+                                    static METHOD: ::robusta_jni::cache::CachedStaticMethod = ::robusta_jni::cache::CachedStaticMethod::new();
+                                    let args = [#input_conversions];
+                                    // The arguments are converted to the types of the signature
+                                    unsafe { METHOD.call(env, #java_class_path, #java_method_name, || #java_signature, &args) }
+                                }},
+                                None => {
+                                    quote_spanned! { block_span => ::robusta_jni::class_loader::find_class(env, #java_class_path).and_then(|class| env.call_static_method(class, #java_method_name, #java_signature, &[#input_conversions])) }
                                 }
                             }
-                        }
+                        };
+
+                        let unwrap = match call_type {
+                            CallType::Safe(_) => quote_spanned! { block_span => },
+                            CallType::Unchecked(_) => quote_spanned! { block_span => .unwrap() },
+                        };
+
+                        parse_quote_spanned! { block_span => {
+                            let env: &'_ ::robusta_jni::jni::JNIEnv<'_> = #env_ident;
+                            let res = #call#unwrap;
+                            #return_expr
+                        }}
                     },
                     attrs: impl_item_attributes,
                     ..node
@@ -0,0 +1,38 @@
+/// Escape `s` as a component of a JNI native method name, as described in the
+/// [JNI specification](https://docs.oracle.com/en/java/javase/17/docs/specs/jni/design.html#resolving-native-method-names).
+/// Slashes of classpath paths and type signatures become underscores.
+pub(crate) fn mangle(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+
+    for c in s.chars() {
+        match c {
+            '/' => result.push('_'),
+            '_' => result.push_str("_1"),
+            ';' => result.push_str("_2"),
+            '[' => result.push_str("_3"),
+            c if c.is_ascii_alphanumeric() => result.push(c),
+            c => {
+                let mut buffer = [0; 2];
+                for unit in c.encode_utf16(&mut buffer) {
+                    result.push_str(&format!("_0{:04x}", unit));
+                }
+            }
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn mangle_escapes() {
+        assert_eq!(mangle("Ljava/lang/String;"), "Ljava_lang_String_2");
+        assert_eq!(mangle("[I"), "_3I");
+        assert_eq!(mangle("my_method"), "my_1method");
+        assert_eq!(mangle("Outer$Inner"), "Outer_00024Inner");
+        assert_eq!(mangle("caf\u{e9}"), "caf_000e9");
+    }
+}
@@ -1,12 +1,12 @@
-use std::collections::{BTreeSet, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 
 use darling::util::Flag;
 use darling::FromMeta;
 use proc_macro2::{Ident, TokenStream};
-use proc_macro_error::{emit_error, emit_warning};
-use quote::ToTokens;
+use proc_macro_error::{emit_call_site_error, emit_error, emit_warning};
+use quote::{quote, ToTokens};
 use syn::fold::Fold;
 use syn::parse::{Parse, ParseBuffer, ParseStream, Parser};
 use syn::punctuated::Punctuated;
@@ -14,24 +14,32 @@ use syn::spanned::Spanned;
 use syn::visit::Visit;
 use syn::{
     parse_quote, Attribute, FnArg, GenericArgument, GenericParam, ImplItemFn, Item, ItemImpl,
-    ItemMod, ItemStruct, Lit, Pat, PatIdent, PatType, Path, PathArguments, PathSegment, Type,
+    ItemMod, ItemStruct, Lit, Meta, Pat, PatIdent, PatType, Path, PathArguments, PathSegment, Type,
     TypePath, TypeReference, Visibility,
 };
 use syn::{Error, ImplItem, Token};
 
 use imported::ImportedMethodTransformer;
 
+use crate::derive::handle::get_native_handle_params;
+use crate::transformation::classpath::{track_classpath_var, Verifier};
 use crate::transformation::context::StructContext;
-use crate::transformation::exported::ExportedMethodTransformer;
-use crate::utils::{canonicalize_path, get_abi};
+use crate::transformation::exported::{ExportedMethodTransformer, NativeMethod};
+use crate::transformation::mangling::mangle;
+use crate::transformation::utils::type_descriptor;
+use crate::utils::{
+    canonicalize_path, get_abi, get_derived_traits, submit_verify_members, to_static,
+};
 use crate::validation::JNIBridgeModule;
 use std::fmt;
 
 #[macro_use]
 mod utils;
+mod classpath;
 mod context;
 mod exported;
 mod imported;
+mod mangling;
 
 #[derive(Copy, Clone)]
 pub(crate) enum ImplItemType {
@@ -40,18 +48,104 @@ pub(crate) enum ImplItemType {
     Unexported,
 }
 
+/// Parameters of the `#[bridge]` attribute.
+#[derive(Default, FromMeta)]
+#[darling(default)]
+pub struct BridgeParams {
+    /// Register exported methods from a generated `JNI_OnLoad` instead of exporting them by symbol name.
+    pub(crate) register_natives: Flag,
+    /// Check every Java member used by the program with `robusta_jni::verify` from a generated `JNI_OnLoad`.
+    pub(crate) verify: Flag,
+}
+
 pub(crate) struct ModTransformer {
     module: JNIBridgeModule,
+    params: BridgeParams,
+    // path of the module being transformed, starting from the bridge module
+    module_path: Vec<Ident>,
+    // exported methods to register, grouped by class
+    natives: BTreeMap<String, Vec<TokenStream>>,
+    // checks imported methods and fields if `ROBUSTA_CLASSPATH` is set
+    verifier: Option<Verifier>,
 }
 
 impl ModTransformer {
-    pub(crate) fn new(module: JNIBridgeModule) -> Self {
-        ModTransformer { module }
+    pub(crate) fn new(module: JNIBridgeModule, params: BridgeParams) -> Self {
+        ModTransformer {
+            module,
+            params,
+            module_path: Vec::new(),
+            natives: BTreeMap::new(),
+            verifier: Verifier::from_env(),
+        }
     }
 
     pub(crate) fn transform_module(&mut self) -> TokenStream {
         let module_decl = self.module.module_decl.clone();
-        self.fold_item_mod(module_decl).into_token_stream()
+        let mut module = self.fold_item_mod(module_decl);
+
+        if let Some((_, items)) = &mut module.content {
+            items.push(Item::Verbatim(track_classpath_var()));
+
+            if self.params.verify.is_present() && !cfg!(feature = "verify") {
+                emit_call_site_error!("`#[bridge(verify)]` requires the `verify` feature of `robusta_jni`");
+            }
+
+            if self.has_on_load() {
+                items.push(Item::Verbatim(self.jni_onload()));
+            } else {
+                items.push(Item::Verbatim(self.capture_fn()));
+            }
+        }
+
+        module.into_token_stream()
+    }
+
+    fn has_on_load(&self) -> bool {
+        self.params.register_natives.is_present() || self.params.verify.is_present()
+    }
+
+    /// Function capturing the Java VM and the class loaders of the module on the first call to an exported method,
+    /// for modules without a `JNI_OnLoad`. Later calls only check a `Once`.
+    fn capture_fn(&self) -> TokenStream {
+        let java_classes = self.module.java_classes.values();
+
+        quote! {
+            #[doc(hidden)]
+            fn __robusta_capture(env: &::robusta_jni::jni::JNIEnv) {
+                static CAPTURED: ::std::sync::Once = ::std::sync::Once::new();
+                CAPTURED.call_once(|| {
+                    ::robusta_jni::vm::capture(env);
+                    ::robusta_jni::class_loader::capture(env, &[#(#java_classes),*]);
+                });
+            }
+        }
+    }
+
+    /// `JNI_OnLoad` function capturing the class loaders of the module, registering every exported method
+    /// and checking the Java members used by the program if requested.
+    fn jni_onload(&self) -> TokenStream {
+        let classes = self.natives.keys();
+        let methods = self.natives.values();
+        let java_classes = self.module.java_classes.values();
+        let verify = if self.params.verify.is_present() {
+            quote! { ::robusta_jni::verify::verify(env)?; }
+        } else {
+            TokenStream::new()
+        };
+
+        quote! {
+            #[no_mangle]
+            pub extern "system" fn JNI_OnLoad(vm: ::robusta_jni::jni::JavaVM, _reserved: *mut ::std::ffi::c_void) -> ::robusta_jni::jni::sys::jint {
+                ::robusta_jni::registration::on_load(vm, |env| {
+                    ::robusta_jni::class_loader::capture(env, &[#(#java_classes),*]);
+                    #(::robusta_jni::registration::register_natives(env, #classes, &[#(#methods),*])?;)*
+                    #verify
+
+                    Ok(())
+                })
+            }
+        }
     }
 
     /// If the impl block is a standard impl block for a type, makes every exported fn a freestanding one
@@ -59,6 +153,7 @@ impl ModTransformer {
         let mut impl_export_visitor = ImplExportVisitor::default();
         impl_export_visitor.visit_item_impl(&node);
 
+        let mut verify_members = TokenStream::new();
         let (preserved_items, transformed_items) = if let Type::Path(p) = &*node.self_ty {
             let canonical_path = canonicalize_path(&p.path);
             let struct_name = canonical_path
@@ -103,18 +198,42 @@ impl ModTransformer {
                 })
                 .collect();
 
+            let outer_class = self.module.outer_classes.get(&struct_name).cloned();
+            let write_back = self.module.write_back_structs.contains(&struct_name);
+            let overloaded_methods = self
+                .module
+                .overloaded_methods
+                .get(&struct_name)
+                .cloned()
+                .unwrap_or_default();
             let context = StructContext {
                 struct_type: p.path.clone(),
                 struct_name,
                 struct_lifetimes,
                 package: struct_package,
+                outer_class,
+                write_back,
+                register_natives: self.params.register_natives.is_present(),
+                capture: if self.has_on_load() {
+                    None
+                } else {
+                    // Exported functions are emitted next to the impl block, in a submodule of the bridge module
+                    let supers = self.module_path.iter().skip(1).map(|_| quote! { super:: });
+                    Some(parse_quote! { self::#(#supers)*__robusta_capture })
+                },
+                overloaded_methods,
+                java_classes: self.module.java_classes.clone(),
+                native_handles: self.module.native_handles.clone(),
             };
 
             let mut exported_fns_transformer = ExportedMethodTransformer {
                 struct_context: &context,
+                natives: Vec::new(),
             };
             let mut imported_fns_transformer = ImportedMethodTransformer {
                 struct_context: &context,
+                verifier: self.verifier.as_ref(),
+                members: Vec::new(),
             };
             let mut impl_cleaner = ImplCleaner;
 
@@ -143,6 +262,40 @@ impl ModTransformer {
                 .map(|i| exported_fns_transformer.fold_impl_item(i))
                 .collect();
 
+            // The registry is emitted next to the impl block, where `Self` and the impl lifetimes aren't defined
+            let self_type = p.path.to_token_stream();
+            verify_members = to_static(
+                submit_verify_members(&imported_fns_transformer.members),
+                Some(&self_type),
+            );
+
+            if context.register_natives {
+                let classpath_path = context.classpath_path();
+
+                // Exported functions are emitted next to the impl block, so they are named relative to the bridge module
+                let module_path = &self.module_path[1..];
+                let methods = exported_fns_transformer.natives.into_iter().map(
+                    |NativeMethod {
+                         name,
+                         signature,
+                         function,
+                     }| {
+                        quote! {
+                            ::robusta_jni::jni::NativeMethod {
+                                name: #name.into(),
+                                sig: #signature.into(),
+                                fn_ptr: self::#(#module_path::)*#function as *mut ::std::ffi::c_void,
+                            }
+                        }
+                    },
+                );
+
+                self.natives
+                    .entry(classpath_path)
+                    .or_default()
+                    .extend(methods);
+            }
+
             (preserved, transformed)
         } else {
             (node.items, Vec::new())
@@ -163,13 +316,107 @@ impl ModTransformer {
             ..node
         };
 
-        transformed_items.iter().map(|i| i.to_token_stream()).fold(
+        let mut stream = transformed_items.iter().map(|i| i.to_token_stream()).fold(
             preserved_impl.into_token_stream(),
             |item, mut stream| {
                 item.to_tokens(&mut stream);
                 stream
             },
-        )
+        );
+        stream.extend(verify_members);
+
+        stream
+    }
+
+    /// Native handles get the native method disposing of their value, if requested with `#[native_handle(dispose = "...")]`.
+    fn transform_item_struct(&mut self, node: ItemStruct) -> Item {
+        let struct_name = node.ident.to_string();
+        let dispose = if self.module.native_handles.contains(&struct_name) {
+            get_native_handle_params(&node.attrs).dispose
+        } else {
+            None
+        };
+        let classpath_path = self.module.java_classes.get(&struct_name).cloned();
+        let struct_ident = node.ident.clone();
+        let node = self.fold_item_struct(node);
+
+        let (name, classpath_path) = match (dispose, classpath_path) {
+            (Some(name), Some(classpath_path)) => (name, classpath_path),
+            _ => return Item::Struct(node),
+        };
+
+        let function = Ident::new(
+            &format!("Java_{}_{}", mangle(&classpath_path), mangle(&name)),
+            struct_ident.span(),
+        );
+        let register_natives = self.params.register_natives.is_present();
+        let no_mangle = if register_natives {
+            TokenStream::new()
+        } else {
+            quote! { #[no_mangle] }
+        };
+
+        if register_natives {
+            let module_path = &self.module_path[1..];
+            self.natives
+                .entry(classpath_path)
+                .or_default()
+                .push(quote! {
+                    ::robusta_jni::jni::NativeMethod {
+                        name: #name.into(),
+                        sig: "()V".into(),
+                        fn_ptr: self::#(#module_path::)*#function as *mut ::std::ffi::c_void,
+                    }
+                });
+        }
+
+        Item::Verbatim(quote! {
+            #node
+
+            #no_mangle
+            pub extern "system" fn #function(env: ::robusta_jni::jni::JNIEnv, peer: ::robusta_jni::jni::objects::JObject) {
+                ::robusta_jni::handle::dispose_native::<#struct_ident>(env, peer)
+            }
+        })
+    }
+
+    /// Check the `#[field]`s of a `#[package]` struct against its class.
+    fn verify_fields(&self, verifier: &Verifier, node: &ItemStruct) {
+        let classpath_path = match self.module.java_classes.get(&node.ident.to_string()) {
+            Some(classpath_path) => classpath_path,
+            None => return,
+        };
+
+        for field in node
+            .fields
+            .iter()
+            .filter(|f| f.attrs.iter().any(|a| a.path().is_ident("field")))
+        {
+            let ident = match &field.ident {
+                Some(ident) => ident,
+                None => continue,
+            };
+            // `Field<'env, 'borrow, T>` is converted like `T`
+            let value_type = match &field.ty {
+                Type::Path(TypePath { path, .. }) => {
+                    path.segments.last().and_then(|s| match &s.arguments {
+                        PathArguments::AngleBracketed(a) => a.args.iter().find_map(|a| match a {
+                            GenericArgument::Type(t) => Some(t),
+                            _ => None,
+                        }),
+                        _ => None,
+                    })
+                }
+                _ => None,
+            };
+            let inferred = value_type.and_then(|t| type_descriptor(t, &self.module.java_classes));
+
+            if let Err(mismatch) =
+                verifier.verify_field(classpath_path, &ident.to_string(), inferred)
+            {
+                mismatch.emit(ident.span());
+            }
+        }
     }
 }
 
@@ -185,7 +432,7 @@ impl Fold for ModTransformer {
             Item::Macro(m) => Item::Macro(self.fold_item_macro(m)),
             Item::Mod(m) => Item::Mod(self.fold_item_mod(m)),
             Item::Static(s) => Item::Static(self.fold_item_static(s)),
-            Item::Struct(s) => Item::Struct(self.fold_item_struct(s)),
+            Item::Struct(s) => self.transform_item_struct(s),
             Item::Trait(t) => Item::Trait(self.fold_item_trait(t)),
             Item::TraitAlias(t) => Item::TraitAlias(self.fold_item_trait_alias(t)),
             Item::Type(t) => Item::Type(self.fold_item_type(t)),
@@ -201,7 +448,8 @@ impl Fold for ModTransformer {
 
         node.attrs.extend_from_slice(&[allow_non_snake_case]);
 
-        ItemMod {
+        self.module_path.push(node.ident.clone());
+        let module = ItemMod {
             attrs: node.attrs,
             vis: self.fold_visibility(node.vis),
             unsafety: node.unsafety,
@@ -214,10 +462,17 @@ impl Fold for ModTransformer {
                 )
             }),
             semi: node.semi,
-        }
+        };
+        self.module_path.pop();
+
+        module
     }
 
     fn fold_item_struct(&mut self, node: ItemStruct) -> ItemStruct {
+        if let Some(verifier) = &self.verifier {
+            self.verify_fields(verifier, &node);
+        }
+
         let struct_attributes = {
             /* The `#[bridge]` attribute macro has to discard `#[package()]` attributes, because they don't exists in standard Rust
              * and currently there is no way for attribute macros to automatically introduce inert attributes (see: https://doc.rust-lang.org/reference/attributes.html#active-and-inert-attributes
@@ -235,32 +490,28 @@ impl Fold for ModTransformer {
                 "TryFromJavaValue",
                 "IntoJavaValue",
                 "TryIntoJavaValue",
+                "WriteBack",
+                "NativeHandle",
             ]);
 
-            let has_package_trait = node.attrs.iter().any(|a| {
-                let is_derive =
-                    a.path().get_ident().map(ToString::to_string).as_deref() == Some("derive");
-                let derived_traits = a
-                    .parse_args_with(Punctuated::<Ident, Token![,]>::parse_terminated)
-                    .iter()
-                    .flat_map(|p: &syn::punctuated::Punctuated<Ident, Token![,]>| p)
-                    .map(|i| i.to_string())
-                    .collect::<HashSet<String>>();
-                let needs_package_attr = derived_traits
-                    .iter()
-                    .any(|t| traits_with_package_attr.contains(t.as_str()));
-
-                is_derive && needs_package_attr
-            });
+            let derived_traits = get_derived_traits(&node.attrs);
 
-            if !has_package_trait {
-                attributes
-                    .into_iter()
-                    .filter(|a| a.path().to_token_stream().to_string().as_str() != "package")
-                    .collect()
-            } else {
-                attributes
-            }
+            let has_package_trait = derived_traits
+                .iter()
+                .any(|t| traits_with_package_attr.contains(t.as_str()));
+            // Same goes for `#[write_back]`, which is a helper attribute of the `WriteBack` derive macro only
+            let has_write_back_trait = derived_traits.contains("WriteBack");
+            let has_native_handle_trait = derived_traits.contains("NativeHandle");
+
+            attributes
+                .into_iter()
+                .filter(|a| {
+                    let path = a.path().to_token_stream().to_string();
+                    (has_package_trait || (path != "package" && path != "outer_class"))
+                        && (has_write_back_trait || path != "write_back")
+                        && (has_native_handle_trait || path != "native_handle")
+                })
+                .collect()
         };
 
         ItemStruct {
@@ -320,13 +571,26 @@ impl FromStr for JavaPath {
 }
 
 impl JavaPath {
-    pub fn to_snake_case(&self) -> String {
-        self.0.replace('.', "_")
-    }
-
     pub fn to_classpath_path(&self) -> String {
         self.0.replace('.', "/")
     }
+
+    /// Classpath path of the class `class_name` declared in this package, nested in `outer_class` if any
+    /// (e.g. `com/example/Outer$Inner`).
+    pub fn class_path(&self, outer_class: Option<&JavaPath>, class_name: &str) -> String {
+        let mut path = self.to_classpath_path();
+        if !path.is_empty() {
+            path.push('/');
+        }
+
+        if let Some(outer_class) = outer_class {
+            path.push_str(&outer_class.0.replace('.', "$"));
+            path.push('$');
+        }
+
+        path.push_str(class_name);
+        path
+    }
 }
 
 impl Parse for JavaPath {
@@ -357,7 +621,7 @@ impl FromMeta for JavaPath {
                 "invalid path: packages and classes cannot contain dashes",
             ))
         } else {
-            let tokens = TokenStream::from_str(&path)
+            let tokens = TokenStream::from_str(path)
                 .map_err(|_| Error::custom("cannot create token stream for java path parsing"))?;
             let _parsed: Punctuated<Ident, Token![.]> =
                 Punctuated::<Ident, Token![.]>::parse_separated_nonempty
@@ -385,7 +649,7 @@ impl<'ast> AttributeFilter<'ast> {
 
 impl<'ast> Visit<'ast> for AttributeFilter<'ast> {
     fn visit_attribute(&mut self, attribute: &'ast Attribute) {
-        if self.whitelist.contains(&attribute.path()) {
+        if self.whitelist.contains(attribute.path()) {
             self.filtered_attributes.push(attribute);
         }
     }
@@ -404,11 +668,14 @@ impl Fold for ImplCleaner {
         match (&node.vis, &abi.as_deref()) {
             (Visibility::Public(_), Some("jni")) => {
                 node.sig.abi = None;
-                node.attrs = node
-                    .attrs
-                    .into_iter()
-                    .filter(|a| a.path().get_ident().map_or(false, |i| i != "call_type"))
-                    .collect();
+                node.attrs.retain(|a| {
+                    a.path().get_ident().is_some_and(|i| {
+                        i != "call_type"
+                            && i != "catch_panic"
+                            && i != "write_back"
+                            && i != "java_name"
+                    })
+                });
 
                 node
             }
@@ -510,12 +777,14 @@ impl Fold for FreestandingTransformer {
 pub struct SafeParams {
     pub(crate) exception_class: Option<JavaPath>,
     pub(crate) message: Option<String>,
+    // wrap a pending exception as the cause of the thrown one, instead of letting it propagate
+    pub(crate) wrap_pending: Flag,
 }
 
 #[derive(Clone, FromMeta)]
 pub enum CallType {
     Safe(Option<SafeParams>),
-    Unchecked(Flag),
+    Unchecked(#[allow(dead_code)] Flag),
 }
 
 pub struct CallTypeAttribute {
@@ -565,3 +834,50 @@ impl Parse for CallTypeAttribute {
         }
     }
 }
+
+#[derive(Clone, Default, FromMeta)]
+#[darling(default)]
+pub struct CatchPanicParams {
+    pub(crate) exception_class: Option<JavaPath>,
+    pub(crate) backtrace: Flag,
+    pub(crate) disable: Flag,
+}
+
+pub struct CatchPanicAttribute {
+    pub(crate) params: CatchPanicParams,
+}
+
+impl Parse for CatchPanicAttribute {
+    fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
+        let attribute = input
+            .call(Attribute::parse_outer)?
+            .first()
+            .cloned()
+            .ok_or_else(|| {
+                Error::new(input.span(), "Invalid parsing of `catch_panic` attribute")
+            })?;
+
+        if !attribute.path().is_ident("catch_panic") {
+            return Err(Error::new(
+                attribute.path().span(),
+                "expected identifier `catch_panic` for attribute",
+            ));
+        }
+
+        // Bare `#[catch_panic]` keeps the default behaviour
+        if let Meta::Path(_) = attribute.meta {
+            return Ok(CatchPanicAttribute {
+                params: CatchPanicParams::default(),
+            });
+        }
+
+        CatchPanicParams::from_meta(&attribute.meta)
+            .map_err(|e| {
+                Error::new(
+                    attribute.meta.span(),
+                    format!("invalid `catch_panic` attribute options ({})", e),
+                )
+            })
+            .map(|params| CatchPanicAttribute { params })
+    }
+}
@@ -1,13 +1,14 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::str::FromStr;
 
 use proc_macro2::TokenStream;
-use proc_macro_error::emit_warning;
+use proc_macro_error::{emit_error, emit_warning};
 use quote::ToTokens;
+use robusta_build::types::java_type;
 use syn::visit::Visit;
-use syn::ImplItemFn;
+use syn::{Attribute, Expr, ExprLit, ImplItemFn, Lit, Type};
 
-use crate::transformation::{AttributeFilter, CallTypeAttribute};
+use crate::transformation::{AttributeFilter, CallTypeAttribute, CatchPanicAttribute};
 
 pub(crate) fn get_call_type(node: &ImplItemFn) -> Option<CallTypeAttribute> {
     let whitelist = {
@@ -17,7 +18,7 @@ pub(crate) fn get_call_type(node: &ImplItemFn) -> Option<CallTypeAttribute> {
     };
 
     let mut attributes_collector = AttributeFilter::with_whitelist(whitelist);
-    attributes_collector.visit_impl_item_fn(&node);
+    attributes_collector.visit_impl_item_fn(node);
 
     let call_type_attribute = attributes_collector.filtered_attributes.first().and_then(|call_type_attr| {
         syn::parse2(call_type_attr.to_token_stream()).map_err(|e| {
@@ -29,6 +30,50 @@ pub(crate) fn get_call_type(node: &ImplItemFn) -> Option<CallTypeAttribute> {
     call_type_attribute
 }
 
+pub(crate) fn get_catch_panic(node: &ImplItemFn) -> Option<CatchPanicAttribute> {
+    node.attrs
+        .iter()
+        .find(|a| a.path().is_ident("catch_panic"))
+        .and_then(|catch_panic_attr| {
+            syn::parse2(catch_panic_attr.to_token_stream())
+                .map_err(|e| {
+                    emit_error!(e.span(), "{}", e);
+                    e
+                })
+                .ok()
+        })
+}
+
+pub(crate) fn get_write_back(node: &ImplItemFn) -> Option<&Attribute> {
+    node.attrs.iter().find(|a| a.path().is_ident("write_back"))
+}
+
+/// Value of the `#[java_name = "..."]` attribute of a method, if any.
+/// Malformed attributes are ignored here, since they are already reported when validating the bridge module.
+pub(crate) fn get_java_method_name(node: &ImplItemFn) -> Option<String> {
+    node.attrs
+        .iter()
+        .find(|a| a.path().is_ident("java_name"))
+        .and_then(|a| match &a.meta.require_name_value().ok()?.value {
+            Expr::Lit(ExprLit {
+                lit: Lit::Str(s), ..
+            }) => Some(s.value()),
+            _ => None,
+        })
+}
+
+/// Guess of `<ty as Signature>::SIG_TYPE` at macro expansion time, for the types of the conversion table
+/// and the `#[package]` types of the bridge module (`java_classes` maps their name to their classpath path).
+///
+/// Used both to name overloaded native methods and to check imported methods against compiled classes.
+/// Callers generating code from the result must check it against the actual `Signature` implementation at compile time.
+pub(crate) fn type_descriptor(
+    ty: &Type,
+    java_classes: &BTreeMap<String, String>,
+) -> Option<String> {
+    java_type(ty, &|name: &str| java_classes.get(name).cloned()).map(|t| t.descriptor())
+}
+
 macro_rules! parse_quote_spanned {
     ($span:expr => $($tt:tt)*) => {
         syn::parse2(quote::quote_spanned!($span => $($tt)*)).unwrap_or_else(|e| panic!("{}", e))
@@ -1,8 +1,13 @@
+use std::collections::HashSet;
 use std::iter;
 
+use proc_macro2::{Group, Ident, TokenStream, TokenTree};
 use proc_macro_error::emit_error;
+use quote::{quote, ToTokens};
+use syn::punctuated::Punctuated;
 use syn::{
-    parse_quote, FnArg, Pat, PatIdent, PatType, Path, PathArguments, Signature, Type, TypeReference,
+    parse_quote, Attribute, FnArg, Pat, PatIdent, PatType, Path, PathArguments, Signature, Token,
+    Type, TypeReference,
 };
 
 pub fn canonicalize_path(path: &Path) -> Path {
@@ -19,6 +24,20 @@ pub fn canonicalize_path(path: &Path) -> Path {
     result
 }
 
+/// Names of the traits derived with `#[derive(...)]`, as written.
+pub fn get_derived_traits(attrs: &[Attribute]) -> HashSet<String> {
+    attrs
+        .iter()
+        .filter(|a| a.path().get_ident().map(ToString::to_string).as_deref() == Some("derive"))
+        .flat_map(|a| {
+            a.parse_args_with(Punctuated::<Ident, Token![,]>::parse_terminated)
+                .into_iter()
+                .flatten()
+        })
+        .map(|i| i.to_string())
+        .collect()
+}
+
 pub fn is_self_method(signature: &Signature) -> bool {
     signature.inputs.iter().any(|i| match i {
         FnArg::Receiver(_) => true,
@@ -96,7 +115,9 @@ pub fn get_env_arg(signature: Signature) -> (Signature, Option<FnArg>) {
 }
 
 pub fn get_class_arg_if_any(signature: Signature) -> (Signature, Option<FnArg>) {
-    let has_explicit_class_ref_arg = if let Some(FnArg::Typed(PatType { ty, .. })) = signature.inputs.iter().next() {
+    let has_explicit_class_ref_arg = if let Some(FnArg::Typed(PatType { ty, .. })) =
+        signature.inputs.iter().next()
+    {
         if let Type::Reference(TypeReference { elem, .. }) = &**ty {
             if let Type::Path(t) = &**elem {
                 let full_path: Path = parse_quote! { ::robusta_jni::jni::objects::GlobalRef };
@@ -114,7 +135,10 @@ pub fn get_class_arg_if_any(signature: Signature) -> (Signature, Option<FnArg>)
             let canonicalized_type_path = canonicalize_path(&t.path);
 
             if canonicalized_type_path == imported_path || canonicalized_type_path == full_path {
-                emit_error!(t, "explicit environment parameter must be of type `&GlobalRef`");
+                emit_error!(
+                    t,
+                    "explicit environment parameter must be of type `&GlobalRef`"
+                );
             }
 
             false
@@ -133,7 +157,6 @@ pub fn get_class_arg_if_any(signature: Signature) -> (Signature, Option<FnArg>)
 
         inner_signature.inputs = iter.collect();
         (inner_signature, class_arg)
-
     } else {
         (signature, None)
     }
@@ -144,3 +167,57 @@ pub fn get_abi(sig: &Signature) -> Option<String> {
         .as_ref()
         .and_then(|l| l.name.as_ref().map(|n| n.value()))
 }
+
+/// Replace every lifetime in `tokens` with `'static`, and `Self` with `self_type`, so that types can be named outside of their impl block.
+pub fn to_static(tokens: TokenStream, self_type: Option<&TokenStream>) -> TokenStream {
+    let mut lifetime = false;
+
+    tokens
+        .into_iter()
+        .flat_map(|t| {
+            let after_quote = std::mem::replace(
+                &mut lifetime,
+                matches!(&t, TokenTree::Punct(p) if p.as_char() == '\''),
+            );
+
+            match t {
+                TokenTree::Ident(i) if after_quote => {
+                    TokenTree::Ident(Ident::new("static", i.span())).into_token_stream()
+                }
+                TokenTree::Ident(i) if i == "Self" => match self_type {
+                    Some(self_type) => to_static(self_type.clone(), None),
+                    None => i.into_token_stream(),
+                },
+                TokenTree::Group(g) => {
+                    let mut group = Group::new(g.delimiter(), to_static(g.stream(), self_type));
+                    group.set_span(g.span());
+                    TokenTree::Group(group).into_token_stream()
+                }
+                t => t.into_token_stream(),
+            }
+        })
+        .collect()
+}
+
+/// Entry of the registry checked by `robusta_jni::verify`.
+/// `kind` is a variant of `robusta_jni::verify::MemberKind`, `signature` a comma-terminated list of `&'static str` expressions.
+pub fn verify_member(kind: &str, class: &str, name: &str, signature: TokenStream) -> TokenStream {
+    let kind = Ident::new(kind, proc_macro2::Span::call_site());
+
+    quote! {
+        ::robusta_jni::verify::Member::new(::robusta_jni::verify::MemberKind::#kind, #class, #name, &[#signature])
+    }
+}
+
+/// Add `members` to the registry checked by `robusta_jni::verify`, if the `verify` feature is enabled.
+pub fn submit_verify_members(members: &[TokenStream]) -> TokenStream {
+    if members.is_empty() || !cfg!(feature = "verify") {
+        return TokenStream::new();
+    }
+
+    quote! {
+        ::robusta_jni::verify::inventory::submit! {
+            ::robusta_jni::verify::Members(&[#(#members),*])
+        }
+    }
+}
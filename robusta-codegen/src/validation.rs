@@ -1,15 +1,21 @@
 use core::option::Option::{None, Some};
 use core::result::Result::{Err, Ok};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
+use proc_macro2::Ident;
 use proc_macro_error::{emit_error, emit_warning};
 use quote::ToTokens;
 use syn::parse::{Parse, ParseBuffer};
 use syn::spanned::Spanned;
 use syn::visit::Visit;
-use syn::{Attribute, Error, GenericParam, Item, ItemImpl, ItemMod, ItemStruct, Result, Type};
+use syn::{
+    Attribute, Error, GenericParam, ImplItem, Item, ItemEnum, ItemImpl, ItemMod, ItemStruct,
+    Result, Type, Visibility,
+};
 
+use crate::derive::utils::get_java_name;
 use crate::transformation::JavaPath;
+use crate::utils::{get_abi, get_derived_traits};
 
 struct AttribItemChecker {
     valid: bool,
@@ -28,59 +34,96 @@ impl<'ast> Visit<'ast> for AttribItemChecker {
         match node {
             Item::Struct(_) => {}
             Item::Const(i) if i.attrs.iter().any(has_package_attribute) => {
-                emit_error!(i.span(), "`package` attribute used on non-struct type");
-                self.valid = false;
-            }
-            Item::Enum(i) if i.attrs.iter().any(has_package_attribute) => {
-                emit_error!(i.span(), "`package` attribute used on non-struct type"; help = i.enum_token.span() => "replace `enum` with `struct`");
+                emit_error!(
+                    i.span(),
+                    "`package` attribute used on non-struct or enum type"
+                );
                 self.valid = false;
             }
+            // Enums can be mapped to Java enums with the conversion derive macros
+            Item::Enum(_) => {}
             Item::ExternCrate(i) if i.attrs.iter().any(has_package_attribute) => {
-                emit_error!(i.span(), "`package` attribute used on non-struct type");
+                emit_error!(
+                    i.span(),
+                    "`package` attribute used on non-struct or enum type"
+                );
                 self.valid = false;
             }
             Item::Fn(i) if i.attrs.iter().any(has_package_attribute) => {
-                emit_error!(i.span(), "`package` attribute used on non-struct type");
+                emit_error!(
+                    i.span(),
+                    "`package` attribute used on non-struct or enum type"
+                );
                 self.valid = false;
             }
             Item::ForeignMod(i) => {
-                emit_error!(i.span(), "`package` attribute used on non-struct type");
+                emit_error!(
+                    i.span(),
+                    "`package` attribute used on non-struct or enum type"
+                );
                 self.valid = false;
             }
             Item::Impl(i) if i.attrs.iter().any(has_package_attribute) => {
-                emit_error!(i.span(), "`package` attribute used on non-struct type");
+                emit_error!(
+                    i.span(),
+                    "`package` attribute used on non-struct or enum type"
+                );
                 self.valid = false;
             }
             Item::Macro(i) if i.attrs.iter().any(has_package_attribute) => {
-                emit_error!(i.span(), "`package` attribute used on non-struct type");
+                emit_error!(
+                    i.span(),
+                    "`package` attribute used on non-struct or enum type"
+                );
                 self.valid = false;
             }
             Item::Mod(i) if i.attrs.iter().any(has_package_attribute) => {
-                emit_error!(i.span(), "`package` attribute used on non-struct type");
+                emit_error!(
+                    i.span(),
+                    "`package` attribute used on non-struct or enum type"
+                );
                 self.valid = false;
             }
             Item::Static(i) if i.attrs.iter().any(has_package_attribute) => {
-                emit_error!(i.span(), "`package` attribute used on non-struct type");
+                emit_error!(
+                    i.span(),
+                    "`package` attribute used on non-struct or enum type"
+                );
                 self.valid = false;
             }
             Item::Trait(i) if i.attrs.iter().any(has_package_attribute) => {
-                emit_error!(i.span(), "`package` attribute used on non-struct type");
+                emit_error!(
+                    i.span(),
+                    "`package` attribute used on non-struct or enum type"
+                );
                 self.valid = false;
             }
             Item::TraitAlias(i) if i.attrs.iter().any(has_package_attribute) => {
-                emit_error!(i.span(), "`package` attribute used on non-struct type");
+                emit_error!(
+                    i.span(),
+                    "`package` attribute used on non-struct or enum type"
+                );
                 self.valid = false;
             }
             Item::Type(i) if i.attrs.iter().any(has_package_attribute) => {
-                emit_error!(i.span(), "`package` attribute used on non-struct type");
+                emit_error!(
+                    i.span(),
+                    "`package` attribute used on non-struct or enum type"
+                );
                 self.valid = false;
             }
             Item::Union(i) if i.attrs.iter().any(has_package_attribute) => {
-                emit_error!(i.span(), "`package` attribute used on non-struct type");
+                emit_error!(
+                    i.span(),
+                    "`package` attribute used on non-struct or enum type"
+                );
                 self.valid = false;
             }
             Item::Use(i) if i.attrs.iter().any(has_package_attribute) => {
-                emit_error!(i.span(), "`package` attribute used on non-struct type");
+                emit_error!(
+                    i.span(),
+                    "`package` attribute used on non-struct or enum type"
+                );
                 self.valid = false;
             }
             Item::Verbatim(_) => {}
@@ -153,9 +196,63 @@ impl<'ast> Visit<'ast> for StructDeclVisitor<'ast> {
     }
 }
 
+/// Collects the classpath path of every struct and enum with a `package` attribute, the enclosing class of nested ones,
+/// and the structs deriving `NativeHandle`.
+#[derive(Default)]
+struct JavaClassVisitor {
+    java_classes: BTreeMap<String, String>,
+    outer_classes: BTreeMap<String, JavaPath>,
+    native_handles: BTreeSet<String>,
+}
+
+impl JavaClassVisitor {
+    fn add(&mut self, ident: &Ident, attrs: &[Attribute]) {
+        let parse_attribute = |name: &str| {
+            attrs
+                .iter()
+                .find(|a| a.path().is_ident(name))
+                .and_then(|a| a.parse_args::<JavaPath>().ok())
+        };
+
+        let outer_class = parse_attribute("outer_class");
+        if let Some(package) = parse_attribute("package") {
+            let classpath_path = package.class_path(outer_class.as_ref(), &ident.to_string());
+            self.java_classes.insert(ident.to_string(), classpath_path);
+        }
+
+        if let Some(outer_class) = outer_class {
+            self.outer_classes.insert(ident.to_string(), outer_class);
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for JavaClassVisitor {
+    fn visit_item_struct(&mut self, node: &'ast ItemStruct) {
+        self.add(&node.ident, &node.attrs);
+
+        if get_derived_traits(&node.attrs).contains("NativeHandle") {
+            self.native_handles.insert(node.ident.to_string());
+        }
+    }
+
+    fn visit_item_enum(&mut self, node: &'ast ItemEnum) {
+        self.add(&node.ident, &node.attrs);
+    }
+}
+
 pub(crate) struct JNIBridgeModule {
     pub(crate) module_decl: ItemMod,
     pub(crate) package_map: BTreeMap<String, Option<JavaPath>>,
+    // structs whose `&mut self` methods write their fields back to the Java instance
+    pub(crate) write_back_structs: BTreeSet<String>,
+    // Java names shared by several exported methods of a struct
+    pub(crate) overloaded_methods: BTreeMap<String, BTreeSet<String>>,
+    // classpath path of every `package`-annotated type, by name
+    pub(crate) java_classes: BTreeMap<String, String>,
+    // enclosing class of every `outer_class`-annotated type, by name
+    pub(crate) outer_classes: BTreeMap<String, JavaPath>,
+    // structs deriving `NativeHandle`, owned by their Java peer
+    pub(crate) native_handles: BTreeSet<String>,
 }
 
 impl Parse for JNIBridgeModule {
@@ -281,6 +378,51 @@ impl Parse for JNIBridgeModule {
             })
             .collect();
 
+        let write_back_structs: BTreeSet<String> = bridged_structs
+            .iter()
+            .filter(|s| {
+                s.attrs
+                    .iter()
+                    .any(|a| a.path().segments.last().unwrap().ident == "write_back")
+            })
+            .map(|s| s.ident.to_string())
+            .collect();
+
+        let mut exported_names: BTreeMap<(String, String), usize> = BTreeMap::new();
+        for (struct_item, item_impl) in &bridged_impls {
+            for item in &item_impl.items {
+                if let ImplItem::Fn(method) = item {
+                    let abi = get_abi(&method.sig);
+                    if !matches!(abi.as_deref(), Some("jni") | Some("java")) {
+                        continue;
+                    }
+
+                    let java_name = get_java_name(&method.attrs);
+                    if abi.as_deref() == Some("jni") && matches!(method.vis, Visibility::Public(_))
+                    {
+                        let java_name = java_name.unwrap_or_else(|| method.sig.ident.to_string());
+                        *exported_names
+                            .entry((struct_item.ident.to_string(), java_name))
+                            .or_default() += 1;
+                    }
+                }
+            }
+        }
+
+        let mut overloaded_methods: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        exported_names
+            .into_iter()
+            .filter(|(_, count)| *count > 1)
+            .for_each(|((struct_name, java_name), _)| {
+                overloaded_methods
+                    .entry(struct_name)
+                    .or_default()
+                    .insert(java_name);
+            });
+
+        let mut java_class_visitor = JavaClassVisitor::default();
+        java_class_visitor.visit_item_mod(&module_decl);
+
         if !valid_input {
             Err(Error::new(
                 module_decl.span(),
@@ -290,6 +432,11 @@ impl Parse for JNIBridgeModule {
             Ok(JNIBridgeModule {
                 module_decl,
                 package_map,
+                write_back_structs,
+                overloaded_methods,
+                java_classes: java_class_visitor.java_classes,
+                outer_classes: java_class_visitor.outer_classes,
+                native_handles: java_class_visitor.native_handles,
             })
         }
     }
@@ -12,8 +12,8 @@ class HelloWorld {
         return i + u;
     }
 
-    // pub extern "jni" fn catchMe(self, _env: &JNIEnv) -> JniResult<i32>
-    private native void catchMe() throws IllegalArgumentException;
+    // pub extern "jni" fn catchMe(self, _env: &JNIEnv) -> Result<i32, String>
+    private native void catchMe();
 
     // pub extern "java" fn javaAdd(&self, i: i32, u: i32) -> i32 {}
     public int javaAdd(int i, int u) {
@@ -43,7 +43,7 @@ class HelloWorld {
 
         try {
             h.catchMe();
-        } catch (IllegalArgumentException e) {
+        } catch (RuntimeException e) {
             System.out.println("Caught exception. Message: \"" + e.getMessage() + "\"");
             System.out.println("Printing stacktrace:");
             e.printStackTrace();
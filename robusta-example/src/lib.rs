@@ -1,11 +1,11 @@
 use robusta_jni::bridge;
 
 #[bridge]
+#[allow(clippy::disallowed_names)]
 mod jni {
     use robusta_jni::convert::{
         Field, IntoJavaValue, Signature, TryFromJavaValue, TryIntoJavaValue,
     };
-    use robusta_jni::jni::errors::Error as JniError;
     use robusta_jni::jni::errors::Result as JniResult;
     use robusta_jni::jni::objects::AutoLocal;
     use robusta_jni::jni::JNIEnv;
@@ -37,12 +37,8 @@ mod jni {
             }
         }
 
-        #[call_type(safe(
-            exception_class = "java.lang.IllegalArgumentException",
-            message = "something bad happened"
-        ))]
-        pub extern "jni" fn catchMe(self, _env: &JNIEnv) -> JniResult<i32> {
-            Err(JniError::NullPtr("catch me if you can"))
+        pub extern "jni" fn catchMe(self, _env: &JNIEnv) -> Result<i32, String> {
+            Err("catch me if you can".to_string())
         }
 
         pub extern "java" fn javaAdd(&self, _env: &JNIEnv, i: i32, u: i32) -> JniResult<i32> {}
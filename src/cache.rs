@@ -0,0 +1,469 @@
+//! Cached class, method and field lookups.
+//!
+//! Looking up a class by name and a method or field by name and signature is expensive compared to the call itself,
+//! and the result never changes while the class is loaded.
+//! The glue generated for `extern "java"` methods and by the conversion derive macros keeps one cache per call site in a `static`:
+//! the first call resolves the class (kept as a global reference, so that it can't be unloaded while its IDs are in use)
+//! and the method or field ID, along with the parsed return type, and every subsequent call goes straight through the
+//! `_unchecked` JNI entry points.
+//! Instance methods and fields check that the object is an instance of the class first, and throw a `ClassCastException` if it isn't.
+//!
+//! The signature of the method or field is given as a closure, so that it's only built on the first call.
+//!
+//! Since JNI doesn't check the `_unchecked` calls, calling methods and constructors and setting fields is `unsafe`:
+//! the arguments must match the signature, and a cache must always be used with the same class, name and signature,
+//! which are only read on the first call.
+//! Caches can also be used in hand-written code:
+//! ```
+//! use robusta_jni::cache::CachedStaticMethod;
+//! use robusta_jni::jni::errors::Result;
+//! use robusta_jni::jni::objects::JValue;
+//! use robusta_jni::jni::JNIEnv;
+//!
+//! fn max(env: &JNIEnv, a: i32, b: i32) -> Result<i32> {
+//!     static MAX: CachedStaticMethod = CachedStaticMethod::new();
+//!
+//!     // Two `int`s, as declared by the signature
+//!     unsafe { MAX.call(env, "java/lang/Math", "max", || "(II)I".into(), &[JValue::Int(a), JValue::Int(b)]) }?
+//!         .i()
+//! }
+//! ```
+//!
+//! Classes are looked up with [`class_loader::find_class`](crate::class_loader::find_class), so that caches can be used from any thread.
+//!
+//! If a lookup fails, the error is returned and nothing is cached, so that the lookup is retried on the next call.
+//!
+
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use jni::errors::{Error, Result};
+use jni::objects::{GlobalRef, JClass, JFieldID, JMethodID, JObject, JStaticMethodID, JValue};
+use jni::signature::ReturnType;
+use jni::sys::jvalue;
+use jni::JNIEnv;
+
+/// A resolved member ID, along with the class declaring it.
+struct Resolved<T> {
+    class: GlobalRef,
+    id: T,
+    return_type: ReturnType,
+}
+
+/// Get the value in `cell`, initializing it with `init` if empty.
+/// If another thread initializes the cell first, the value computed by this thread is discarded.
+fn get_or_try_init<T>(cell: &OnceLock<T>, init: impl FnOnce() -> Result<T>) -> Result<&T> {
+    if let Some(v) = cell.get() {
+        return Ok(v);
+    }
+
+    let v = init()?;
+    Ok(cell.get_or_init(|| v))
+}
+
+fn resolve<T>(
+    env: &JNIEnv,
+    classpath_path: &str,
+    return_type: &str,
+    id: impl FnOnce(JClass) -> Result<T>,
+) -> Result<Resolved<T>> {
+    let class = crate::class_loader::find_class(env, classpath_path)?;
+    let resolved = id(class).and_then(|id| {
+        Ok(Resolved {
+            class: env.new_global_ref(class)?,
+            id,
+            return_type: ReturnType::from_str(return_type)?,
+        })
+    });
+    env.delete_local_ref(class.into())?;
+
+    resolved
+}
+
+/// Throw a `ClassCastException` unless `obj` is an instance of the class declaring a member.
+/// Member IDs are resolved on the declaring class, so using them with any other object is undefined behaviour.
+fn check_instance<T>(
+    env: &JNIEnv,
+    obj: JObject,
+    member: &Resolved<T>,
+    classpath_path: &str,
+) -> Result<()> {
+    if obj.is_null() {
+        return Err(Error::NullPtr("obj argument"));
+    }
+
+    if env.is_instance_of(obj, &member.class)? {
+        Ok(())
+    } else {
+        env.throw_new(
+            "java/lang/ClassCastException",
+            format!(
+                "object is not an instance of {}",
+                classpath_path.replace('/', ".")
+            ),
+        )?;
+        Err(Error::JavaException)
+    }
+}
+
+/// Return type of a method signature, i.e. everything after the closing parenthesis.
+fn method_return_type(signature: &str) -> &str {
+    signature
+        .rfind(')')
+        .map(|i| &signature[i + 1..])
+        .unwrap_or(signature)
+}
+
+fn to_jni(args: &[JValue]) -> Vec<jvalue> {
+    args.iter().map(|v| v.to_jni()).collect()
+}
+
+/// Cached class lookup.
+pub struct CachedClass {
+    class: OnceLock<GlobalRef>,
+}
+
+impl CachedClass {
+    /// Create an empty cache.
+    pub const fn new() -> Self {
+        CachedClass {
+            class: OnceLock::new(),
+        }
+    }
+
+    /// Get the class, looking it up on the first call.
+    ///
+    /// The cache must be a `static`, because the returned reference is only valid as long as the cache holds the global reference.
+    pub fn get<'env>(
+        &'static self,
+        env: &JNIEnv<'env>,
+        classpath_path: &str,
+    ) -> Result<JClass<'env>> {
+        let class = get_or_try_init(&self.class, || {
+            let class = crate::class_loader::find_class(env, classpath_path)?;
+            let global = env.new_global_ref(class);
+            env.delete_local_ref(class.into())?;
+            global
+        })?;
+
+        // The global reference is never deleted, since the cache is never dropped
+        Ok(JClass::from(unsafe {
+            JObject::from_raw(class.as_obj().into_raw())
+        }))
+    }
+}
+
+impl Default for CachedClass {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Cached instance method lookup.
+pub struct CachedMethod {
+    method: OnceLock<Resolved<JMethodID>>,
+}
+
+impl CachedMethod {
+    /// Create an empty cache.
+    pub const fn new() -> Self {
+        CachedMethod {
+            method: OnceLock::new(),
+        }
+    }
+
+    /// Call the method on `obj`, looking it up on the first call.
+    ///
+    /// # Safety
+    ///
+    /// `args` must match the parameter types of `signature`, and every call must be made with the same `classpath_path`,
+    /// `name` and `signature`, which are ignored once the method is cached.
+    pub unsafe fn call<'env>(
+        &self,
+        env: &JNIEnv<'env>,
+        obj: JObject<'env>,
+        classpath_path: &str,
+        name: &str,
+        signature: impl FnOnce() -> String,
+        args: &[JValue],
+    ) -> Result<JValue<'env>> {
+        let method = get_or_try_init(&self.method, || {
+            let signature = signature();
+            resolve(
+                env,
+                classpath_path,
+                method_return_type(&signature),
+                |class| env.get_method_id(class, name, &signature),
+            )
+        })?;
+        check_instance(env, obj, method, classpath_path)?;
+
+        env.call_method_unchecked(obj, method.id, method.return_type.clone(), &to_jni(args))
+    }
+}
+
+impl Default for CachedMethod {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Cached static method lookup.
+pub struct CachedStaticMethod {
+    method: OnceLock<Resolved<JStaticMethodID>>,
+}
+
+impl CachedStaticMethod {
+    /// Create an empty cache.
+    pub const fn new() -> Self {
+        CachedStaticMethod {
+            method: OnceLock::new(),
+        }
+    }
+
+    /// Call the static method, looking it up on the first call.
+    ///
+    /// # Safety
+    ///
+    /// `args` must match the parameter types of `signature`, and every call must be made with the same `classpath_path`,
+    /// `name` and `signature`, which are ignored once the method is cached.
+    pub unsafe fn call<'env>(
+        &self,
+        env: &JNIEnv<'env>,
+        classpath_path: &str,
+        name: &str,
+        signature: impl FnOnce() -> String,
+        args: &[JValue],
+    ) -> Result<JValue<'env>> {
+        let method = get_or_try_init(&self.method, || {
+            let signature = signature();
+            resolve(
+                env,
+                classpath_path,
+                method_return_type(&signature),
+                |class| env.get_static_method_id(class, name, &signature),
+            )
+        })?;
+
+        env.call_static_method_unchecked(
+            &method.class,
+            method.id,
+            method.return_type.clone(),
+            &to_jni(args),
+        )
+    }
+}
+
+impl Default for CachedStaticMethod {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Cached constructor lookup.
+pub struct CachedConstructor {
+    constructor: OnceLock<Resolved<JMethodID>>,
+}
+
+impl CachedConstructor {
+    /// Create an empty cache.
+    pub const fn new() -> Self {
+        CachedConstructor {
+            constructor: OnceLock::new(),
+        }
+    }
+
+    /// Create a new object calling the constructor, looking it up on the first call.
+    ///
+    /// # Safety
+    ///
+    /// `args` must match the parameter types of `signature`, and every call must be made with the same `classpath_path`
+    /// and `signature`, which are ignored once the constructor is cached.
+    pub unsafe fn new_object<'env>(
+        &self,
+        env: &JNIEnv<'env>,
+        classpath_path: &str,
+        signature: impl FnOnce() -> String,
+        args: &[JValue],
+    ) -> Result<JObject<'env>> {
+        let constructor = get_or_try_init(&self.constructor, || {
+            let signature = signature();
+            resolve(env, classpath_path, "V", |class| {
+                env.get_method_id(class, "<init>", &signature)
+            })
+        })?;
+
+        env.new_object_unchecked(&constructor.class, constructor.id, args)
+    }
+}
+
+impl Default for CachedConstructor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Cached instance field lookup.
+pub struct CachedField {
+    field: OnceLock<Resolved<JFieldID>>,
+}
+
+impl CachedField {
+    /// Create an empty cache.
+    pub const fn new() -> Self {
+        CachedField {
+            field: OnceLock::new(),
+        }
+    }
+
+    fn resolve(
+        &self,
+        env: &JNIEnv,
+        classpath_path: &str,
+        name: &str,
+        signature: &str,
+    ) -> Result<&Resolved<JFieldID>> {
+        get_or_try_init(&self.field, || {
+            resolve(env, classpath_path, signature, |class| {
+                env.get_field_id(class, name, signature)
+            })
+        })
+    }
+
+    /// Get the value of the field of `obj`, looking it up on the first call.
+    pub fn get<'env>(
+        &self,
+        env: &JNIEnv<'env>,
+        obj: JObject<'env>,
+        classpath_path: &str,
+        name: &str,
+        signature: &str,
+    ) -> Result<JValue<'env>> {
+        let field = self.resolve(env, classpath_path, name, signature)?;
+        check_instance(env, obj, field, classpath_path)?;
+
+        env.get_field_unchecked(obj, field.id, field.return_type.clone())
+    }
+
+    /// Set the value of the field of `obj`, looking it up on the first call.
+    ///
+    /// # Safety
+    ///
+    /// `value` must match the type of the field, and every call must be made with the same `classpath_path`,
+    /// `name` and `signature`, which are ignored once the field is cached.
+    pub unsafe fn set<'env>(
+        &self,
+        env: &JNIEnv<'env>,
+        obj: JObject<'env>,
+        classpath_path: &str,
+        name: &str,
+        signature: &str,
+        value: JValue,
+    ) -> Result<()> {
+        let field = self.resolve(env, classpath_path, name, signature)?;
+        check_instance(env, obj, field, classpath_path)?;
+
+        env.set_field_unchecked(obj, field.id, value)
+    }
+}
+
+impl Default for CachedField {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Cached constants of a Java enum, used by the enum conversion derive macros.
+///
+/// `names` are the names of the constants mapped to Rust variants, in the order of the variants.
+pub struct CachedEnumConstants {
+    constants: OnceLock<Vec<GlobalRef>>,
+}
+
+impl CachedEnumConstants {
+    /// Create an empty cache.
+    pub const fn new() -> Self {
+        CachedEnumConstants {
+            constants: OnceLock::new(),
+        }
+    }
+
+    fn resolve(
+        &self,
+        env: &JNIEnv,
+        classpath_path: &str,
+        signature: &str,
+        names: &[&str],
+    ) -> Result<&[GlobalRef]> {
+        get_or_try_init(&self.constants, || {
+            let class = crate::class_loader::find_class(env, classpath_path)?;
+            let constants = names
+                .iter()
+                .map(|name| {
+                    let constant = env.get_static_field(class, *name, signature)?.l()?;
+                    let global = env.new_global_ref(constant);
+                    env.delete_local_ref(constant)?;
+                    global
+                })
+                .collect();
+            env.delete_local_ref(class.into())?;
+
+            constants
+        })
+        .map(Vec::as_slice)
+    }
+
+    /// Get the constant `names[index]`, looking up every constant on the first call.
+    pub fn get<'env>(
+        &self,
+        env: &JNIEnv<'env>,
+        classpath_path: &str,
+        signature: &str,
+        names: &[&str],
+        index: usize,
+    ) -> Result<JObject<'env>> {
+        let constants = self.resolve(env, classpath_path, signature, names)?;
+
+        env.new_local_ref(constants[index].as_obj())
+    }
+
+    /// Get the index in `names` of the constant `obj`, looking up every constant on the first call.
+    ///
+    /// Throws an `IllegalArgumentException` if `obj` isn't one of the constants.
+    pub fn index_of(
+        &self,
+        env: &JNIEnv,
+        obj: JObject,
+        classpath_path: &str,
+        signature: &str,
+        names: &[&str],
+    ) -> Result<usize> {
+        if obj.is_null() {
+            return Err(Error::NullPtr("enum constant"));
+        }
+
+        let constants = self.resolve(env, classpath_path, signature, names)?;
+        for (index, constant) in constants.iter().enumerate() {
+            if env.is_same_object(obj, constant.as_obj())? {
+                return Ok(index);
+            }
+        }
+
+        let name = crate::convert::java_enum_constant_name(obj, env)?;
+        env.throw_new(
+            "java/lang/IllegalArgumentException",
+            format!(
+                "unknown {} constant: {}",
+                classpath_path.replace('/', "."),
+                name
+            ),
+        )?;
+        Err(Error::JavaException)
+    }
+}
+
+impl Default for CachedEnumConstants {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,121 @@
+//! Class lookups from any thread.
+//!
+//! `FindClass` looks up classes with the class loader of the native method being called, or with the system class loader
+//! if there's none, e.g. on a thread spawned from Rust. On Android, or in servlet and OSGi containers, the system class loader
+//! can't find application classes.
+//!
+//! The glue generated by `#[bridge]` captures the class loader of every `#[package]` struct of the bridge module,
+//! from `JNI_OnLoad` with `#[bridge(register_natives)]` or `#[bridge(verify)]`, or else on the first call to one of its native methods.
+//! [`find_class`] then loads these classes with `ClassLoader.loadClass`, and other classes with `FindClass`, falling back to
+//! the first captured class loader if they can't be found.
+//!
+//! Imported methods, [`Field`](crate::convert::Field) and the [`cache`](crate::cache) module look up classes with [`find_class`],
+//! so that they can be used from threads spawned from Rust (see the [`vm`](crate::vm) module) without passing class references around.
+//!
+
+use std::collections::BTreeMap;
+use std::sync::{OnceLock, PoisonError, RwLock};
+
+use jni::errors::{Error, Result};
+use jni::objects::{GlobalRef, JClass, JObject, JValue};
+use jni::JNIEnv;
+
+static CLASS_LOADERS: RwLock<BTreeMap<String, GlobalRef>> = RwLock::new(BTreeMap::new());
+
+static DEFAULT_CLASS_LOADER: OnceLock<GlobalRef> = OnceLock::new();
+
+/// Class loader captured for the class `classpath_path` (e.g. `com/example/robusta/HelloWorld`), if any.
+pub fn class_loader(classpath_path: &str) -> Option<GlobalRef> {
+    CLASS_LOADERS
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(classpath_path)
+        .cloned()
+}
+
+/// First captured class loader, if any.
+#[cfg(feature = "verify")]
+pub(crate) fn default_class_loader() -> Option<GlobalRef> {
+    DEFAULT_CLASS_LOADER.get().cloned()
+}
+
+/// Capture the class loader of `class`, unless it's the bootstrap class loader.
+pub(crate) fn capture_class(env: &JNIEnv, classpath_path: &str, class: JClass) -> Result<()> {
+    let loader = env
+        .call_method(class, "getClassLoader", "()Ljava/lang/ClassLoader;", &[])?
+        .l()?;
+    if loader.is_null() {
+        return Ok(());
+    }
+
+    let global = env.new_global_ref(loader);
+    env.delete_local_ref(loader)?;
+    let loader = global?;
+    let _ = DEFAULT_CLASS_LOADER.set(loader.clone());
+    CLASS_LOADERS
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .entry(classpath_path.to_string())
+        .or_insert(loader);
+
+    Ok(())
+}
+
+/// Capture the class loader of every class of `classpath_paths`, if not captured already.
+///
+/// Called by the generated glue from `JNI_OnLoad` or a native method, where `FindClass` uses the class loader of the library.
+/// Classes that can't be found are skipped.
+pub fn capture(env: &JNIEnv, classpath_paths: &[&str]) {
+    for classpath_path in classpath_paths {
+        if class_loader(classpath_path).is_some() {
+            continue;
+        }
+
+        let captured = env.find_class(*classpath_path).and_then(|class| {
+            let captured = capture_class(env, classpath_path, class);
+            env.delete_local_ref(class.into())?;
+            captured
+        });
+        if captured.is_err() {
+            let _ = env.exception_clear();
+        }
+    }
+}
+
+fn load_class<'env>(
+    env: &JNIEnv<'env>,
+    loader: &GlobalRef,
+    classpath_path: &str,
+) -> Result<JClass<'env>> {
+    let name = env.new_string(classpath_path.replace('/', "."))?;
+    // The global reference outlives the call
+    let loader = unsafe { JObject::from_raw(loader.as_obj().into_raw()) };
+
+    let class = env.call_method(
+        loader,
+        "loadClass",
+        "(Ljava/lang/String;)Ljava/lang/Class;",
+        &[JValue::Object(JObject::from(name))],
+    );
+    env.delete_local_ref(name.into())?;
+
+    class?.l().map(JClass::from)
+}
+
+/// Find the class `classpath_path` (e.g. `com/example/robusta/HelloWorld`) from any thread.
+///
+/// Classes with a captured class loader are loaded with it. Other classes are looked up with `FindClass`,
+/// then with the first captured class loader if they can't be found.
+pub fn find_class<'env>(env: &JNIEnv<'env>, classpath_path: &str) -> Result<JClass<'env>> {
+    if let Some(loader) = class_loader(classpath_path) {
+        return load_class(env, &loader, classpath_path);
+    }
+
+    match (env.find_class(classpath_path), DEFAULT_CLASS_LOADER.get()) {
+        (Err(Error::JavaException), Some(loader)) => {
+            env.exception_clear()?;
+            load_class(env, loader, classpath_path)
+        }
+        (result, _) => result,
+    }
+}
@@ -0,0 +1,211 @@
+//! `java.lang.ref.Cleaner` integration.
+//!
+//! Cleaning actions are instances of a `Runnable` class defined by this module, whose native `run` method calls a release function.
+//! The release functions stay on the Rust side, in a registry keyed by an opaque identifier: the Java action only holds the identifier,
+//! so that nothing read from the Java heap is ever called.
+//!
+//! The class is defined by a class loader of its own, so that every library linking `robusta_jni` has its own class,
+//! bound to its own `run` method and registry.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Mutex, OnceLock, PoisonError};
+
+use jni::errors::Result;
+use jni::objects::{GlobalRef, JObject, JValue};
+use jni::sys::jlong;
+use jni::{JNIEnv, NativeMethod};
+
+/// Name of the class of the cleaning actions.
+const ACTION_CLASS: &str = "robusta/Cleanup";
+
+/// Class file of:
+/// ```java
+/// package robusta;
+///
+/// public final class Cleanup implements Runnable {
+///     private long id;
+///
+///     public native void run();
+/// }
+/// ```
+/// It has no constructor: instances are created with `AllocObject`.
+#[rustfmt::skip]
+const ACTION_CLASS_FILE: &[u8] = &[
+    0xCA, 0xFE, 0xBA, 0xBE, // magic
+    0x00, 0x00, 0x00, 0x34, // version 52.0 (Java 8)
+    0x00, 0x0B, // constant pool count
+    0x01, 0x00, 0x0F, b'r', b'o', b'b', b'u', b's', b't', b'a', b'/', b'C', b'l', b'e', b'a', b'n', b'u',
+    b'p', // #1 Utf8 robusta/Cleanup
+    0x07, 0x00, 0x01, // #2 Class #1
+    0x01, 0x00, 0x10, b'j', b'a', b'v', b'a', b'/', b'l', b'a', b'n', b'g', b'/', b'O', b'b', b'j', b'e',
+    b'c', b't', // #3 Utf8 java/lang/Object
+    0x07, 0x00, 0x03, // #4 Class #3
+    0x01, 0x00, 0x12, b'j', b'a', b'v', b'a', b'/', b'l', b'a', b'n', b'g', b'/', b'R', b'u', b'n', b'n',
+    b'a', b'b', b'l', b'e', // #5 Utf8 java/lang/Runnable
+    0x07, 0x00, 0x05, // #6 Class #5
+    0x01, 0x00, 0x02, b'i', b'd', // #7 Utf8 id
+    0x01, 0x00, 0x01, b'J', // #8 Utf8 J
+    0x01, 0x00, 0x03, b'r', b'u', b'n', // #9 Utf8 run
+    0x01, 0x00, 0x03, b'(', b')', b'V', // #10 Utf8 ()V
+    0x00, 0x31, // public final super
+    0x00, 0x02, // this class
+    0x00, 0x04, // super class
+    0x00, 0x01, 0x00, 0x06, // interfaces
+    0x00, 0x01, // fields count
+    0x00, 0x02, 0x00, 0x07, 0x00, 0x08, 0x00, 0x00, // private long id
+    0x00, 0x01, // methods count
+    0x01, 0x01, 0x00, 0x09, 0x00, 0x0A, 0x00, 0x00, // public native void run()
+    0x00, 0x00, // attributes count
+];
+
+type Release = Box<dyn FnOnce() + Send>;
+
+/// Release functions of the registered objects, by identifier.
+static ACTIONS: Mutex<BTreeMap<jlong, Release>> = Mutex::new(BTreeMap::new());
+
+static NEXT_ID: AtomicI64 = AtomicI64::new(1);
+
+/// Cleaner shared by every registered object, and class of the cleaning actions.
+static CLEANER: OnceLock<(GlobalRef, GlobalRef)> = OnceLock::new();
+
+extern "system" fn run(env: JNIEnv, action: JObject) {
+    let released = crate::panic::catch_unwind(false, || -> Result<()> {
+        let id = env.get_field(action, "id", "J")?.j()?;
+        // Removed first, so that it's called at most once
+        let release = ACTIONS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&id);
+
+        if let Some(release) = release {
+            release();
+        }
+
+        Ok(())
+    });
+
+    match released {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => eprintln!("Error while running cleaning action: {}", e),
+        Err(panic) => crate::panic::throw_panic(&env, "java/lang/Error", panic),
+    }
+}
+
+fn init(env: &JNIEnv) -> Result<(GlobalRef, GlobalRef)> {
+    // An empty class loader delegating to the bootstrap class loader
+    let urls = env.new_object_array(0, "java/net/URL", JObject::null())?;
+    let loader = env.new_object(
+        "java/net/URLClassLoader",
+        "([Ljava/net/URL;Ljava/lang/ClassLoader;)V",
+        &[
+            JValue::Object(unsafe { JObject::from_raw(urls) }),
+            JValue::Object(JObject::null()),
+        ],
+    )?;
+    let class = env.define_class(ACTION_CLASS, loader, ACTION_CLASS_FILE)?;
+    env.register_native_methods(
+        class,
+        &[NativeMethod {
+            name: "run".into(),
+            sig: "()V".into(),
+            fn_ptr: run as *mut std::ffi::c_void,
+        }],
+    )?;
+
+    let cleaner = env
+        .call_static_method(
+            "java/lang/ref/Cleaner",
+            "create",
+            "()Ljava/lang/ref/Cleaner;",
+            &[],
+        )?
+        .l()?;
+
+    Ok((env.new_global_ref(cleaner)?, env.new_global_ref(class)?))
+}
+
+/// Register `obj` with the cleaner, calling `release` once it's unreachable.
+///
+/// `release` is dropped without being called if registering fails.
+pub(crate) fn register<F>(env: &JNIEnv, obj: JObject, release: F) -> Result<()>
+where
+    F: FnOnce() + Send + 'static,
+{
+    let (cleaner, class) = match CLEANER.get() {
+        Some(cleaner) => cleaner,
+        None => {
+            let cleaner = init(env)?;
+            CLEANER.get_or_init(|| cleaner)
+        }
+    };
+
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let action = env.alloc_object(class)?;
+    env.set_field(action, "id", "J", JValue::Long(id))?;
+
+    ACTIONS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(id, Box::new(release));
+
+    let cleanable = env
+        .call_method(
+            cleaner.as_obj(),
+            "register",
+            "(Ljava/lang/Object;Ljava/lang/Runnable;)Ljava/lang/ref/Cleaner$Cleanable;",
+            &[JValue::Object(obj), JValue::Object(action)],
+        )
+        .and_then(|cleanable| cleanable.l());
+
+    let cleanable = match cleanable {
+        Ok(cleanable) => cleanable,
+        Err(e) => {
+            // Never called by the cleaner
+            let release = ACTIONS
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .remove(&id);
+            drop(release);
+            return Err(e);
+        }
+    };
+
+    env.delete_local_ref(cleanable)?;
+    env.delete_local_ref(action)
+}
+
+#[cfg(test)]
+mod test {
+    use robusta_build::classfile::{ClassFile, ACC_PUBLIC};
+
+    use super::*;
+
+    const ACC_PRIVATE: u16 = 0x0002;
+    const ACC_FINAL: u16 = 0x0010;
+    const ACC_NATIVE: u16 = 0x0100;
+
+    #[test]
+    fn action_class_file_matches_documented_source() {
+        let class = ClassFile::parse(ACTION_CLASS_FILE).unwrap();
+
+        assert_eq!(class.name, ACTION_CLASS);
+        assert_eq!(class.super_class.as_deref(), Some("java/lang/Object"));
+        assert_eq!(class.interfaces, vec!["java/lang/Runnable"]);
+        assert!(class.is_public() && class.access_flags & ACC_FINAL != 0);
+
+        let fields: Vec<_> = class
+            .fields
+            .iter()
+            .map(|f| (f.name.as_str(), f.descriptor.as_str(), f.access_flags))
+            .collect();
+        assert_eq!(fields, vec![("id", "J", ACC_PRIVATE)]);
+
+        let methods: Vec<_> = class
+            .methods
+            .iter()
+            .map(|m| (m.name.as_str(), m.descriptor.as_str(), m.access_flags))
+            .collect();
+        assert_eq!(methods, vec![("run", "()V", ACC_PUBLIC | ACC_NATIVE)]);
+    }
+}
@@ -0,0 +1,241 @@
+//! Conversions for direct [`java.nio.ByteBuffer`](https://docs.oracle.com/en/java/javase/15/docs/api/java.base/java/nio/ByteBuffer.html)s.
+//!
+//! Borrowed slices (`&'borrow [u8]` and `&'borrow mut [u8]`) can be used as `extern "jni"` method parameters to access
+//! the content of a direct `ByteBuffer` in place. The slice covers the *remaining* bytes of the buffer
+//! (from its position up to its limit); neither position nor limit are updated.
+//!
+//! Heap buffers (i.e. created with `ByteBuffer.allocate` or `ByteBuffer.wrap`) have no stable native address, so passing
+//! one where a slice is expected is a conversion error, reported like any other conversion error
+//! (a Java exception with `#[call_type(safe)]`, a panic with `#[call_type(unchecked)]`).
+//!
+//! Read-only buffers can only be borrowed as `&[u8]`: passing one where a `&mut [u8]` is expected throws a
+//! `java.nio.ReadOnlyBufferException`.
+//!
+//! Note that the JVM doesn't prevent other Java threads from accessing the buffer while native code holds the slice,
+//! and nothing prevents the same buffer from being passed twice as a `&mut [u8]` argument: keeping buffers confined
+//! to a single native call is up to the caller.
+//!
+//! [`DirectByteBuffer`] is an owned buffer that can be returned to Java (or received from it).
+//! When converted to Java, its Rust allocation is handed over to a new direct `ByteBuffer` created with `NewDirectByteBuffer`,
+//! without copying the bytes. The allocation is then owned by the Java buffer: it's freed by a `java.lang.ref.Cleaner`
+//! once the buffer is unreachable (Java 9 and later, not available on Android).
+//! When received from Java, the remaining bytes of the direct buffer are copied into a new Rust allocation.
+//!
+//! Example:
+//! ```
+//! use robusta_jni::bridge;
+//!
+//! #[bridge]
+//! mod jni {
+//!     use robusta_jni::convert::DirectByteBuffer;
+//!
+//!     #[package(com.example.robusta)]
+//!     struct Buffers;
+//!
+//!     impl<'env: 'borrow, 'borrow> Buffers {
+//!         pub extern "jni" fn checksum(data: &'borrow [u8]) -> i32 {
+//!             data.iter().map(|&b| b as i32).sum()
+//!         }
+//!
+//!         pub extern "jni" fn fill(data: &'borrow mut [u8], value: i8) {
+//!             data.fill(value as u8)
+//!         }
+//!
+//!         pub extern "jni" fn allocate(len: i32) -> DirectByteBuffer {
+//!             vec![0; len as usize].into()
+//!         }
+//!     }
+//! }
+//! ```
+//!
+
+use std::ops::{Deref, DerefMut};
+use std::{ptr, slice};
+
+use jni::errors::{Error, Result};
+use jni::objects::{JByteBuffer, JObject, JThrowable};
+use jni::JNIEnv;
+
+use crate::cache::CachedMethod;
+use crate::convert::{FromJavaValue, IntoJavaValue, Signature, TryFromJavaValue, TryIntoJavaValue};
+
+const BYTE_BUFFER_SIG_TYPE: &str = "Ljava/nio/ByteBuffer;";
+
+const BUFFER_CLASS: &str = "java/nio/Buffer";
+
+/// Get a pointer to the remaining bytes of a direct `ByteBuffer`, along with their count.
+///
+/// If `mutable` is set, read-only buffers are rejected with a `java.nio.ReadOnlyBufferException`.
+fn remaining(buffer: JObject, env: &JNIEnv, mutable: bool) -> Result<(*mut u8, usize)> {
+    static IS_DIRECT: CachedMethod = CachedMethod::new();
+    static IS_READ_ONLY: CachedMethod = CachedMethod::new();
+    static POSITION: CachedMethod = CachedMethod::new();
+    static LIMIT: CachedMethod = CachedMethod::new();
+
+    if buffer.is_null() {
+        return Err(Error::NullPtr("ByteBuffer argument"));
+    }
+
+    // The `Buffer` methods called here take no arguments
+    let call = |method: &CachedMethod, name: &str, signature: &str| unsafe {
+        method.call(env, buffer, BUFFER_CLASS, name, || signature.into(), &[])
+    };
+
+    if !call(&IS_DIRECT, "isDirect", "()Z")?.z()? {
+        return Err(Error::WrongJValueType(
+            "direct ByteBuffer",
+            "heap ByteBuffer",
+        ));
+    }
+
+    if mutable && call(&IS_READ_ONLY, "isReadOnly", "()Z")?.z()? {
+        let exception = env.new_object("java/nio/ReadOnlyBufferException", "()V", &[])?;
+        env.throw(JThrowable::from(exception))?;
+        return Err(Error::JavaException);
+    }
+
+    let position = call(&POSITION, "position", "()I")?.i()? as usize;
+    let limit = call(&LIMIT, "limit", "()I")?.i()? as usize;
+    let address = env.get_direct_buffer_address(JByteBuffer::from(buffer))?;
+
+    Ok((unsafe { address.add(position) }, limit - position))
+}
+
+impl Signature for &[u8] {
+    const SIG_TYPE: &'static str = BYTE_BUFFER_SIG_TYPE;
+}
+
+impl Signature for &mut [u8] {
+    const SIG_TYPE: &'static str = BYTE_BUFFER_SIG_TYPE;
+}
+
+impl<'env: 'borrow, 'borrow> TryFromJavaValue<'env, 'borrow> for &'borrow [u8] {
+    type Source = JObject<'env>;
+
+    fn try_from(s: Self::Source, env: &'borrow JNIEnv<'env>) -> Result<Self> {
+        let (address, len) = remaining(s, env, false)?;
+        Ok(unsafe { slice::from_raw_parts(address, len) })
+    }
+}
+
+impl<'env: 'borrow, 'borrow> FromJavaValue<'env, 'borrow> for &'borrow [u8] {
+    type Source = JObject<'env>;
+
+    fn from(s: Self::Source, env: &'borrow JNIEnv<'env>) -> Self {
+        TryFromJavaValue::try_from(s, env).unwrap()
+    }
+}
+
+impl<'env: 'borrow, 'borrow> TryFromJavaValue<'env, 'borrow> for &'borrow mut [u8] {
+    type Source = JObject<'env>;
+
+    fn try_from(s: Self::Source, env: &'borrow JNIEnv<'env>) -> Result<Self> {
+        let (address, len) = remaining(s, env, true)?;
+        Ok(unsafe { slice::from_raw_parts_mut(address, len) })
+    }
+}
+
+impl<'env: 'borrow, 'borrow> FromJavaValue<'env, 'borrow> for &'borrow mut [u8] {
+    type Source = JObject<'env>;
+
+    fn from(s: Self::Source, env: &'borrow JNIEnv<'env>) -> Self {
+        TryFromJavaValue::try_from(s, env).unwrap()
+    }
+}
+
+/// Owned byte buffer, converted to and from a direct `ByteBuffer`.
+///
+/// See the [module documentation](self) for details about ownership.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct DirectByteBuffer(Box<[u8]>);
+
+impl DirectByteBuffer {
+    /// Consume the buffer, returning its bytes.
+    pub fn into_inner(self) -> Box<[u8]> {
+        self.0
+    }
+}
+
+impl From<Box<[u8]>> for DirectByteBuffer {
+    fn from(bytes: Box<[u8]>) -> Self {
+        DirectByteBuffer(bytes)
+    }
+}
+
+impl From<Vec<u8>> for DirectByteBuffer {
+    fn from(bytes: Vec<u8>) -> Self {
+        DirectByteBuffer(bytes.into_boxed_slice())
+    }
+}
+
+impl Deref for DirectByteBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for DirectByteBuffer {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl Signature for DirectByteBuffer {
+    const SIG_TYPE: &'static str = BYTE_BUFFER_SIG_TYPE;
+}
+
+impl<'env> TryIntoJavaValue<'env> for DirectByteBuffer {
+    type Target = JObject<'env>;
+    const KEEPS_LOCAL_REF: bool = false;
+
+    fn try_into(self, env: &JNIEnv<'env>) -> Result<Self::Target> {
+        let len = self.0.len();
+        let data = Box::into_raw(self.0) as *mut u8;
+
+        let buffer: JObject = match unsafe { env.new_direct_byte_buffer(data, len) } {
+            Ok(buffer) => buffer.into(),
+            Err(e) => {
+                drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(data, len)) });
+                return Err(e);
+            }
+        };
+
+        // If registering fails, the allocation is leaked rather than freed while the Java buffer may still point to it
+        let data = data as usize;
+        crate::cleaner::register(env, buffer, move || {
+            drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(data as *mut u8, len)) })
+        })?;
+
+        Ok(buffer)
+    }
+}
+
+impl<'env> IntoJavaValue<'env> for DirectByteBuffer {
+    type Target = JObject<'env>;
+    const KEEPS_LOCAL_REF: bool = false;
+
+    fn into(self, env: &JNIEnv<'env>) -> Self::Target {
+        TryIntoJavaValue::try_into(self, env).unwrap()
+    }
+}
+
+impl<'env: 'borrow, 'borrow> TryFromJavaValue<'env, 'borrow> for DirectByteBuffer {
+    type Source = JObject<'env>;
+    const KEEPS_LOCAL_REF: bool = false;
+
+    fn try_from(s: Self::Source, env: &'borrow JNIEnv<'env>) -> Result<Self> {
+        let bytes: &[u8] = TryFromJavaValue::try_from(s, env)?;
+        Ok(DirectByteBuffer(bytes.into()))
+    }
+}
+
+impl<'env: 'borrow, 'borrow> FromJavaValue<'env, 'borrow> for DirectByteBuffer {
+    type Source = JObject<'env>;
+    const KEEPS_LOCAL_REF: bool = false;
+
+    fn from(s: Self::Source, env: &'borrow JNIEnv<'env>) -> Self {
+        TryFromJavaValue::try_from(s, env).unwrap()
+    }
+}
@@ -0,0 +1,251 @@
+//! Configurable mapping of Rust vectors to Java collections.
+//!
+//! [`Vec<T>`] is always mapped to `java.util.ArrayList`, which is fine for exported methods but prevents
+//! imported methods that are declared with a more general type (e.g. `List<String>` or `Collection<String>`) from being resolved.
+//!
+//! [`JavaCollection<T, K>`] wraps a vector, selecting the Java type of the collection with the `K` marker type:
+//!
+//! | **Marker**        | **Java type**          | **Class instantiated on output** |
+//! |-------------------|------------------------|----------------------------------|
+//! | [`ArrayList`]     | `java.util.ArrayList`  | `java.util.ArrayList`            |
+//! | [`LinkedList`]    | `java.util.LinkedList` | `java.util.LinkedList`           |
+//! | [`List`]          | `java.util.List`       | `java.util.ArrayList`            |
+//! | [`Collection`]    | `java.util.Collection` | `java.util.ArrayList`            |
+//! | [`Iterable`]      | `java.lang.Iterable`   | `java.util.ArrayList`            |
+//!
+//! Since the marker is part of the type, the mapping can be chosen independently for every parameter and return type.
+//!
+//! On input, any `java.lang.Iterable` is accepted regardless of the marker, and its elements are read through its iterator
+//! (the same applies to [`Vec<T>`]).
+//!
+//! Example:
+//! ```
+//! use robusta_jni::bridge;
+//!
+//! #[bridge]
+//! mod jni {
+//!     use robusta_jni::convert::collection::{JavaCollection, LinkedList, List};
+//!
+//!     #[package(com.example.robusta)]
+//!     struct Names;
+//!
+//!     impl Names {
+//!         pub extern "jni" fn sorted(names: JavaCollection<String, List>) -> JavaCollection<String, LinkedList> {
+//!             let mut names = names.into_inner();
+//!             names.sort();
+//!             names.into()
+//!         }
+//!     }
+//! }
+//! ```
+//!
+
+use std::fmt::{self, Debug, Formatter};
+use std::iter::FromIterator;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+
+use jni::errors::Result;
+use jni::objects::{JObject, JValue};
+use jni::JNIEnv;
+
+use crate::convert::{
+    delete_element_ref, from_element, try_from_element, FromJavaValue, IntoJavaValue, JavaIterator,
+    JavaValue, Signature, TryFromJavaValue, TryIntoJavaValue,
+};
+
+/// Java collection type a [`JavaCollection`] is mapped to.
+pub trait CollectionKind {
+    /// [Java type signature](https://docs.oracle.com/en/java/javase/15/docs/specs/jni/types.html#type-signatures) of the collection type.
+    const SIG_TYPE: &'static str;
+
+    /// Classpath path of the class instantiated when converting to Java. It must have a no-argument constructor.
+    const CLASS: &'static str;
+}
+
+macro_rules! collection_kinds {
+    ($($(#[$meta:meta])* $kind:ident: $sig:literal [$class:literal]),+) => {
+        $(
+            $(#[$meta])*
+            #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+            pub struct $kind;
+
+            impl CollectionKind for $kind {
+                const SIG_TYPE: &'static str = $sig;
+                const CLASS: &'static str = $class;
+            }
+        )+
+    };
+}
+
+collection_kinds! {
+    /// `java.util.ArrayList`.
+    ArrayList: "Ljava/util/ArrayList;" ["java/util/ArrayList"],
+    /// `java.util.LinkedList`.
+    LinkedList: "Ljava/util/LinkedList;" ["java/util/LinkedList"],
+    /// `java.util.List`, instantiated as an `ArrayList`.
+    List: "Ljava/util/List;" ["java/util/ArrayList"],
+    /// `java.util.Collection`, instantiated as an `ArrayList`.
+    Collection: "Ljava/util/Collection;" ["java/util/ArrayList"],
+    /// `java.lang.Iterable`, instantiated as an `ArrayList`.
+    Iterable: "Ljava/lang/Iterable;" ["java/util/ArrayList"]
+}
+
+/// A vector mapped to the Java collection type selected by `K`.
+pub struct JavaCollection<T, K: CollectionKind = ArrayList> {
+    elements: Vec<T>,
+    marker: PhantomData<K>,
+}
+
+impl<T, K: CollectionKind> JavaCollection<T, K> {
+    /// Consume the collection, returning its elements.
+    pub fn into_inner(self) -> Vec<T> {
+        self.elements
+    }
+}
+
+impl<T, K: CollectionKind> From<Vec<T>> for JavaCollection<T, K> {
+    fn from(elements: Vec<T>) -> Self {
+        JavaCollection {
+            elements,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T, K: CollectionKind> FromIterator<T> for JavaCollection<T, K> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Vec::from_iter(iter).into()
+    }
+}
+
+impl<T, K: CollectionKind> IntoIterator for JavaCollection<T, K> {
+    type Item = T;
+    type IntoIter = std::vec::IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.elements.into_iter()
+    }
+}
+
+impl<T, K: CollectionKind> Deref for JavaCollection<T, K> {
+    type Target = Vec<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.elements
+    }
+}
+
+impl<T, K: CollectionKind> DerefMut for JavaCollection<T, K> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.elements
+    }
+}
+
+impl<T: Clone, K: CollectionKind> Clone for JavaCollection<T, K> {
+    fn clone(&self) -> Self {
+        self.elements.clone().into()
+    }
+}
+
+impl<T: Debug, K: CollectionKind> Debug for JavaCollection<T, K> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.elements.fmt(f)
+    }
+}
+
+impl<T: PartialEq, K: CollectionKind> PartialEq for JavaCollection<T, K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.elements == other.elements
+    }
+}
+
+impl<T, K: CollectionKind> Signature for JavaCollection<T, K> {
+    const SIG_TYPE: &'static str = K::SIG_TYPE;
+}
+
+impl<'env, T, K> TryIntoJavaValue<'env> for JavaCollection<T, K>
+where
+    T: TryIntoJavaValue<'env>,
+    K: CollectionKind,
+{
+    type Target = JObject<'env>;
+    const KEEPS_LOCAL_REF: bool = false;
+
+    fn try_into(self, env: &JNIEnv<'env>) -> Result<Self::Target> {
+        let collection = env.new_object(K::CLASS, "()V", &[])?;
+
+        for el in self.elements {
+            let el = JavaValue::autobox(TryIntoJavaValue::try_into(el, env)?, env);
+            env.call_method(
+                collection,
+                "add",
+                "(Ljava/lang/Object;)Z",
+                &[JValue::Object(el)],
+            )?;
+            delete_element_ref(env, el, T::KEEPS_LOCAL_REF)?;
+        }
+
+        Ok(collection)
+    }
+}
+
+impl<'env: 'borrow, 'borrow, T, K, U> TryFromJavaValue<'env, 'borrow> for JavaCollection<T, K>
+where
+    T: TryFromJavaValue<'env, 'borrow, Source = U>,
+    K: CollectionKind,
+    U: JavaValue<'env>,
+{
+    type Source = JObject<'env>;
+    const KEEPS_LOCAL_REF: bool = false;
+
+    fn try_from(s: Self::Source, env: &'borrow JNIEnv<'env>) -> Result<Self> {
+        JavaIterator::new(s, env)?
+            .map(|el| try_from_element(el?, env))
+            .collect()
+    }
+}
+
+impl<'env, T, K> IntoJavaValue<'env> for JavaCollection<T, K>
+where
+    T: IntoJavaValue<'env>,
+    K: CollectionKind,
+{
+    type Target = JObject<'env>;
+    const KEEPS_LOCAL_REF: bool = false;
+
+    fn into(self, env: &JNIEnv<'env>) -> Self::Target {
+        let collection = env.new_object(K::CLASS, "()V", &[]).unwrap();
+
+        for el in self.elements {
+            let el = JavaValue::autobox(IntoJavaValue::into(el, env), env);
+            env.call_method(
+                collection,
+                "add",
+                "(Ljava/lang/Object;)Z",
+                &[JValue::Object(el)],
+            )
+            .unwrap();
+            delete_element_ref(env, el, T::KEEPS_LOCAL_REF).unwrap();
+        }
+
+        collection
+    }
+}
+
+impl<'env: 'borrow, 'borrow, T, K, U> FromJavaValue<'env, 'borrow> for JavaCollection<T, K>
+where
+    T: FromJavaValue<'env, 'borrow, Source = U>,
+    K: CollectionKind,
+    U: JavaValue<'env>,
+{
+    type Source = JObject<'env>;
+    const KEEPS_LOCAL_REF: bool = false;
+
+    fn from(s: Self::Source, env: &'borrow JNIEnv<'env>) -> Self {
+        JavaIterator::new(s, env)
+            .unwrap()
+            .map(|el| from_element(el.unwrap(), env))
+            .collect()
+    }
+}
@@ -72,7 +72,7 @@ where
         )?;
 
         let f = JValueWrapper::from(res);
-        TryInto::try_into(f).and_then(|v| TryFromJavaValue::try_from(v, &self.env))
+        TryInto::try_into(f).and_then(|v| TryFromJavaValue::try_from(v, self.env))
     }
 
     // Java object is not sufficient to retrieve parent object / field owner
@@ -87,7 +87,7 @@ where
         field_name: &str,
         env: &'borrow JNIEnv<'env>,
     ) -> JniResult<Self> {
-        let class = env.find_class(classpath_path)?;
+        let class = crate::class_loader::find_class(env, classpath_path)?;
         let field_id = env.get_field_id(class, field_name, <T as Signature>::SIG_TYPE)?;
 
         Ok(Self {
@@ -125,7 +125,7 @@ where
             .unwrap();
 
         TryInto::try_into(JValueWrapper::from(res))
-            .map(|v| FromJavaValue::from(v, &self.env))
+            .map(|v| FromJavaValue::from(v, self.env))
             .unwrap()
     }
 
@@ -135,7 +135,7 @@ where
         field_name: &str,
         env: &'borrow JNIEnv<'env>,
     ) -> Self {
-        let class = env.find_class(classpath_path).unwrap();
+        let class = crate::class_loader::find_class(env, classpath_path).unwrap();
         let field_id = env
             .get_field_id(class, field_name, <T as Signature>::SIG_TYPE)
             .unwrap();
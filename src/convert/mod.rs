@@ -37,34 +37,57 @@
 //! When used with `#[call_type(safe)]`, if an `Err` is returned a Java exception is thrown (the one specified in the `call_type` attribute,
 //! or `java.lang.RuntimeException` if omitted).
 //!
+//! Functions can also return a `Result<T, E>` with an error type implementing [`IntoJavaException`](crate::exception::IntoJavaException),
+//! which chooses the exception thrown for each error: see the [`exception`](crate::exception) module.
+//!
 
 use std::convert::TryFrom;
-use std::str::FromStr;
 
 use jni::errors::Error;
 use jni::objects::{JObject, JString, JValue};
-use jni::signature::ReturnType;
 use jni::sys::{jboolean, jbyte, jchar, jdouble, jfloat, jint, jlong, jobject, jshort};
 use jni::JNIEnv;
 use paste::paste;
 
+use crate::cache::{CachedClass, CachedMethod, CachedStaticMethod};
+
+pub use buffer::*;
+pub use collection::JavaCollection;
 pub use field::*;
 pub use robusta_codegen::Signature;
 pub use safe::*;
+pub use slice::*;
 pub use unchecked::*;
+pub use write_back::*;
 
+pub mod buffer;
+pub mod collection;
 pub mod field;
 pub mod safe;
+pub mod slice;
 pub mod unchecked;
+pub mod write_back;
 
 /// A trait for types that are ffi-safe to use with JNI. It is implemented for primitives, [JObject](jni::objects::JObject) and [jobject](jni::sys::jobject).
 /// Users that want automatic conversion should instead implement [FromJavaValue], [IntoJavaValue] and/or [TryFromJavaValue], [TryIntoJavaValue]
 pub trait JavaValue<'env> {
+    /// Whether the value is the object it's boxed into (e.g. a [`JObject`]), as opposed to a primitive boxed into a new object.
+    const KEEPS_LOCAL_REF: bool = true;
+
     /// Convert instance to a [`JObject`].
     fn autobox(self, env: &JNIEnv<'env>) -> JObject<'env>;
 
     /// Convert [`JObject`] to the implementing type.
     fn unbox(s: JObject<'env>, env: &JNIEnv<'env>) -> Self;
+
+    /// Fallible version of [`JavaValue::unbox`], used by the safe conversions.
+    /// Objects that aren't instances of the expected wrapper class are reported as [`Error::WrongJValueType`].
+    fn try_unbox(s: JObject<'env>, env: &JNIEnv<'env>) -> jni::errors::Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(Self::unbox(s, env))
+    }
 }
 
 /// This trait provides [type signatures](https://docs.oracle.com/en/java/javase/15/docs/specs/jni/types.html#type-signatures) for types.
@@ -77,26 +100,69 @@ pub trait JavaValue<'env> {
 pub trait Signature {
     /// [Java type signature](https://docs.oracle.com/en/java/javase/15/docs/specs/jni/types.html#type-signatures) for the implementing type.
     const SIG_TYPE: &'static str;
+
+    /// Java type signature of the implementing type when boxed into an object (e.g. when wrapped in an [`Option`]).
+    /// For primitives this is the signature of the wrapper class (`Ljava/lang/Integer;` for `int`), otherwise it's the same as [`Signature::SIG_TYPE`].
+    const BOXED_SIG_TYPE: &'static str = Self::SIG_TYPE;
+}
+
+/// Compare two type signatures in a `const` context.
+///
+/// Used by the code generated for overloaded native methods to check that the JNI name computed by the macro matches [`Signature::SIG_TYPE`].
+pub const fn signature_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+
+    true
 }
 
 macro_rules! jvalue_types {
     ($type:ty: $boxed:ident ($sig:ident) [$unbox_method:ident]) => {
         impl Signature for $type {
             const SIG_TYPE: &'static str = stringify!($sig);
+            const BOXED_SIG_TYPE: &'static str = concat!("Ljava/lang/", stringify!($boxed), ";");
         }
 
         impl<'env> JavaValue<'env> for $type {
+            const KEEPS_LOCAL_REF: bool = false;
+
             fn autobox(self, env: &JNIEnv<'env>) -> JObject<'env> {
-                env.call_static_method_unchecked(concat!("java/lang/", stringify!($boxed)),
-                    (concat!("java/lang/", stringify!($boxed)), "valueOf", concat!(stringify!(($sig)), "Ljava/lang/", stringify!($boxed), ";")),
-                    ReturnType::from_str(concat!("Ljava/lang/", stringify!($boxed), ";")).unwrap(),
-                    &[JValue::from(self).to_jni()]).unwrap().l().unwrap()
+                static VALUE_OF: CachedStaticMethod = CachedStaticMethod::new();
+
+                // The value has the primitive type of the signature
+                unsafe {
+                    VALUE_OF.call(env, concat!("java/lang/", stringify!($boxed)), "valueOf",
+                        || concat!(stringify!(($sig)), "Ljava/lang/", stringify!($boxed), ";").into(),
+                        &[JValue::from(self)])
+                }.unwrap().l().unwrap()
             }
 
             fn unbox(s: JObject<'env>, env: &JNIEnv<'env>) -> Self {
-                paste!(Into::into(env.call_method_unchecked(s, (concat!("java/lang/", stringify!($boxed)), stringify!($unbox_method), concat!("()", stringify!($sig))), ReturnType::from_str(stringify!($sig)).unwrap(), &[])
-                    .unwrap().[<$sig:lower>]()
-                    .unwrap()))
+                Self::try_unbox(s, env).unwrap()
+            }
+
+            fn try_unbox(s: JObject<'env>, env: &JNIEnv<'env>) -> jni::errors::Result<Self> {
+                static CLASS: CachedClass = CachedClass::new();
+                static UNBOX: CachedMethod = CachedMethod::new();
+
+                if !s.is_null() && !env.is_instance_of(s, CLASS.get(env, concat!("java/lang/", stringify!($boxed)))?)? {
+                    return Err(Error::WrongJValueType(concat!("java.lang.", stringify!($boxed)), "another class"));
+                }
+
+                let unboxed = unsafe {
+                    UNBOX.call(env, s, concat!("java/lang/", stringify!($boxed)), stringify!($unbox_method), || concat!("()", stringify!($sig)).into(), &[])
+                }?;
+                paste!(Ok(Into::into(unboxed.[<$sig:lower>]()?)))
             }
         }
     };
@@ -146,6 +212,7 @@ impl<'env> JavaValue<'env> for JObject<'env> {
 }
 
 impl<'env> JavaValue<'env> for jobject {
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
     fn autobox(self, _env: &JNIEnv<'env>) -> JObject<'env> {
         unsafe { JObject::from_raw(self) }
     }
@@ -169,10 +236,145 @@ impl<'env> JavaValue<'env> for JString<'env> {
     }
 }
 
-impl<T: Signature> Signature for jni::errors::Result<T> {
+impl<T: Signature, E> Signature for Result<T, E> {
     const SIG_TYPE: &'static str = <T as Signature>::SIG_TYPE;
 }
 
+/// `None` is mapped to `null`. Primitive types are mapped to their wrapper class (e.g. `Option<i32>` to `java.lang.Integer`).
+impl<T: Signature> Signature for Option<T> {
+    const SIG_TYPE: &'static str = <T as Signature>::BOXED_SIG_TYPE;
+}
+
+/// Name of a Java enum constant, used to report constants without a matching Rust variant.
+#[doc(hidden)]
+pub fn java_enum_constant_name(constant: JObject, env: &JNIEnv) -> jni::errors::Result<String> {
+    if constant.is_null() {
+        return Err(Error::NullPtr("enum constant"));
+    }
+
+    let name: JString = From::from(
+        env.call_method(constant, "name", "()Ljava/lang/String;", &[])?
+            .l()?,
+    );
+    Ok(env.get_string(name)?.into())
+}
+
+/// Iterator over the elements of a [`java.lang.Iterable`](https://docs.oracle.com/en/java/javase/15/docs/api/java.base/java/lang/Iterable.html),
+/// obtained through its `iterator()` method.
+pub(crate) struct JavaIterator<'env: 'borrow, 'borrow> {
+    env: &'borrow JNIEnv<'env>,
+    iterator: JObject<'env>,
+}
+
+impl<'env: 'borrow, 'borrow> JavaIterator<'env, 'borrow> {
+    pub(crate) fn new(
+        iterable: JObject<'env>,
+        env: &'borrow JNIEnv<'env>,
+    ) -> jni::errors::Result<Self> {
+        let iterator = env
+            .call_method(iterable, "iterator", "()Ljava/util/Iterator;", &[])?
+            .l()?;
+
+        Ok(JavaIterator { env, iterator })
+    }
+}
+
+impl<'env: 'borrow, 'borrow> Drop for JavaIterator<'env, 'borrow> {
+    fn drop(&mut self) {
+        let _ = self.env.delete_local_ref(self.iterator);
+    }
+}
+
+impl<'env: 'borrow, 'borrow> Iterator for JavaIterator<'env, 'borrow> {
+    type Item = jni::errors::Result<JObject<'env>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let has_next = self
+            .env
+            .call_method(self.iterator, "hasNext", "()Z", &[])
+            .and_then(|v| v.z());
+
+        match has_next {
+            Ok(true) => Some(
+                self.env
+                    .call_method(self.iterator, "next", "()Ljava/lang/Object;", &[])
+                    .and_then(|v| v.l()),
+            ),
+            Ok(false) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+/// Iterate over the keys and values of a [`java.util.Map`](https://docs.oracle.com/en/java/javase/15/docs/api/java.base/java/util/Map.html).
+pub(crate) fn java_map_entries<'env: 'borrow, 'borrow>(
+    map: JObject<'env>,
+    env: &'borrow JNIEnv<'env>,
+) -> jni::errors::Result<
+    impl Iterator<Item = jni::errors::Result<(JObject<'env>, JObject<'env>)>> + 'borrow,
+> {
+    let entries = env
+        .call_method(map, "entrySet", "()Ljava/util/Set;", &[])?
+        .l()?;
+    let iterator = JavaIterator::new(entries, env);
+    env.delete_local_ref(entries)?;
+
+    Ok(iterator?.map(move |entry| {
+        let entry = entry?;
+        let key = env
+            .call_method(entry, "getKey", "()Ljava/lang/Object;", &[])?
+            .l()?;
+        let value = env
+            .call_method(entry, "getValue", "()Ljava/lang/Object;", &[])?
+            .l()?;
+        env.delete_local_ref(entry)?;
+
+        Ok((key, value))
+    }))
+}
+
+/// Delete the local reference to a collection element once converted, unless the converted value keeps it
+/// (see `KEEPS_LOCAL_REF` on the conversion traits).
+pub(crate) fn delete_element_ref(
+    env: &JNIEnv,
+    element: JObject,
+    keeps_local_ref: bool,
+) -> jni::errors::Result<()> {
+    if keeps_local_ref {
+        Ok(())
+    } else {
+        env.delete_local_ref(element)
+    }
+}
+
+/// Convert an element of a Java collection, unboxing primitives, then delete its local reference unless the value keeps it.
+pub(crate) fn try_from_element<'env: 'borrow, 'borrow, T, U>(
+    el: JObject<'env>,
+    env: &'borrow JNIEnv<'env>,
+) -> jni::errors::Result<T>
+where
+    T: TryFromJavaValue<'env, 'borrow, Source = U>,
+    U: JavaValue<'env>,
+{
+    let value = T::try_from(U::try_unbox(el, env)?, env)?;
+    delete_element_ref(env, el, T::KEEPS_LOCAL_REF)?;
+    Ok(value)
+}
+
+/// Same as [`try_from_element`], with the infallible conversions.
+pub(crate) fn from_element<'env: 'borrow, 'borrow, T, U>(
+    el: JObject<'env>,
+    env: &'borrow JNIEnv<'env>,
+) -> T
+where
+    T: FromJavaValue<'env, 'borrow, Source = U>,
+    U: JavaValue<'env>,
+{
+    let value = T::from(U::unbox(el, env), env);
+    delete_element_ref(env, el, T::KEEPS_LOCAL_REF).unwrap();
+    value
+}
+
 pub struct JValueWrapper<'a>(pub JValue<'a>);
 
 impl<'a> From<JValue<'a>> for JValueWrapper<'a> {
@@ -193,7 +395,7 @@ impl<'a> TryFrom<JValueWrapper<'a>> for jboolean {
     fn try_from(value: JValueWrapper<'a>) -> Result<Self, Self::Error> {
         match value.0 {
             JValue::Bool(b) => Ok(b),
-            _ => Err(Error::WrongJValueType("bool", value.0.type_name()).into()),
+            _ => Err(Error::WrongJValueType("bool", value.0.type_name())),
         }
     }
 }
@@ -204,7 +406,7 @@ impl<'a> TryFrom<JValueWrapper<'a>> for jbyte {
     fn try_from(value: JValueWrapper<'a>) -> Result<Self, Self::Error> {
         match value.0 {
             JValue::Byte(b) => Ok(b),
-            _ => Err(Error::WrongJValueType("byte", value.0.type_name()).into()),
+            _ => Err(Error::WrongJValueType("byte", value.0.type_name())),
         }
     }
 }
@@ -215,7 +417,7 @@ impl<'a> TryFrom<JValueWrapper<'a>> for jchar {
     fn try_from(value: JValueWrapper<'a>) -> Result<Self, Self::Error> {
         match value.0 {
             JValue::Char(c) => Ok(c),
-            _ => Err(Error::WrongJValueType("char", value.0.type_name()).into()),
+            _ => Err(Error::WrongJValueType("char", value.0.type_name())),
         }
     }
 }
@@ -226,7 +428,7 @@ impl<'a> TryFrom<JValueWrapper<'a>> for jdouble {
     fn try_from(value: JValueWrapper<'a>) -> Result<Self, Self::Error> {
         match value.0 {
             JValue::Double(d) => Ok(d),
-            _ => Err(Error::WrongJValueType("double", value.0.type_name()).into()),
+            _ => Err(Error::WrongJValueType("double", value.0.type_name())),
         }
     }
 }
@@ -237,7 +439,7 @@ impl<'a> TryFrom<JValueWrapper<'a>> for jfloat {
     fn try_from(value: JValueWrapper<'a>) -> Result<Self, Self::Error> {
         match value.0 {
             JValue::Float(f) => Ok(f),
-            _ => Err(Error::WrongJValueType("float", value.0.type_name()).into()),
+            _ => Err(Error::WrongJValueType("float", value.0.type_name())),
         }
     }
 }
@@ -248,7 +450,7 @@ impl<'a> TryFrom<JValueWrapper<'a>> for jint {
     fn try_from(value: JValueWrapper<'a>) -> Result<Self, Self::Error> {
         match value.0 {
             JValue::Int(i) => Ok(i),
-            _ => Err(Error::WrongJValueType("int", value.0.type_name()).into()),
+            _ => Err(Error::WrongJValueType("int", value.0.type_name())),
         }
     }
 }
@@ -259,7 +461,7 @@ impl<'a> TryFrom<JValueWrapper<'a>> for jshort {
     fn try_from(value: JValueWrapper<'a>) -> Result<Self, Self::Error> {
         match value.0 {
             JValue::Short(s) => Ok(s),
-            _ => Err(Error::WrongJValueType("short", value.0.type_name()).into()),
+            _ => Err(Error::WrongJValueType("short", value.0.type_name())),
         }
     }
 }
@@ -270,7 +472,7 @@ impl<'a> TryFrom<JValueWrapper<'a>> for jlong {
     fn try_from(value: JValueWrapper<'a>) -> Result<Self, Self::Error> {
         match value.0 {
             JValue::Long(l) => Ok(l),
-            _ => Err(Error::WrongJValueType("long", value.0.type_name()).into()),
+            _ => Err(Error::WrongJValueType("long", value.0.type_name())),
         }
     }
 }
@@ -281,7 +483,7 @@ impl<'a> TryFrom<JValueWrapper<'a>> for () {
     fn try_from(value: JValueWrapper<'a>) -> Result<Self, Self::Error> {
         match value.0 {
             JValue::Void => Ok(()),
-            _ => Err(Error::WrongJValueType("void", value.0.type_name()).into()),
+            _ => Err(Error::WrongJValueType("void", value.0.type_name())),
         }
     }
 }
@@ -292,7 +494,7 @@ impl<'a> TryFrom<JValueWrapper<'a>> for JObject<'a> {
     fn try_from(value: JValueWrapper<'a>) -> Result<Self, Self::Error> {
         match value.0 {
             JValue::Object(o) => Ok(o),
-            _ => Err(Error::WrongJValueType("object", value.0.type_name()).into()),
+            _ => Err(Error::WrongJValueType("object", value.0.type_name())),
         }
     }
 }
@@ -303,7 +505,7 @@ impl<'a> TryFrom<JValueWrapper<'a>> for JString<'a> {
     fn try_from(value: JValueWrapper<'a>) -> Result<Self, Self::Error> {
         match value.0 {
             JValue::Object(o) => Ok(From::from(o)),
-            _ => Err(Error::WrongJValueType("string", value.0.type_name()).into()),
+            _ => Err(Error::WrongJValueType("string", value.0.type_name())),
         }
     }
 }
@@ -15,14 +15,30 @@
 //!
 //! Both of these parameters are optional. By default, the exception class is `java.lang.RuntimeException`.
 //!
+//! If a Java exception is already pending when the method fails (e.g. it was thrown by a Java method called through an imported method),
+//! no new exception is thrown and the pending one propagates to the caller unchanged.
+//! With the `wrap_pending` parameter, the pending exception becomes the cause of the configured exception instead:
+//!
+//! ```ignore
+//! #[call_type(safe(exception_class = "java.lang.IllegalStateException", message = "Callback failed", wrap_pending))]
+//! ```
+//!
+//! The exception thrown for an error returned as a `Result<T, E>` (see [`IntoJavaException`]) is never wrapped.
+//!
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::hash::Hash;
 
 use jni::errors::{Error, Result};
 use jni::objects::{JList, JObject, JString, JValue};
-use jni::sys::{jboolean, jbooleanArray, jbyteArray, jchar, jobject};
+use jni::sys::{jboolean, jbyte, jchar, jdouble, jfloat, jint, jlong, jobject, jshort, jsize};
 use jni::JNIEnv;
 
 use crate::convert::unchecked::{FromJavaValue, IntoJavaValue};
-use crate::convert::{JavaValue, Signature};
+use crate::convert::{
+    delete_element_ref, java_map_entries, try_from_element, JavaIterator, JavaValue, Signature,
+};
+use crate::exception::IntoJavaException;
 
 pub use robusta_codegen::{TryFromJavaValue, TryIntoJavaValue};
 
@@ -43,6 +59,10 @@ pub trait TryIntoJavaValue<'env>: Signature {
     /// By default, use the one defined on the [`Signature`] trait for the implementing type.
     const SIG_TYPE: &'static str = <Self as Signature>::SIG_TYPE;
 
+    /// Whether the value still references the Java object it's converted to (e.g. a [`JObject`] is converted to itself).
+    /// If not, collection conversions delete the local reference to each element once it's added to the Java collection.
+    const KEEPS_LOCAL_REF: bool = true;
+
     /// Perform the conversion.
     fn try_into(self, env: &JNIEnv<'env>) -> Result<Self::Target>;
 }
@@ -79,6 +99,57 @@ pub trait TryIntoJavaValue<'env>: Signature {
 /// # }
 /// ```
 ///
+/// ## Value types
+/// Structs without an `#[instance]` field are converted by value: converting from Java reads every field from the Java object,
+/// while converting to Java creates a new Java object of the class named after the struct.
+/// The Java field name can be overridden with a `#[java_name = "..."]` attribute on the Rust field.
+///
+/// By default the new object is allocated without running any constructor and then every field is set.
+/// With a `#[constructor]` attribute on the struct, the constructor taking all the fields in declaration order is called instead;
+/// `#[constructor(a, b)]` selects the constructor taking only the listed fields, in the listed order.
+///
+/// ```rust
+/// # use robusta_jni::bridge;
+/// #
+/// # #[bridge]
+/// # mod jni {
+///     # use robusta_jni::convert::{Signature, TryFromJavaValue, TryIntoJavaValue};
+/// #[derive(Signature, TryFromJavaValue, TryIntoJavaValue)]
+/// #[package(com.example.robusta)]
+/// #[constructor]
+/// struct Point {
+///     x: i32,
+///     y: i32,
+///     #[java_name = "name"]
+///     label: String,
+/// }
+/// # }
+/// ```
+///
+/// ## Enums
+/// Fieldless enums can also derive [`Signature`], [`TryFromJavaValue`] and [`TryIntoJavaValue`] (and the unchecked traits).
+/// Each variant is mapped to the constant with the same name of the Java enum named after the Rust enum;
+/// the constant name can be overridden with a `#[java_name = "..."]` attribute.
+///
+/// Converting a constant that has no matching variant is an error (a panic for the unchecked traits).
+///
+/// ```rust
+/// # use robusta_jni::bridge;
+/// #
+/// # #[bridge]
+/// # mod jni {
+///     # use robusta_jni::convert::{Signature, TryFromJavaValue, TryIntoJavaValue};
+/// #[derive(Signature, TryFromJavaValue, TryIntoJavaValue)]
+/// #[package(com.example.robusta)]
+/// enum Mode {
+///     #[java_name = "READ_ONLY"]
+///     ReadOnly,
+///     #[java_name = "READ_WRITE"]
+///     ReadWrite,
+/// }
+/// # }
+/// ```
+///
 /// [`AutoLocal`]: jni::objects::AutoLocal
 ///
 pub trait TryFromJavaValue<'env: 'borrow, 'borrow>
@@ -92,6 +163,10 @@ where
     /// By default, use the one defined on the [`Signature`] trait for the implementing type.
     const SIG_TYPE: &'static str = <Self as Signature>::SIG_TYPE;
 
+    /// Whether the value keeps the Java object it's converted from (e.g. a [`JObject`], or a struct with an `#[instance]` field).
+    /// If not, collection conversions delete the local reference to each element once it's converted.
+    const KEEPS_LOCAL_REF: bool = true;
+
     /// Perform the conversion.
     fn try_from(s: Self::Source, env: &'borrow JNIEnv<'env>) -> Result<Self>;
 }
@@ -101,6 +176,7 @@ where
     T: JavaValue<'env> + Signature,
 {
     type Target = T;
+    const KEEPS_LOCAL_REF: bool = <T as JavaValue<'env>>::KEEPS_LOCAL_REF;
 
     fn try_into(self, env: &JNIEnv<'env>) -> Result<Self::Target> {
         Ok(IntoJavaValue::into(self, env))
@@ -112,6 +188,7 @@ where
     T: JavaValue<'env> + Signature,
 {
     type Source = T;
+    const KEEPS_LOCAL_REF: bool = <T as JavaValue<'env>>::KEEPS_LOCAL_REF;
 
     fn try_from(s: Self::Source, env: &'borrow JNIEnv<'env>) -> Result<Self> {
         Ok(FromJavaValue::from(s, env))
@@ -120,6 +197,7 @@ where
 
 impl<'env> TryIntoJavaValue<'env> for String {
     type Target = JString<'env>;
+    const KEEPS_LOCAL_REF: bool = false;
     const SIG_TYPE: &'static str = "Ljava/lang/String;";
 
     fn try_into(self, env: &JNIEnv<'env>) -> Result<Self::Target> {
@@ -129,6 +207,7 @@ impl<'env> TryIntoJavaValue<'env> for String {
 
 impl<'env: 'borrow, 'borrow> TryFromJavaValue<'env, 'borrow> for String {
     type Source = JString<'env>;
+    const KEEPS_LOCAL_REF: bool = false;
 
     fn try_from(s: Self::Source, env: &'borrow JNIEnv<'env>) -> Result<Self> {
         env.get_string(s).map(Into::into)
@@ -137,6 +216,7 @@ impl<'env: 'borrow, 'borrow> TryFromJavaValue<'env, 'borrow> for String {
 
 impl<'env> TryIntoJavaValue<'env> for bool {
     type Target = jboolean;
+    const KEEPS_LOCAL_REF: bool = false;
 
     fn try_into(self, _env: &JNIEnv<'env>) -> Result<Self::Target> {
         Ok(IntoJavaValue::into(self, _env))
@@ -145,6 +225,7 @@ impl<'env> TryIntoJavaValue<'env> for bool {
 
 impl<'env: 'borrow, 'borrow> TryFromJavaValue<'env, 'borrow> for bool {
     type Source = jboolean;
+    const KEEPS_LOCAL_REF: bool = false;
 
     fn try_from(s: Self::Source, _env: &JNIEnv<'env>) -> Result<Self> {
         Ok(FromJavaValue::from(s, _env))
@@ -153,6 +234,7 @@ impl<'env: 'borrow, 'borrow> TryFromJavaValue<'env, 'borrow> for bool {
 
 impl<'env> TryIntoJavaValue<'env> for char {
     type Target = jchar;
+    const KEEPS_LOCAL_REF: bool = false;
 
     fn try_into(self, _env: &JNIEnv<'env>) -> Result<Self::Target> {
         Ok(IntoJavaValue::into(self, _env))
@@ -161,6 +243,7 @@ impl<'env> TryIntoJavaValue<'env> for char {
 
 impl<'env: 'borrow, 'borrow> TryFromJavaValue<'env, 'borrow> for char {
     type Source = jchar;
+    const KEEPS_LOCAL_REF: bool = false;
 
     fn try_from(s: Self::Source, _env: &JNIEnv<'env>) -> Result<Self> {
         let res = std::char::decode_utf16(std::iter::once(s)).next();
@@ -177,36 +260,84 @@ impl Signature for Box<[bool]> {
 }
 
 impl<'env> TryIntoJavaValue<'env> for Box<[bool]> {
-    type Target = jbooleanArray;
+    type Target = JObject<'env>;
+    const KEEPS_LOCAL_REF: bool = false;
 
     fn try_into(self, env: &JNIEnv<'env>) -> Result<Self::Target> {
         let len = self.len();
         let buf: Vec<_> = self.iter().map(|&b| Into::into(b)).collect();
-        let raw = env.new_boolean_array(len as i32)?;
+        let raw = env.new_boolean_array(len as jsize)?;
         env.set_boolean_array_region(raw, 0, &buf)?;
-        Ok(raw)
+        Ok(unsafe { JObject::from_raw(raw) })
     }
 }
 
 impl<'env: 'borrow, 'borrow> TryFromJavaValue<'env, 'borrow> for Box<[bool]> {
-    type Source = jbooleanArray;
+    type Source = JObject<'env>;
+    const KEEPS_LOCAL_REF: bool = false;
 
     fn try_from(s: Self::Source, env: &'borrow JNIEnv<'env>) -> Result<Self> {
-        let len = env.get_array_length(s)?;
-        let mut buf = Vec::with_capacity(len as usize).into_boxed_slice();
-        env.get_boolean_array_region(s, 0, &mut *buf)?;
+        let len = env.get_array_length(s.into_raw())?;
+        let mut buf = vec![jboolean::default(); len as usize].into_boxed_slice();
+        env.get_boolean_array_region(s.into_raw(), 0, &mut buf)?;
 
         buf.iter()
-            .map(|&b| TryFromJavaValue::try_from(b, &env))
+            .map(|&b| TryFromJavaValue::try_from(b, env))
             .collect()
     }
 }
 
+/// Conversions between boxed slices of JNI primitive types and Java primitive arrays,
+/// copying the whole array with `Get<Type>ArrayRegion`/`Set<Type>ArrayRegion`.
+macro_rules! primitive_array_conversions {
+    ($($type:ty: $sig:literal [$new_array:ident, $get_region:ident, $set_region:ident]),+) => {
+        $(
+            impl Signature for Box<[$type]> {
+                const SIG_TYPE: &'static str = $sig;
+            }
+
+            impl<'env> TryIntoJavaValue<'env> for Box<[$type]> {
+                type Target = JObject<'env>;
+                const KEEPS_LOCAL_REF: bool = false;
+
+                fn try_into(self, env: &JNIEnv<'env>) -> Result<Self::Target> {
+                    let raw = env.$new_array(self.len() as jsize)?;
+                    env.$set_region(raw, 0, &self)?;
+                    Ok(unsafe { JObject::from_raw(raw) })
+                }
+            }
+
+            impl<'env: 'borrow, 'borrow> TryFromJavaValue<'env, 'borrow> for Box<[$type]> {
+                type Source = JObject<'env>;
+                const KEEPS_LOCAL_REF: bool = false;
+
+                fn try_from(s: Self::Source, env: &'borrow JNIEnv<'env>) -> Result<Self> {
+                    let len = env.get_array_length(s.into_raw())?;
+                    let mut buf = vec![<$type>::default(); len as usize].into_boxed_slice();
+                    env.$get_region(s.into_raw(), 0, &mut buf)?;
+                    Ok(buf)
+                }
+            }
+        )+
+    };
+}
+
+primitive_array_conversions! {
+    jbyte: "[B" [new_byte_array, get_byte_array_region, set_byte_array_region],
+    jchar: "[C" [new_char_array, get_char_array_region, set_char_array_region],
+    jshort: "[S" [new_short_array, get_short_array_region, set_short_array_region],
+    jint: "[I" [new_int_array, get_int_array_region, set_int_array_region],
+    jlong: "[J" [new_long_array, get_long_array_region, set_long_array_region],
+    jfloat: "[F" [new_float_array, get_float_array_region, set_float_array_region],
+    jdouble: "[D" [new_double_array, get_double_array_region, set_double_array_region]
+}
+
 impl<'env, T> TryIntoJavaValue<'env> for Vec<T>
 where
     T: TryIntoJavaValue<'env>,
 {
     type Target = jobject;
+    const KEEPS_LOCAL_REF: bool = false;
 
     fn try_into(self, env: &JNIEnv<'env>) -> Result<Self::Target> {
         let obj = env.new_object(
@@ -214,18 +345,13 @@ where
             "(I)V",
             &[JValue::Int(self.len() as i32)],
         )?;
-        let list = JList::from_env(&env, obj)?;
-
-        let _: Result<Vec<_>> = self
-            .into_iter()
-            .map::<Result<_>, _>(|el| {
-                Ok(JavaValue::autobox(
-                    TryIntoJavaValue::try_into(el, &env)?,
-                    &env,
-                ))
-            })
-            .map(|el| Ok(list.add(el?)))
-            .collect();
+        let list = JList::from_env(env, obj)?;
+
+        for el in self {
+            let el = JavaValue::autobox(TryIntoJavaValue::try_into(el, env)?, env);
+            list.add(el)?;
+            delete_element_ref(env, el, T::KEEPS_LOCAL_REF)?;
+        }
 
         Ok(list.into_raw())
     }
@@ -237,38 +363,206 @@ where
     U: JavaValue<'env>,
 {
     type Source = JObject<'env>;
+    const KEEPS_LOCAL_REF: bool = false;
 
     fn try_from(s: Self::Source, env: &'borrow JNIEnv<'env>) -> Result<Self> {
-        let list = JList::from_env(env, s)?;
-
-        list.iter()?
-            .map(|el| T::try_from(U::unbox(el, env), env))
+        JavaIterator::new(s, env)?
+            .map(|el| try_from_element(el?, env))
             .collect()
     }
 }
 
+/// Conversions between Rust maps and `java.util.Map`. Any `Map` implementation is accepted as input, the output class is fixed.
+/// Keys and values are autoboxed, like [`Vec`] elements.
+macro_rules! map_conversions {
+    ($($map:ident: $class:literal [$($key_bound:tt)+]),+) => {
+        $(
+            impl<'env, K, V> TryIntoJavaValue<'env> for $map<K, V>
+            where
+                K: TryIntoJavaValue<'env>,
+                V: TryIntoJavaValue<'env>,
+            {
+                type Target = JObject<'env>;
+                const KEEPS_LOCAL_REF: bool = false;
+
+                fn try_into(self, env: &JNIEnv<'env>) -> Result<Self::Target> {
+                    let map = env.new_object($class, "()V", &[])?;
+
+                    for (key, value) in self {
+                        let key = JavaValue::autobox(TryIntoJavaValue::try_into(key, env)?, env);
+                        let value = JavaValue::autobox(TryIntoJavaValue::try_into(value, env)?, env);
+                        let previous = env
+                            .call_method(
+                                map,
+                                "put",
+                                "(Ljava/lang/Object;Ljava/lang/Object;)Ljava/lang/Object;",
+                                &[JValue::Object(key), JValue::Object(value)],
+                            )?
+                            .l()?;
+                        env.delete_local_ref(previous)?;
+                        delete_element_ref(env, key, K::KEEPS_LOCAL_REF)?;
+                        delete_element_ref(env, value, V::KEEPS_LOCAL_REF)?;
+                    }
+
+                    Ok(map)
+                }
+            }
+
+            impl<'env: 'borrow, 'borrow, K, V, KU, VU> TryFromJavaValue<'env, 'borrow> for $map<K, V>
+            where
+                K: TryFromJavaValue<'env, 'borrow, Source = KU> + $($key_bound)+,
+                V: TryFromJavaValue<'env, 'borrow, Source = VU>,
+                KU: JavaValue<'env>,
+                VU: JavaValue<'env>,
+            {
+                type Source = JObject<'env>;
+                const KEEPS_LOCAL_REF: bool = false;
+
+                fn try_from(s: Self::Source, env: &'borrow JNIEnv<'env>) -> Result<Self> {
+                    java_map_entries(s, env)?
+                        .map(|entry| {
+                            let (key, value) = entry?;
+                            Ok((try_from_element(key, env)?, try_from_element(value, env)?))
+                        })
+                        .collect()
+                }
+            }
+        )+
+    };
+}
+
+map_conversions! {
+    HashMap: "java/util/HashMap" [Eq + Hash],
+    BTreeMap: "java/util/TreeMap" [Ord]
+}
+
+/// Conversions between Rust sets and `java.util.Set`. Any `Set` implementation is accepted as input, the output class is fixed.
+/// Elements are autoboxed, like [`Vec`] elements.
+macro_rules! set_conversions {
+    ($($set:ident: $class:literal [$($bound:tt)+]),+) => {
+        $(
+            impl<'env, T> TryIntoJavaValue<'env> for $set<T>
+            where
+                T: TryIntoJavaValue<'env>,
+            {
+                type Target = JObject<'env>;
+                const KEEPS_LOCAL_REF: bool = false;
+
+                fn try_into(self, env: &JNIEnv<'env>) -> Result<Self::Target> {
+                    let set = env.new_object($class, "()V", &[])?;
+
+                    for el in self {
+                        let el = JavaValue::autobox(TryIntoJavaValue::try_into(el, env)?, env);
+                        env.call_method(set, "add", "(Ljava/lang/Object;)Z", &[JValue::Object(el)])?;
+                        delete_element_ref(env, el, T::KEEPS_LOCAL_REF)?;
+                    }
+
+                    Ok(set)
+                }
+            }
+
+            impl<'env: 'borrow, 'borrow, T, U> TryFromJavaValue<'env, 'borrow> for $set<T>
+            where
+                T: TryFromJavaValue<'env, 'borrow, Source = U> + $($bound)+,
+                U: JavaValue<'env>,
+            {
+                type Source = JObject<'env>;
+                const KEEPS_LOCAL_REF: bool = false;
+
+                fn try_from(s: Self::Source, env: &'borrow JNIEnv<'env>) -> Result<Self> {
+                    JavaIterator::new(s, env)?
+                        .map(|el| try_from_element(el?, env))
+                        .collect()
+                }
+            }
+        )+
+    };
+}
+
+set_conversions! {
+    HashSet: "java/util/HashSet" [Eq + Hash],
+    BTreeSet: "java/util/TreeSet" [Ord]
+}
+
 impl Signature for Box<[u8]> {
     const SIG_TYPE: &'static str = "[B";
 }
 
 impl<'env> TryIntoJavaValue<'env> for Box<[u8]> {
-    type Target = jbyteArray;
+    type Target = JObject<'env>;
+    const KEEPS_LOCAL_REF: bool = false;
 
     fn try_into(self, env: &JNIEnv<'env>) -> Result<Self::Target> {
-        env.byte_array_from_slice(self.as_ref())
+        let raw = env.byte_array_from_slice(self.as_ref())?;
+        Ok(unsafe { JObject::from_raw(raw) })
     }
 }
 
 impl<'env: 'borrow, 'borrow> TryFromJavaValue<'env, 'borrow> for Box<[u8]> {
-    type Source = jbyteArray;
+    type Source = JObject<'env>;
+    const KEEPS_LOCAL_REF: bool = false;
 
     fn try_from(s: Self::Source, env: &'borrow JNIEnv<'env>) -> Result<Box<[u8]>> {
-        let buf = env.convert_byte_array(s)?;
+        let buf = env.convert_byte_array(s.into_raw())?;
         let boxed_slice = buf.into_boxed_slice();
         Ok(boxed_slice)
     }
 }
 
+impl<'env, T> TryIntoJavaValue<'env> for Option<T>
+where
+    T: TryIntoJavaValue<'env>,
+{
+    type Target = JObject<'env>;
+    const KEEPS_LOCAL_REF: bool = T::KEEPS_LOCAL_REF;
+
+    fn try_into(self, env: &JNIEnv<'env>) -> Result<Self::Target> {
+        match self {
+            Some(v) => Ok(JavaValue::autobox(TryIntoJavaValue::try_into(v, env)?, env)),
+            None => Ok(JObject::null()),
+        }
+    }
+}
+
+impl<'env: 'borrow, 'borrow, T, U> TryFromJavaValue<'env, 'borrow> for Option<T>
+where
+    T: TryFromJavaValue<'env, 'borrow, Source = U>,
+    U: JavaValue<'env>,
+{
+    type Source = JObject<'env>;
+    const KEEPS_LOCAL_REF: bool = T::KEEPS_LOCAL_REF;
+
+    fn try_from(s: Self::Source, env: &'borrow JNIEnv<'env>) -> Result<Self> {
+        if s.is_null() {
+            Ok(None)
+        } else {
+            T::try_from(U::try_unbox(s, env)?, env).map(Some)
+        }
+    }
+}
+
+/// When returning a `Result<T, E>` with an error type implementing [`IntoJavaException`], if the returned variant is `Ok(v)`
+/// then the value `v` is returned as usual.
+///
+/// If the returned value is `Err(e)`, the exception built from `e` is thrown.
+impl<'env, T, E> TryIntoJavaValue<'env> for std::result::Result<T, E>
+where
+    T: TryIntoJavaValue<'env>,
+    E: IntoJavaException,
+{
+    type Target = <T as TryIntoJavaValue<'env>>::Target;
+
+    fn try_into(self, env: &JNIEnv<'env>) -> Result<Self::Target> {
+        match self {
+            Ok(s) => TryIntoJavaValue::try_into(s, env),
+            Err(e) => {
+                e.throw(env)?;
+                Err(Error::JavaException)
+            }
+        }
+    }
+}
+
 /// When returning a [`jni::errors::Result`], if the returned variant is `Ok(v)` then the value `v` is returned as usual.
 ///
 /// If the returned value is `Err`, the Java exception specified in the `#[call_type(safe)]` attribute is thrown
@@ -0,0 +1,272 @@
+//! Zero-copy views of Java primitive arrays.
+//!
+//! [`JavaSlice`] and [`JavaSliceMut`] can be used as `extern "jni"` method parameters to access a Java primitive array
+//! as a Rust slice without copying it into a Rust-owned buffer first (as `Box<[T]>` conversions do).
+//!
+//! The array elements are obtained with `Get<Type>ArrayElements`, which on most JVMs pins the array instead of copying it.
+//! `GetPrimitiveArrayCritical` is not used: the generated glue (and usually the method body) still calls into JNI while
+//! the array is borrowed, which is not allowed inside a critical region.
+//!
+//! The elements are released when the view is dropped, i.e. at the latest when the native method call returns
+//! (even if it panics). The release mode depends on the view:
+//!
+//! * [`JavaSlice`] never writes anything back (`JNI_ABORT`).
+//! * [`JavaSliceMut`] copies its content back to the Java array on release. Changes can also be written back
+//!   early with [`JavaSliceMut::commit`] (`JNI_COMMIT`), or dropped altogether with [`JavaSliceMut::discard`] (`JNI_ABORT`).
+//!
+//! Views check that the array has the element type of the view, so passing e.g. an `int[]` where a `JavaSlice<f64>` is expected
+//! is a conversion error.
+//!
+//! Since a pinned array can be seen by several views, the glue generated by `#[bridge]` throws a `java.lang.IllegalArgumentException`
+//! if the same array is passed as a [`JavaSliceMut`] parameter and as another `JavaSlice` or `JavaSliceMut` parameter of the same method.
+//! Views created by hand with [`JavaSlice::new`] and [`JavaSliceMut::new`] aren't checked: not borrowing an array mutably
+//! more than once at a time is up to the caller. Note also that the JVM doesn't prevent other Java threads from accessing the array
+//! while it is borrowed.
+//!
+//! Example:
+//! ```
+//! use robusta_jni::bridge;
+//!
+//! #[bridge]
+//! mod jni {
+//!     use robusta_jni::convert::{JavaSlice, JavaSliceMut};
+//!
+//!     #[package(com.example.robusta)]
+//!     struct Vectors;
+//!
+//!     impl<'env> Vectors {
+//!         pub extern "jni" fn sum(v: JavaSlice<'env, f32>) -> f32 {
+//!             v.iter().sum()
+//!         }
+//!
+//!         pub extern "jni" fn scale(mut v: JavaSliceMut<'env, f32>, factor: f32) {
+//!             v.iter_mut().for_each(|x| *x *= factor);
+//!         }
+//!     }
+//! }
+//! ```
+//!
+
+use std::fmt::{self, Debug, Formatter};
+use std::ops::{Deref, DerefMut};
+use std::slice;
+
+use jni::errors::{Error, Result};
+use jni::objects::{AutoArray, JClass, JObject, ReleaseMode, TypeArray};
+use jni::sys::{jboolean, jbyte, jchar, jdouble, jfloat, jint, jlong, jshort};
+use jni::JNIEnv;
+
+use crate::cache::CachedClass;
+use crate::convert::{FromJavaValue, Signature, TryFromJavaValue};
+
+/// Element types of Java primitive arrays.
+pub trait ArrayElement: TypeArray + Copy {
+    /// [Java type signature](https://docs.oracle.com/en/java/javase/15/docs/specs/jni/types.html#type-signatures) of an array of this element type.
+    const ARRAY_SIG_TYPE: &'static str;
+
+    /// Class of arrays of this element type.
+    fn array_class<'env>(env: &JNIEnv<'env>) -> Result<JClass<'env>>;
+}
+
+macro_rules! array_elements {
+    ($($type:ty: $sig:literal),+) => {
+        $(
+            impl ArrayElement for $type {
+                const ARRAY_SIG_TYPE: &'static str = $sig;
+
+                fn array_class<'env>(env: &JNIEnv<'env>) -> Result<JClass<'env>> {
+                    static CLASS: CachedClass = CachedClass::new();
+                    CLASS.get(env, $sig)
+                }
+            }
+        )+
+    };
+}
+
+array_elements! {
+    jboolean: "[Z",
+    jbyte: "[B",
+    jchar: "[C",
+    jshort: "[S",
+    jint: "[I",
+    jlong: "[J",
+    jfloat: "[F",
+    jdouble: "[D"
+}
+
+fn get_elements<'env, T: ArrayElement>(
+    s: JObject<'env>,
+    env: &JNIEnv<'env>,
+    mode: ReleaseMode,
+) -> Result<(AutoArray<'env, T>, usize)> {
+    if s.is_null() {
+        return Err(Error::NullPtr("array argument"));
+    }
+
+    // The elements of an array of another type would be read or written out of bounds
+    if !env.is_instance_of(s, T::array_class(env)?)? {
+        return Err(Error::WrongJValueType(
+            T::ARRAY_SIG_TYPE,
+            "another array type",
+        ));
+    }
+
+    let len = env.get_array_length(s.into_raw())?;
+    let elements = env.get_array_elements(s.into_raw(), mode)?;
+    Ok((elements, len as usize))
+}
+
+/// Read-only view of a Java primitive array.
+pub struct JavaSlice<'env, T: ArrayElement> {
+    elements: AutoArray<'env, T>,
+    len: usize,
+}
+
+impl<'env, T: ArrayElement> JavaSlice<'env, T> {
+    /// Borrow the elements of `array`.
+    pub fn new(array: JObject<'env>, env: &JNIEnv<'env>) -> Result<Self> {
+        let (elements, len) = get_elements(array, env, ReleaseMode::NoCopyBack)?;
+        Ok(JavaSlice { elements, len })
+    }
+
+    /// Whether the JVM handed out a copy of the array instead of pinning it.
+    pub fn is_copy(&self) -> bool {
+        self.elements.is_copy()
+    }
+}
+
+impl<'env, T: ArrayElement> Deref for JavaSlice<'env, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { slice::from_raw_parts(self.elements.as_ptr(), self.len) }
+    }
+}
+
+impl<'env, T: ArrayElement + Debug> Debug for JavaSlice<'env, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Mutable view of a Java primitive array.
+///
+/// Changes are copied back to the Java array when the view is dropped, unless [`JavaSliceMut::discard`] is called.
+pub struct JavaSliceMut<'env, T: ArrayElement> {
+    elements: AutoArray<'env, T>,
+    len: usize,
+}
+
+impl<'env, T: ArrayElement> JavaSliceMut<'env, T> {
+    /// Borrow the elements of `array`.
+    ///
+    /// The array must not be borrowed by another view meanwhile, see the [module documentation](self).
+    pub fn new(array: JObject<'env>, env: &JNIEnv<'env>) -> Result<Self> {
+        let (elements, len) = get_elements(array, env, ReleaseMode::CopyBack)?;
+        Ok(JavaSliceMut { elements, len })
+    }
+
+    /// Whether the JVM handed out a copy of the array instead of pinning it.
+    pub fn is_copy(&self) -> bool {
+        self.elements.is_copy()
+    }
+
+    /// Write the changes made so far back to the Java array, keeping the view alive.
+    /// This is a no-op if the array is not a copy.
+    pub fn commit(&self) -> Result<()> {
+        self.elements.commit()
+    }
+
+    /// Don't write changes back to the Java array on release.
+    /// This has no effect if the array is not a copy, as changes are then made in place.
+    pub fn discard(&mut self) {
+        self.elements.discard()
+    }
+}
+
+impl<'env, T: ArrayElement> Deref for JavaSliceMut<'env, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { slice::from_raw_parts(self.elements.as_ptr(), self.len) }
+    }
+}
+
+impl<'env, T: ArrayElement> DerefMut for JavaSliceMut<'env, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { slice::from_raw_parts_mut(self.elements.as_ptr(), self.len) }
+    }
+}
+
+impl<'env, T: ArrayElement + Debug> Debug for JavaSliceMut<'env, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Throw a `java.lang.IllegalArgumentException` if the same array is borrowed mutably and by another view.
+///
+/// `arrays` are the array arguments of a native method, along with whether they are borrowed mutably.
+/// Called by the glue generated by `#[bridge]`.
+#[doc(hidden)]
+pub fn check_distinct_arrays(env: &JNIEnv, arrays: &[(JObject, bool)]) -> Result<()> {
+    for (i, &(array, mutable)) in arrays.iter().enumerate() {
+        for &(other, other_mutable) in &arrays[i + 1..] {
+            if (mutable || other_mutable) && !array.is_null() && env.is_same_object(array, other)? {
+                env.throw_new(
+                    "java/lang/IllegalArgumentException",
+                    "the same array can't be borrowed both mutably and by another slice",
+                )?;
+                return Err(Error::JavaException);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+impl<'env, T: ArrayElement> Signature for JavaSlice<'env, T> {
+    const SIG_TYPE: &'static str = T::ARRAY_SIG_TYPE;
+}
+
+impl<'env, T: ArrayElement> Signature for JavaSliceMut<'env, T> {
+    const SIG_TYPE: &'static str = T::ARRAY_SIG_TYPE;
+}
+
+impl<'env: 'borrow, 'borrow, T: ArrayElement> TryFromJavaValue<'env, 'borrow>
+    for JavaSlice<'env, T>
+{
+    type Source = JObject<'env>;
+
+    fn try_from(s: Self::Source, env: &'borrow JNIEnv<'env>) -> Result<Self> {
+        JavaSlice::new(s, env)
+    }
+}
+
+impl<'env: 'borrow, 'borrow, T: ArrayElement> FromJavaValue<'env, 'borrow> for JavaSlice<'env, T> {
+    type Source = JObject<'env>;
+
+    fn from(s: Self::Source, env: &'borrow JNIEnv<'env>) -> Self {
+        JavaSlice::new(s, env).unwrap()
+    }
+}
+
+impl<'env: 'borrow, 'borrow, T: ArrayElement> TryFromJavaValue<'env, 'borrow>
+    for JavaSliceMut<'env, T>
+{
+    type Source = JObject<'env>;
+
+    fn try_from(s: Self::Source, env: &'borrow JNIEnv<'env>) -> Result<Self> {
+        JavaSliceMut::new(s, env)
+    }
+}
+
+impl<'env: 'borrow, 'borrow, T: ArrayElement> FromJavaValue<'env, 'borrow>
+    for JavaSliceMut<'env, T>
+{
+    type Source = JObject<'env>;
+
+    fn from(s: Self::Source, env: &'borrow JNIEnv<'env>) -> Self {
+        JavaSliceMut::new(s, env).unwrap()
+    }
+}
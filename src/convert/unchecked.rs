@@ -11,11 +11,18 @@
 //! **These functions *will* panic should any conversion fail.**
 //!
 
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::hash::Hash;
+
 use jni::objects::{JList, JObject, JString, JValue};
-use jni::sys::{jboolean, jbooleanArray, jchar, jobject, jstring};
+use jni::sys::{
+    jboolean, jbyte, jchar, jdouble, jfloat, jint, jlong, jobject, jshort, jsize, jstring,
+};
 use jni::JNIEnv;
 
-use crate::convert::{JavaValue, Signature};
+use crate::convert::{
+    delete_element_ref, from_element, java_map_entries, JavaIterator, JavaValue, Signature,
+};
 
 pub use robusta_codegen::{FromJavaValue, IntoJavaValue};
 
@@ -40,6 +47,9 @@ pub trait IntoJavaValue<'env>: Signature {
     /// By default, use the one defined on the [`Signature`] trait for the implementing type.
     const SIG_TYPE: &'static str = <Self as Signature>::SIG_TYPE;
 
+    /// Same as [`TryIntoJavaValue::KEEPS_LOCAL_REF`](crate::convert::TryIntoJavaValue::KEEPS_LOCAL_REF).
+    const KEEPS_LOCAL_REF: bool = true;
+
     /// Perform the conversion.
     fn into(self, env: &JNIEnv<'env>) -> Self::Target;
 }
@@ -60,6 +70,9 @@ pub trait FromJavaValue<'env: 'borrow, 'borrow>: Signature {
     /// By default, use the one defined on the [`Signature`] trait for the implementing type.
     const SIG_TYPE: &'static str = <Self as Signature>::SIG_TYPE;
 
+    /// Same as [`TryFromJavaValue::KEEPS_LOCAL_REF`](crate::convert::TryFromJavaValue::KEEPS_LOCAL_REF).
+    const KEEPS_LOCAL_REF: bool = true;
+
     /// Perform the conversion.
     fn from(s: Self::Source, env: &'borrow JNIEnv<'env>) -> Self;
 }
@@ -69,6 +82,7 @@ where
     T: JavaValue<'env> + Signature,
 {
     type Target = T;
+    const KEEPS_LOCAL_REF: bool = <T as JavaValue<'env>>::KEEPS_LOCAL_REF;
 
     fn into(self, _: &JNIEnv<'env>) -> Self::Target {
         self
@@ -80,6 +94,7 @@ where
     T: JavaValue<'env> + Signature,
 {
     type Source = T;
+    const KEEPS_LOCAL_REF: bool = <T as JavaValue<'env>>::KEEPS_LOCAL_REF;
 
     fn from(t: Self::Source, _: &'borrow JNIEnv<'env>) -> Self {
         t
@@ -92,6 +107,7 @@ impl Signature for String {
 
 impl<'env> IntoJavaValue<'env> for String {
     type Target = jstring;
+    const KEEPS_LOCAL_REF: bool = false;
 
     fn into(self, env: &JNIEnv<'env>) -> Self::Target {
         env.new_string(self).unwrap().into_raw()
@@ -100,6 +116,7 @@ impl<'env> IntoJavaValue<'env> for String {
 
 impl<'env: 'borrow, 'borrow> FromJavaValue<'env, 'borrow> for String {
     type Source = JString<'env>;
+    const KEEPS_LOCAL_REF: bool = false;
 
     fn from(s: Self::Source, env: &'borrow JNIEnv<'env>) -> Self {
         env.get_string(s).unwrap().into()
@@ -108,6 +125,7 @@ impl<'env: 'borrow, 'borrow> FromJavaValue<'env, 'borrow> for String {
 
 impl<'env> IntoJavaValue<'env> for bool {
     type Target = jboolean;
+    const KEEPS_LOCAL_REF: bool = false;
 
     fn into(self, _env: &JNIEnv<'env>) -> Self::Target {
         if self {
@@ -120,10 +138,12 @@ impl<'env> IntoJavaValue<'env> for bool {
 
 impl Signature for bool {
     const SIG_TYPE: &'static str = <jboolean as Signature>::SIG_TYPE;
+    const BOXED_SIG_TYPE: &'static str = <jboolean as Signature>::BOXED_SIG_TYPE;
 }
 
 impl<'env: 'borrow, 'borrow> FromJavaValue<'env, 'borrow> for bool {
     type Source = jboolean;
+    const KEEPS_LOCAL_REF: bool = false;
 
     fn from(s: Self::Source, _env: &JNIEnv<'env>) -> Self {
         s == 1
@@ -132,10 +152,12 @@ impl<'env: 'borrow, 'borrow> FromJavaValue<'env, 'borrow> for bool {
 
 impl Signature for char {
     const SIG_TYPE: &'static str = <jchar as Signature>::SIG_TYPE;
+    const BOXED_SIG_TYPE: &'static str = <jchar as Signature>::BOXED_SIG_TYPE;
 }
 
 impl<'env> IntoJavaValue<'env> for char {
     type Target = jchar;
+    const KEEPS_LOCAL_REF: bool = false;
 
     fn into(self, _env: &JNIEnv<'env>) -> Self::Target {
         self as jchar
@@ -144,6 +166,7 @@ impl<'env> IntoJavaValue<'env> for char {
 
 impl<'env: 'borrow, 'borrow> FromJavaValue<'env, 'borrow> for char {
     type Source = jchar;
+    const KEEPS_LOCAL_REF: bool = false;
 
     fn from(s: Self::Source, _env: &JNIEnv<'env>) -> Self {
         std::char::decode_utf16(std::iter::once(s))
@@ -154,29 +177,93 @@ impl<'env: 'borrow, 'borrow> FromJavaValue<'env, 'borrow> for char {
 }
 
 impl<'env> IntoJavaValue<'env> for Box<[bool]> {
-    type Target = jbooleanArray;
+    type Target = JObject<'env>;
+    const KEEPS_LOCAL_REF: bool = false;
 
     fn into(self, env: &JNIEnv<'env>) -> Self::Target {
         let len = self.len();
         let buf: Vec<_> = self.iter().map(|&b| Into::into(b)).collect();
-        let raw = env.new_boolean_array(len as i32).unwrap();
+        let raw = env.new_boolean_array(len as jsize).unwrap();
         env.set_boolean_array_region(raw, 0, &buf).unwrap();
-        raw
+        unsafe { JObject::from_raw(raw) }
     }
 }
 
 impl<'env: 'borrow, 'borrow> FromJavaValue<'env, 'borrow> for Box<[bool]> {
-    type Source = jbooleanArray;
+    type Source = JObject<'env>;
+    const KEEPS_LOCAL_REF: bool = false;
 
     fn from(s: Self::Source, env: &'borrow JNIEnv<'env>) -> Self {
-        let len = env.get_array_length(s).unwrap();
-        let mut buf = Vec::with_capacity(len as usize).into_boxed_slice();
-        env.get_boolean_array_region(s, 0, &mut *buf).unwrap();
+        let len = env.get_array_length(s.into_raw()).unwrap();
+        let mut buf = vec![jboolean::default(); len as usize].into_boxed_slice();
+        env.get_boolean_array_region(s.into_raw(), 0, &mut buf)
+            .unwrap();
+
+        buf.iter().map(|&b| FromJavaValue::from(b, env)).collect()
+    }
+}
+
+impl<'env> IntoJavaValue<'env> for Box<[u8]> {
+    type Target = JObject<'env>;
+    const KEEPS_LOCAL_REF: bool = false;
+
+    fn into(self, env: &JNIEnv<'env>) -> Self::Target {
+        let raw = env.byte_array_from_slice(self.as_ref()).unwrap();
+        unsafe { JObject::from_raw(raw) }
+    }
+}
 
-        buf.iter().map(|&b| FromJavaValue::from(b, &env)).collect()
+impl<'env: 'borrow, 'borrow> FromJavaValue<'env, 'borrow> for Box<[u8]> {
+    type Source = JObject<'env>;
+    const KEEPS_LOCAL_REF: bool = false;
+
+    fn from(s: Self::Source, env: &'borrow JNIEnv<'env>) -> Self {
+        env.convert_byte_array(s.into_raw())
+            .unwrap()
+            .into_boxed_slice()
     }
 }
 
+/// See the fallible counterpart in [`crate::convert::safe`].
+macro_rules! primitive_array_conversions {
+    ($($type:ty [$new_array:ident, $get_region:ident, $set_region:ident]),+) => {
+        $(
+            impl<'env> IntoJavaValue<'env> for Box<[$type]> {
+                type Target = JObject<'env>;
+                const KEEPS_LOCAL_REF: bool = false;
+
+                fn into(self, env: &JNIEnv<'env>) -> Self::Target {
+                    let raw = env.$new_array(self.len() as jsize).unwrap();
+                    env.$set_region(raw, 0, &self).unwrap();
+                    unsafe { JObject::from_raw(raw) }
+                }
+            }
+
+            impl<'env: 'borrow, 'borrow> FromJavaValue<'env, 'borrow> for Box<[$type]> {
+                type Source = JObject<'env>;
+                const KEEPS_LOCAL_REF: bool = false;
+
+                fn from(s: Self::Source, env: &'borrow JNIEnv<'env>) -> Self {
+                    let len = env.get_array_length(s.into_raw()).unwrap();
+                    let mut buf = vec![<$type>::default(); len as usize].into_boxed_slice();
+                    env.$get_region(s.into_raw(), 0, &mut buf).unwrap();
+                    buf
+                }
+            }
+        )+
+    };
+}
+
+primitive_array_conversions! {
+    jbyte [new_byte_array, get_byte_array_region, set_byte_array_region],
+    jchar [new_char_array, get_char_array_region, set_char_array_region],
+    jshort [new_short_array, get_short_array_region, set_short_array_region],
+    jint [new_int_array, get_int_array_region, set_int_array_region],
+    jlong [new_long_array, get_long_array_region, set_long_array_region],
+    jfloat [new_float_array, get_float_array_region, set_float_array_region],
+    jdouble [new_double_array, get_double_array_region, set_double_array_region]
+}
+
 impl<T> Signature for Vec<T> {
     const SIG_TYPE: &'static str = "Ljava/util/ArrayList;";
 }
@@ -186,6 +273,7 @@ where
     T: IntoJavaValue<'env>,
 {
     type Target = jobject;
+    const KEEPS_LOCAL_REF: bool = false;
 
     fn into(self, env: &JNIEnv<'env>) -> Self::Target {
         let obj = env
@@ -195,13 +283,13 @@ where
                 &[JValue::Int(self.len() as i32)],
             )
             .unwrap();
-        let list = JList::from_env(&env, obj).unwrap();
+        let list = JList::from_env(env, obj).unwrap();
 
-        self.into_iter()
-            .map(|el| JavaValue::autobox(IntoJavaValue::into(el, &env), &env))
-            .for_each(|el| {
-                list.add(el).unwrap();
-            });
+        for el in self {
+            let el = JavaValue::autobox(IntoJavaValue::into(el, env), env);
+            list.add(el).unwrap();
+            delete_element_ref(env, el, T::KEEPS_LOCAL_REF).unwrap();
+        }
 
         list.into_raw()
     }
@@ -213,17 +301,170 @@ where
     U: JavaValue<'env>,
 {
     type Source = JObject<'env>;
+    const KEEPS_LOCAL_REF: bool = false;
 
     fn from(s: Self::Source, env: &'borrow JNIEnv<'env>) -> Self {
-        let list = JList::from_env(env, s).unwrap();
-
-        list.iter()
+        JavaIterator::new(s, env)
             .unwrap()
-            .map(|el| T::from(U::unbox(el, env), env))
+            .map(|el| from_element(el.unwrap(), env))
             .collect()
     }
 }
 
+/// See the fallible counterpart in [`crate::convert::safe`].
+macro_rules! map_conversions {
+    ($($map:ident: $class:literal [$($key_bound:tt)+]),+) => {
+        $(
+            impl<K, V> Signature for $map<K, V> {
+                const SIG_TYPE: &'static str = concat!("L", $class, ";");
+            }
+
+            impl<'env, K, V> IntoJavaValue<'env> for $map<K, V>
+            where
+                K: IntoJavaValue<'env>,
+                V: IntoJavaValue<'env>,
+            {
+                type Target = JObject<'env>;
+                const KEEPS_LOCAL_REF: bool = false;
+
+                fn into(self, env: &JNIEnv<'env>) -> Self::Target {
+                    let map = env.new_object($class, "()V", &[]).unwrap();
+
+                    for (key, value) in self {
+                        let key = JavaValue::autobox(IntoJavaValue::into(key, env), env);
+                        let value = JavaValue::autobox(IntoJavaValue::into(value, env), env);
+                        let previous = env
+                            .call_method(
+                                map,
+                                "put",
+                                "(Ljava/lang/Object;Ljava/lang/Object;)Ljava/lang/Object;",
+                                &[JValue::Object(key), JValue::Object(value)],
+                            )
+                            .and_then(|v| v.l())
+                            .unwrap();
+                        env.delete_local_ref(previous).unwrap();
+                        delete_element_ref(env, key, K::KEEPS_LOCAL_REF).unwrap();
+                        delete_element_ref(env, value, V::KEEPS_LOCAL_REF).unwrap();
+                    }
+
+                    map
+                }
+            }
+
+            impl<'env: 'borrow, 'borrow, K, V, KU, VU> FromJavaValue<'env, 'borrow> for $map<K, V>
+            where
+                K: FromJavaValue<'env, 'borrow, Source = KU> + $($key_bound)+,
+                V: FromJavaValue<'env, 'borrow, Source = VU>,
+                KU: JavaValue<'env>,
+                VU: JavaValue<'env>,
+            {
+                type Source = JObject<'env>;
+                const KEEPS_LOCAL_REF: bool = false;
+
+                fn from(s: Self::Source, env: &'borrow JNIEnv<'env>) -> Self {
+                    java_map_entries(s, env)
+                        .unwrap()
+                        .map(|entry| {
+                            let (key, value) = entry.unwrap();
+                            (from_element(key, env), from_element(value, env))
+                        })
+                        .collect()
+                }
+            }
+        )+
+    };
+}
+
+map_conversions! {
+    HashMap: "java/util/HashMap" [Eq + Hash],
+    BTreeMap: "java/util/TreeMap" [Ord]
+}
+
+/// See the fallible counterpart in [`crate::convert::safe`].
+macro_rules! set_conversions {
+    ($($set:ident: $class:literal [$($bound:tt)+]),+) => {
+        $(
+            impl<T> Signature for $set<T> {
+                const SIG_TYPE: &'static str = concat!("L", $class, ";");
+            }
+
+            impl<'env, T> IntoJavaValue<'env> for $set<T>
+            where
+                T: IntoJavaValue<'env>,
+            {
+                type Target = JObject<'env>;
+                const KEEPS_LOCAL_REF: bool = false;
+
+                fn into(self, env: &JNIEnv<'env>) -> Self::Target {
+                    let set = env.new_object($class, "()V", &[]).unwrap();
+
+                    for el in self {
+                        let el = JavaValue::autobox(IntoJavaValue::into(el, env), env);
+                        env.call_method(set, "add", "(Ljava/lang/Object;)Z", &[JValue::Object(el)])
+                            .unwrap();
+                        delete_element_ref(env, el, T::KEEPS_LOCAL_REF).unwrap();
+                    }
+
+                    set
+                }
+            }
+
+            impl<'env: 'borrow, 'borrow, T, U> FromJavaValue<'env, 'borrow> for $set<T>
+            where
+                T: FromJavaValue<'env, 'borrow, Source = U> + $($bound)+,
+                U: JavaValue<'env>,
+            {
+                type Source = JObject<'env>;
+                const KEEPS_LOCAL_REF: bool = false;
+
+                fn from(s: Self::Source, env: &'borrow JNIEnv<'env>) -> Self {
+                    JavaIterator::new(s, env)
+                        .unwrap()
+                        .map(|el| from_element(el.unwrap(), env))
+                        .collect()
+                }
+            }
+        )+
+    };
+}
+
+set_conversions! {
+    HashSet: "java/util/HashSet" [Eq + Hash],
+    BTreeSet: "java/util/TreeSet" [Ord]
+}
+
+impl<'env, T> IntoJavaValue<'env> for Option<T>
+where
+    T: IntoJavaValue<'env>,
+{
+    type Target = JObject<'env>;
+    const KEEPS_LOCAL_REF: bool = T::KEEPS_LOCAL_REF;
+
+    fn into(self, env: &JNIEnv<'env>) -> Self::Target {
+        match self {
+            Some(v) => JavaValue::autobox(IntoJavaValue::into(v, env), env),
+            None => JObject::null(),
+        }
+    }
+}
+
+impl<'env: 'borrow, 'borrow, T, U> FromJavaValue<'env, 'borrow> for Option<T>
+where
+    T: FromJavaValue<'env, 'borrow, Source = U>,
+    U: JavaValue<'env>,
+{
+    type Source = JObject<'env>;
+    const KEEPS_LOCAL_REF: bool = T::KEEPS_LOCAL_REF;
+
+    fn from(s: Self::Source, env: &'borrow JNIEnv<'env>) -> Self {
+        if s.is_null() {
+            None
+        } else {
+            Some(T::from(U::unbox(s, env), env))
+        }
+    }
+}
+
 impl<'env, T> IntoJavaValue<'env> for jni::errors::Result<T>
 where
     T: IntoJavaValue<'env>,
@@ -0,0 +1,92 @@
+//! Writing Rust-side changes back to the Java instance.
+//!
+//! Structs deriving [`TryFromJavaValue`](crate::convert::TryFromJavaValue) with an `#[instance]` field copy their data fields
+//! from the Java object when converted: changing them in Rust doesn't change the Java object.
+//!
+//! Deriving [`WriteBack`] (in addition to the conversion traits) allows the generated glue of `&mut self` native methods
+//! to set the data fields back on the Java instance after the method returns. Write-back is opt-in, either for a single method or
+//! for every `&mut self` method of the struct, with a `#[write_back]` attribute on the method or on the struct respectively.
+//!
+//! Only the fields changed by the method are set: the data fields are snapshotted before the call and compared afterwards,
+//! so their types must implement `Clone` and `PartialEq`. Nothing is written back when the method returns an `Err`
+//! or leaves a Java exception pending, so a failed call doesn't leave the Java instance half-updated.
+//!
+//! Fields annotated with `#[field]` are not copied and already write through, so they are skipped.
+//! Methods taking `self` by value (even `mut self`) consume the receiver, so there is nothing left to write back after they return:
+//! take `&mut self` instead.
+//!
+//! If setting a field fails, the error is handled like any other conversion error
+//! (a Java exception with `#[call_type(safe)]`, a panic with `#[call_type(unchecked)]`).
+//!
+//! Example:
+//! ```
+//! use robusta_jni::bridge;
+//!
+//! #[bridge]
+//! mod jni {
+//!     use robusta_jni::convert::{Signature, TryFromJavaValue, TryIntoJavaValue, WriteBack};
+//!     use robusta_jni::jni::objects::AutoLocal;
+//!
+//!     #[derive(Signature, TryIntoJavaValue, TryFromJavaValue, WriteBack)]
+//!     #[package(com.example.robusta)]
+//!     struct Counter<'env: 'borrow, 'borrow> {
+//!         #[instance]
+//!         raw: AutoLocal<'env, 'borrow>,
+//!         count: i32,
+//!     }
+//!
+//!     impl<'env: 'borrow, 'borrow> Counter<'env, 'borrow> {
+//!         #[write_back]
+//!         pub extern "jni" fn increment(&mut self) {
+//!             self.count += 1;
+//!         }
+//!     }
+//! }
+//! ```
+//!
+
+use jni::errors::Result;
+use jni::JNIEnv;
+
+pub use robusta_codegen::WriteBack;
+
+/// Write the fields of a Rust value back to the Java instance it was converted from.
+///
+/// See the [module documentation](self) for details.
+pub trait WriteBack<'env> {
+    /// Copy of the data fields, taken before the native method is called.
+    type Snapshot;
+
+    /// Copy the data fields.
+    fn snapshot(&self) -> Self::Snapshot;
+
+    /// Set the data fields that differ from `snapshot` on the Java instance, consuming the value.
+    fn write_back(self, snapshot: Self::Snapshot, env: &JNIEnv<'env>) -> Result<()>;
+}
+
+/// Return value of a native method, checked by the generated glue before writing back.
+///
+/// `Result` return values implement [`Fallible`], which is picked first by method resolution on `&&Outcome(&result)`,
+/// any other value falls back to [`Infallible`].
+#[doc(hidden)]
+pub struct Outcome<'a, T>(pub &'a T);
+
+#[doc(hidden)]
+pub trait Fallible {
+    fn is_ok(&self) -> bool;
+}
+
+impl<T, E> Fallible for &Outcome<'_, std::result::Result<T, E>> {
+    fn is_ok(&self) -> bool {
+        self.0.is_ok()
+    }
+}
+
+#[doc(hidden)]
+pub trait Infallible {
+    fn is_ok(&self) -> bool {
+        true
+    }
+}
+
+impl<T> Infallible for Outcome<'_, T> {}
@@ -0,0 +1,553 @@
+//! Capturing Java exceptions thrown by imported methods.
+//!
+//! When a Java method called through the glue of an `extern "java"` method throws, the call fails with
+//! [`jni::errors::Error::JavaException`], which says nothing about the exception, and the exception is left pending:
+//! until it's cleared, calling most JNI functions is not allowed.
+//!
+//! Imported methods can return a [`CallResult<T>`] instead of a [`jni::errors::Result<T>`]: if the call throws,
+//! the exception is cleared and captured in a [`JavaException`], with its class name, message, stack trace and cause chain,
+//! along with a global reference to the `Throwable` itself.
+//!
+//! Example:
+//! ```
+//! use robusta_jni::bridge;
+//!
+//! #[bridge]
+//! mod jni {
+//!     use robusta_jni::convert::Signature;
+//!     use robusta_jni::exception::{CallError, CallResult};
+//!     use robusta_jni::jni::JNIEnv;
+//!
+//!     #[derive(Signature)]
+//!     #[package(com.example.robusta)]
+//!     struct Files;
+//!
+//!     impl Files {
+//!         pub extern "java" fn readFile(env: &JNIEnv, path: String) -> CallResult<String> {}
+//!
+//!         pub extern "jni" fn readOrDefault(env: &JNIEnv, path: String) -> String {
+//!             match Files::readFile(env, path) {
+//!                 Ok(content) => content,
+//!                 Err(CallError::Exception(e))
+//!                     if e.is_instance_of(env, "java/io/FileNotFoundException") =>
+//!                 {
+//!                     String::new()
+//!                 }
+//!                 Err(e) => panic!("{}", e),
+//!             }
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! The error type of imported methods can be any type implementing [`FromJniError`].
+//!
+//! # Throwing exceptions from native methods
+//! Exported methods can return a `Result<T, E>` for any error type `E` implementing [`IntoJavaException`]:
+//! if an `Err` is returned, the exception built from the error is thrown.
+//! The trait can be derived, choosing the exception class and message of each variant with an `#[exception]` attribute
+//! (by default `java.lang.RuntimeException`, with the `Display` representation of the error as message).
+//! A field marked `#[exception(cause)]`, itself implementing [`IntoJavaException`], becomes the cause of the exception:
+//!
+//! ```
+//! use std::fmt::{self, Display, Formatter};
+//!
+//! use robusta_jni::exception::{CallError, IntoJavaException};
+//!
+//! #[derive(Debug, IntoJavaException)]
+//! #[exception(class = "java.lang.IllegalStateException")]
+//! enum StorageError {
+//!     #[exception(class = "java.io.FileNotFoundException")]
+//!     NotFound(String),
+//!     #[exception(message = "storage is read-only")]
+//!     ReadOnly,
+//!     Java(#[exception(cause)] CallError),
+//! }
+//!
+//! impl Display for StorageError {
+//!     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+//!         match self {
+//!             StorageError::NotFound(path) => write!(f, "{} not found", path),
+//!             StorageError::ReadOnly => write!(f, "read-only"),
+//!             StorageError::Java(e) => write!(f, "Java call failed: {}", e),
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! [`JavaException`] and [`CallError`] implement [`IntoJavaException`] as well: a captured Java exception is thrown again as is.
+//!
+//! Errors that only have a textual representation are thrown as a `java.lang.RuntimeException` with the message of the error,
+//! followed by the messages of its sources: this is the case for `String`, `&str`, [`std::io::Error`] and `Box<dyn Error>`,
+//! which any error type can be converted into with `?`. With the `anyhow` feature, `anyhow::Error` is supported as well.
+//! Exported methods can also return a `Result<T, E>` with any other error type implementing [`std::error::Error`],
+//! which is thrown the same way.
+//!
+//! ```
+//! use robusta_jni::bridge;
+//!
+//! #[bridge]
+//! mod jni {
+//!     use std::error::Error;
+//!
+//!     use robusta_jni::convert::Signature;
+//!
+//!     #[derive(Signature)]
+//!     #[package(com.example.robusta)]
+//!     struct Config;
+//!
+//!     impl Config {
+//!         pub extern "jni" fn port(value: String) -> Result<i32, Box<dyn Error + Send + Sync>> {
+//!             Ok(value.trim().parse()?)
+//!         }
+//!     }
+//! }
+//! ```
+//!
+
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+use jni::errors::Error as JniError;
+use jni::objects::{GlobalRef, JObject, JString, JThrowable, JValue};
+use jni::JNIEnv;
+
+pub use robusta_codegen::IntoJavaException;
+
+/// Causes deeper than this are not captured, to guard against cyclic cause chains.
+const MAX_CAUSE_DEPTH: usize = 32;
+
+/// A Java exception, captured while it was pending.
+#[derive(Debug, Clone)]
+pub struct JavaException {
+    class_name: String,
+    message: Option<String>,
+    stack_trace: Vec<String>,
+    cause: Option<Box<JavaException>>,
+    throwable: GlobalRef,
+}
+
+impl JavaException {
+    /// Clear the pending Java exception, if any, and capture it.
+    ///
+    /// If it can't be captured (e.g. if the JVM runs out of memory meanwhile), it's thrown again and `None` is returned.
+    pub fn catch(env: &JNIEnv) -> Option<Self> {
+        let throwable = env.exception_occurred().ok()?;
+        if throwable.is_null() {
+            return None;
+        }
+        let throwable = env.auto_local(throwable);
+
+        env.exception_clear().ok()?;
+
+        let exception = Self::from_throwable(env, JThrowable::from(throwable.as_obj()));
+
+        // Inspecting the exception may throw as well, but we are only interested in the original exception
+        let _ = env.exception_clear();
+        match exception {
+            Ok(exception) => Some(exception),
+            Err(_) => {
+                let _ = env.throw(JThrowable::from(throwable.as_obj()));
+                None
+            }
+        }
+    }
+
+    /// Capture `throwable`, which doesn't need to be thrown.
+    pub fn from_throwable(env: &JNIEnv, throwable: JThrowable) -> jni::errors::Result<Self> {
+        Self::capture(env, throwable.into(), MAX_CAUSE_DEPTH)
+    }
+
+    fn capture(env: &JNIEnv, throwable: JObject, depth: usize) -> jni::errors::Result<Self> {
+        let get_string = |obj: JObject, method: &str| -> jni::errors::Result<Option<String>> {
+            let s = env
+                .call_method(obj, method, "()Ljava/lang/String;", &[])?
+                .l()?;
+
+            if s.is_null() {
+                Ok(None)
+            } else {
+                let s = env.auto_local(s);
+                Ok(Some(env.get_string(JString::from(s.as_obj()))?.into()))
+            }
+        };
+
+        let class = env.auto_local(env.get_object_class(throwable)?);
+        let class_name = get_string(class.as_obj(), "getName")?.unwrap_or_default();
+        let message = get_string(throwable, "getMessage")?;
+
+        let stack_trace = {
+            let elements = env
+                .call_method(
+                    throwable,
+                    "getStackTrace",
+                    "()[Ljava/lang/StackTraceElement;",
+                    &[],
+                )?
+                .l()?;
+            let elements = env.auto_local(elements);
+            let length = env.get_array_length(elements.as_obj().into_raw())?;
+
+            (0..length)
+                .map(|i| {
+                    let element = env.get_object_array_element(elements.as_obj().into_raw(), i)?;
+                    let element = env.auto_local(element);
+                    get_string(element.as_obj(), "toString").map(Option::unwrap_or_default)
+                })
+                .collect::<jni::errors::Result<_>>()?
+        };
+
+        let cause = env
+            .call_method(throwable, "getCause", "()Ljava/lang/Throwable;", &[])?
+            .l()?;
+        let cause = if cause.is_null() || depth == 0 || env.is_same_object(cause, throwable)? {
+            None
+        } else {
+            let cause = env.auto_local(cause);
+            Some(Box::new(Self::capture(env, cause.as_obj(), depth - 1)?))
+        };
+
+        Ok(JavaException {
+            class_name,
+            message,
+            stack_trace,
+            cause,
+            throwable: env.new_global_ref(throwable)?,
+        })
+    }
+
+    /// Binary name of the class of the exception (e.g. `java.io.FileNotFoundException`).
+    pub fn class_name(&self) -> &str {
+        &self.class_name
+    }
+
+    /// Message of the exception, as returned by `getMessage`.
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+
+    /// Stack trace of the exception, one frame per element (e.g. `com.example.Foo.bar(Foo.java:42)`).
+    pub fn stack_trace(&self) -> &[String] {
+        &self.stack_trace
+    }
+
+    /// Cause of the exception, as returned by `getCause`.
+    pub fn cause(&self) -> Option<&JavaException> {
+        self.cause.as_deref()
+    }
+
+    /// Global reference to the `Throwable`.
+    pub fn throwable(&self) -> &GlobalRef {
+        &self.throwable
+    }
+
+    /// Whether the exception is an instance of the class `classpath_path` (e.g. `java/io/IOException`).
+    ///
+    /// Returns `false` if the class can't be found.
+    pub fn is_instance_of(&self, env: &JNIEnv, classpath_path: &str) -> bool {
+        let result = crate::class_loader::find_class(env, classpath_path)
+            .and_then(|class| env.is_instance_of(self.throwable.as_obj(), class));
+        if result.is_err() {
+            let _ = env.exception_clear();
+        }
+
+        result.unwrap_or(false)
+    }
+}
+
+impl Display for JavaException {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.message {
+            Some(message) => write!(f, "{}: {}", self.class_name, message),
+            None => write!(f, "{}", self.class_name),
+        }
+    }
+}
+
+impl Error for JavaException {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.cause.as_deref().map(|c| c as &(dyn Error + 'static))
+    }
+}
+
+/// Error returned by imported methods returning a [`CallResult`].
+#[derive(Debug)]
+pub enum CallError {
+    /// The Java method threw an exception.
+    Exception(JavaException),
+    /// Any other JNI error, e.g. the method could not be found or a conversion failed.
+    Jni(JniError),
+}
+
+impl Display for CallError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CallError::Exception(e) => write!(f, "Java exception thrown: {}", e),
+            CallError::Jni(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for CallError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CallError::Exception(e) => Some(e),
+            CallError::Jni(e) => Some(e),
+        }
+    }
+}
+
+impl From<JniError> for CallError {
+    fn from(e: JniError) -> Self {
+        CallError::Jni(e)
+    }
+}
+
+impl From<JavaException> for CallError {
+    fn from(e: JavaException) -> Self {
+        CallError::Exception(e)
+    }
+}
+
+/// Result of imported methods capturing Java exceptions.
+pub type CallResult<T> = Result<T, CallError>;
+
+/// Conversion of the errors raised by the glue of imported methods into their declared error type.
+pub trait FromJniError {
+    fn from_jni_error(env: &JNIEnv, error: JniError) -> Self;
+}
+
+/// The exception, if any, is left pending.
+impl FromJniError for JniError {
+    fn from_jni_error(_env: &JNIEnv, error: JniError) -> Self {
+        error
+    }
+}
+
+/// The exception, if any, is cleared and captured.
+impl FromJniError for CallError {
+    fn from_jni_error(env: &JNIEnv, error: JniError) -> Self {
+        match error {
+            JniError::JavaException => JavaException::catch(env)
+                .map(CallError::Exception)
+                .unwrap_or(CallError::Jni(error)),
+            error => CallError::Jni(error),
+        }
+    }
+}
+
+/// Conversion of a Rust error into a Java exception, thrown when returned by an exported method.
+pub trait IntoJavaException {
+    /// Class of the exception, in classpath format (e.g. `java/io/FileNotFoundException`).
+    fn exception_class(&self) -> Cow<'_, str>;
+
+    /// Message of the exception.
+    fn exception_message(&self) -> String;
+
+    /// Cause of the exception, if any.
+    fn exception_cause<'env>(
+        &self,
+        env: &JNIEnv<'env>,
+    ) -> jni::errors::Result<Option<JThrowable<'env>>> {
+        let _ = env;
+        Ok(None)
+    }
+
+    /// Create the exception, calling the `(String)` or `(String, Throwable)` constructor of the exception class.
+    fn to_throwable<'env>(&self, env: &JNIEnv<'env>) -> jni::errors::Result<JThrowable<'env>> {
+        let class = crate::class_loader::find_class(env, &self.exception_class())?;
+        let message = JObject::from(env.new_string(self.exception_message())?);
+
+        let throwable = match self.exception_cause(env)? {
+            Some(cause) => env.new_object(
+                class,
+                "(Ljava/lang/String;Ljava/lang/Throwable;)V",
+                &[JValue::Object(message), JValue::Object(cause.into())],
+            )?,
+            None => env.new_object(class, "(Ljava/lang/String;)V", &[JValue::Object(message)])?,
+        };
+
+        Ok(JThrowable::from(throwable))
+    }
+
+    /// Throw the exception.
+    fn throw(&self, env: &JNIEnv) -> jni::errors::Result<()> {
+        env.throw(self.to_throwable(env)?)
+    }
+}
+
+/// The captured `Throwable` is thrown again.
+impl IntoJavaException for JavaException {
+    fn exception_class(&self) -> Cow<'_, str> {
+        Cow::Owned(self.class_name.replace('.', "/"))
+    }
+
+    fn exception_message(&self) -> String {
+        self.message.clone().unwrap_or_default()
+    }
+
+    fn to_throwable<'env>(&self, env: &JNIEnv<'env>) -> jni::errors::Result<JThrowable<'env>> {
+        env.new_local_ref(self.throwable.as_obj())
+            .map(JThrowable::from)
+    }
+}
+
+/// Captured exceptions are thrown again, other errors are thrown as a `java.lang.RuntimeException`.
+impl IntoJavaException for CallError {
+    fn exception_class(&self) -> Cow<'_, str> {
+        match self {
+            CallError::Exception(e) => e.exception_class(),
+            CallError::Jni(_) => Cow::Borrowed("java/lang/RuntimeException"),
+        }
+    }
+
+    fn exception_message(&self) -> String {
+        match self {
+            CallError::Exception(e) => e.exception_message(),
+            CallError::Jni(e) => e.to_string(),
+        }
+    }
+
+    fn to_throwable<'env>(&self, env: &JNIEnv<'env>) -> jni::errors::Result<JThrowable<'env>> {
+        match self {
+            CallError::Exception(e) => e.to_throwable(env),
+            CallError::Jni(_) => env
+                .new_object(
+                    "java/lang/RuntimeException",
+                    "(Ljava/lang/String;)V",
+                    &[JValue::Object(
+                        env.new_string(self.exception_message())?.into(),
+                    )],
+                )
+                .map(JThrowable::from),
+        }
+    }
+}
+
+/// Replace the pending Java exception, if any, with a new exception of class `exception_class` (in classpath format),
+/// having the pending exception as cause.
+///
+/// Returns whether an exception was pending.
+pub fn wrap_pending(
+    env: &JNIEnv,
+    exception_class: &str,
+    message: &str,
+) -> jni::errors::Result<bool> {
+    let cause = env.exception_occurred()?;
+    if cause.is_null() {
+        return Ok(false);
+    }
+
+    env.exception_clear()?;
+
+    // Resolved like the classes of the bridge module, which may not be visible to `FindClass` from this thread
+    let class = crate::class_loader::find_class(env, exception_class)?;
+    let message = env.new_string(message)?;
+    let exception = env.new_object(
+        class,
+        "(Ljava/lang/String;Ljava/lang/Throwable;)V",
+        &[JValue::Object(message.into()), JValue::Object(cause.into())],
+    )?;
+
+    env.throw(JThrowable::from(exception))?;
+    Ok(true)
+}
+
+/// Description of `e` followed by its sources, e.g. `can't load config: file not found`.
+fn error_chain(e: &(dyn Error + 'static)) -> String {
+    let mut chain = e.to_string();
+
+    let mut source = e.source();
+    while let Some(e) = source {
+        chain.push_str(": ");
+        chain.push_str(&e.to_string());
+        source = e.source();
+    }
+
+    chain
+}
+
+macro_rules! runtime_exception {
+    ($ty:ty, |$e:ident| $message:expr) => {
+        /// Thrown as a `java.lang.RuntimeException`.
+        impl IntoJavaException for $ty {
+            fn exception_class(&self) -> Cow<'_, str> {
+                Cow::Borrowed("java/lang/RuntimeException")
+            }
+
+            fn exception_message(&self) -> String {
+                let $e = self;
+                $message
+            }
+        }
+    };
+}
+
+runtime_exception!(String, |e| e.clone());
+runtime_exception!(&str, |e| e.to_string());
+runtime_exception!(std::io::Error, |e| error_chain(e));
+runtime_exception!(Box<dyn Error>, |e| error_chain(e.as_ref()));
+runtime_exception!(Box<dyn Error + Send + Sync>, |e| error_chain(e.as_ref()));
+#[cfg(feature = "anyhow")]
+runtime_exception!(anyhow::Error, |e| format!("{:#}", e));
+
+/// Conversion of the return value of exported methods, used by the glue generated by `#[bridge]`.
+///
+/// Return values implementing [`TryIntoJavaValue`](crate::convert::TryIntoJavaValue) are converted as usual, including
+/// `Result<T, E>` with `E` implementing [`IntoJavaException`].
+/// A `Result<T, E>` with any other error type convertible into `Box<dyn Error>`, e.g. any `E: std::error::Error`,
+/// throws its error as a `java.lang.RuntimeException`.
+///
+/// The conversion is chosen by method resolution on `(&&ReturnValue(&value)).return_kind()`,
+/// which tries the impls for `&ReturnValue` before the ones for `ReturnValue`.
+#[doc(hidden)]
+pub mod dispatch {
+    use std::error::Error;
+
+    use jni::JNIEnv;
+
+    use crate::convert::TryIntoJavaValue;
+
+    pub struct ReturnValue<'a, T>(pub &'a T);
+
+    pub struct ValueTag;
+
+    pub struct ErrorTag;
+
+    pub trait ValueKind<'env> {
+        fn return_kind(&self) -> ValueTag {
+            ValueTag
+        }
+    }
+
+    impl<'env, T: TryIntoJavaValue<'env>> ValueKind<'env> for &ReturnValue<'_, T> {}
+
+    pub trait ErrorKind {
+        fn return_kind(&self) -> ErrorTag {
+            ErrorTag
+        }
+    }
+
+    impl<T, E: Into<Box<dyn Error>>> ErrorKind for ReturnValue<'_, Result<T, E>> {}
+
+    impl ValueTag {
+        pub fn try_into<'env, T: TryIntoJavaValue<'env>>(
+            self,
+            value: T,
+            env: &JNIEnv<'env>,
+        ) -> jni::errors::Result<T::Target> {
+            TryIntoJavaValue::try_into(value, env)
+        }
+    }
+
+    impl ErrorTag {
+        pub fn try_into<'env, T: TryIntoJavaValue<'env>, E: Into<Box<dyn Error>>>(
+            self,
+            value: Result<T, E>,
+            env: &JNIEnv<'env>,
+        ) -> jni::errors::Result<T::Target> {
+            TryIntoJavaValue::try_into(value.map_err(Into::<Box<dyn Error>>::into), env)
+        }
+    }
+}
@@ -0,0 +1,313 @@
+//! Rust values owned by Java objects.
+//!
+//! A common JNI pattern keeps a boxed Rust value alive behind a Java object, its peer, which stores the pointer in a `long` field.
+//! `#[derive(NativeHandle)]` implements it for a `#[package]` struct of a bridge module:
+//!
+//!  * returning the struct from an exported method creates a new peer with its no-argument constructor, owning the value;
+//!  * `&self` and `&mut self` methods, and `&T`/`&mut T` parameters, borrow the value owned by the peer;
+//!  * `self` methods and `T` parameters take the value out of the peer, which is then closed;
+//!  * with `#[native_handle(dispose = "close")]`, the bridge generates a `close` native method dropping the value.
+//!
+//! ```
+//! use robusta_jni::bridge;
+//!
+//! #[bridge]
+//! mod jni {
+//!     use robusta_jni::convert::Signature;
+//!     use robusta_jni::handle::NativeHandle;
+//!
+//!     #[derive(Signature, NativeHandle)]
+//!     #[package(com.example.robusta)]
+//!     #[native_handle(dispose = "close")]
+//!     pub struct Tokenizer {
+//!         separator: char,
+//!         tokens: Vec<String>,
+//!     }
+//!
+//!     impl Tokenizer {
+//!         pub extern "jni" fn create(separator: char) -> Tokenizer {
+//!             Tokenizer { separator, tokens: Vec::new() }
+//!         }
+//!
+//!         pub extern "jni" fn feed(&mut self, input: String) -> i32 {
+//!             self.tokens.extend(input.split(self.separator).map(ToString::to_string));
+//!             self.tokens.len() as i32
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! ```java
+//! package com.example.robusta;
+//!
+//! public class Tokenizer implements AutoCloseable {
+//!     private long nativeHandle;
+//!
+//!     private Tokenizer() {}
+//!
+//!     public static native Tokenizer create(char separator);
+//!
+//!     public native int feed(String input);
+//!
+//!     public native void close();
+//! }
+//! ```
+//!
+//! The field is named `nativeHandle` unless set with `#[native_handle(field = "...")]`. It's `0` once the peer is closed:
+//! closing it again does nothing, and borrowing its value throws a `java.lang.IllegalStateException`, as does borrowing
+//! it again from a Java method called while it's borrowed. Calls borrowing the same value from several threads wait for each other.
+//!
+//! Values of peers which are never closed are leaked, unless `#[native_handle(cleaner)]` registers the peers with a
+//! `java.lang.ref.Cleaner` (Java 9 and later, not available on Android), which drops the value once the peer is unreachable.
+//!
+
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use jni::errors::{Error, Result};
+use jni::objects::{JObject, JValue};
+use jni::sys::jlong;
+use jni::JNIEnv;
+
+use crate::cache::CachedField;
+use crate::convert::Signature;
+
+pub use robusta_codegen::NativeHandle;
+
+/// Rust type owned by a Java peer object, see the [module documentation](self).
+///
+/// While you can implement this trait manually, you should probably use the derive macro, which also implements the conversion traits.
+pub trait NativeHandle: Signature + Send + Sized + 'static {
+    /// Name of the `long` field of the peer holding the pointer to the value.
+    const FIELD: &'static str;
+
+    /// Whether the value is dropped by a `java.lang.ref.Cleaner` once the peer is unreachable, if it isn't closed before.
+    const CLEANER: bool;
+
+    /// Cache of the lookup of the field, which must be a distinct `static` for every implementing type.
+    fn field_cache() -> &'static CachedField;
+}
+
+/// Allocation pointed to by the field of the peer.
+struct Slot<T> {
+    value: Mutex<Option<T>>,
+    // identifier of the thread borrowing the value, 0 if none
+    borrower: AtomicUsize,
+}
+
+thread_local! {
+    static THREAD: u8 = const { 0 };
+}
+
+/// Identifier of the current thread, which is never 0.
+fn current_thread() -> usize {
+    THREAD.with(|t| t as *const u8 as usize)
+}
+
+/// Class of the peers of `T`, in classpath format.
+fn peer_class<T: NativeHandle>() -> &'static str {
+    let signature = <T as Signature>::SIG_TYPE;
+    signature
+        .strip_prefix('L')
+        .and_then(|s| s.strip_suffix(';'))
+        .unwrap_or(signature)
+}
+
+/// Throw a `java.lang.IllegalStateException` about a peer of `T`.
+fn illegal_state<T: NativeHandle>(env: &JNIEnv, reason: &str) -> Error {
+    let message = format!("`{}` {}", peer_class::<T>().replace('/', "."), reason);
+
+    match env.throw_new("java/lang/IllegalStateException", message) {
+        Ok(()) => Error::JavaException,
+        Err(e) => e,
+    }
+}
+
+fn slot_pointer<'env, T: NativeHandle>(
+    env: &JNIEnv<'env>,
+    peer: JObject<'env>,
+) -> Result<*const Slot<T>> {
+    let pointer = T::field_cache().get(env, peer, peer_class::<T>(), T::FIELD, "J")?;
+    Ok(pointer.j()? as usize as *const Slot<T>)
+}
+
+fn set_slot_pointer<'env, T: NativeHandle>(
+    env: &JNIEnv<'env>,
+    peer: JObject<'env>,
+    pointer: *const Slot<T>,
+) -> Result<()> {
+    let pointer = JValue::Long(pointer as usize as jlong);
+    // A `long`, as declared by the signature
+    unsafe { T::field_cache().set(env, peer, peer_class::<T>(), T::FIELD, "J", pointer) }
+}
+
+/// Attach `value` to `peer`, which must not own a value already.
+pub fn attach<T: NativeHandle>(env: &JNIEnv, peer: JObject, value: T) -> Result<()> {
+    let slot = Arc::new(Slot {
+        value: Mutex::new(Some(value)),
+        borrower: AtomicUsize::new(0),
+    });
+
+    let _monitor = env.lock_obj(peer)?;
+    if !slot_pointer::<T>(env, peer)?.is_null() {
+        return Err(illegal_state::<T>(env, "already owns a value"));
+    }
+
+    // The reference held by the peer is released when it's closed, or by the cleaner
+    let pointer = Arc::into_raw(slot);
+    let attached = set_slot_pointer::<T>(env, peer, pointer).and_then(|()| {
+        if T::CLEANER {
+            let pointer = pointer as usize;
+            let release = move || unsafe { Arc::decrement_strong_count(pointer as *const Slot<T>) };
+            crate::cleaner::register(env, peer, release)
+                .or_else(|e| set_slot_pointer::<T>(env, peer, std::ptr::null()).and(Err(e)))
+        } else {
+            Ok(())
+        }
+    });
+
+    if attached.is_err() {
+        drop(unsafe { Arc::from_raw(pointer) });
+    }
+
+    attached
+}
+
+/// Value borrowed from a peer, see [`borrow`].
+pub struct Borrowed<T: NativeHandle> {
+    // Dropped before `slot`, which keeps the mutex alive
+    guard: MutexGuard<'static, Option<T>>,
+    slot: Arc<Slot<T>>,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<T: NativeHandle> Deref for Borrowed<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.guard
+            .as_ref()
+            .expect("borrowed value of a closed peer")
+    }
+}
+
+impl<T: NativeHandle> DerefMut for Borrowed<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.guard
+            .as_mut()
+            .expect("borrowed value of a closed peer")
+    }
+}
+
+impl<T: NativeHandle> Drop for Borrowed<T> {
+    fn drop(&mut self) {
+        self.slot.borrower.store(0, Ordering::SeqCst);
+    }
+}
+
+/// Reference to the slot of `peer`, or `None` if it's closed.
+fn slot<T: NativeHandle>(env: &JNIEnv, peer: JObject) -> Result<Option<Arc<Slot<T>>>> {
+    let pointer = slot_pointer::<T>(env, peer)?;
+    if pointer.is_null() {
+        return Ok(None);
+    }
+
+    // The caller holds the monitor of the peer, so the reference held by the peer can't be released meanwhile
+    unsafe {
+        Arc::increment_strong_count(pointer);
+        Ok(Some(Arc::from_raw(pointer)))
+    }
+}
+
+/// Borrow the value owned by `peer`, waiting for other threads borrowing it.
+///
+/// Throws a `java.lang.IllegalStateException` if the peer is closed, or if the current thread is already borrowing its value.
+pub fn borrow<T: NativeHandle>(env: &JNIEnv, peer: JObject) -> Result<Borrowed<T>> {
+    let slot = {
+        let _monitor = env.lock_obj(peer)?;
+        slot::<T>(env, peer)?.ok_or_else(|| illegal_state::<T>(env, "is closed"))?
+    };
+
+    if slot.borrower.load(Ordering::SeqCst) == current_thread() {
+        return Err(illegal_state::<T>(
+            env,
+            "is already borrowed by this thread",
+        ));
+    }
+
+    let guard = slot.value.lock().unwrap_or_else(PoisonError::into_inner);
+    // The mutex lives as long as `slot`, which outlives the guard in `Borrowed`
+    let guard: MutexGuard<'static, Option<T>> = unsafe { std::mem::transmute(guard) };
+    if guard.is_none() {
+        // Closed by another thread while waiting
+        return Err(illegal_state::<T>(env, "is closed"));
+    }
+    slot.borrower.store(current_thread(), Ordering::SeqCst);
+
+    Ok(Borrowed {
+        guard,
+        slot,
+        _not_send: PhantomData,
+    })
+}
+
+/// Close `peer`, returning the value it owned, if any.
+fn close<T: NativeHandle>(env: &JNIEnv, peer: JObject) -> Result<Option<T>> {
+    let slot = {
+        let _monitor = env.lock_obj(peer)?;
+        let slot = match slot::<T>(env, peer)? {
+            Some(slot) => slot,
+            None => return Ok(None),
+        };
+
+        if slot.borrower.load(Ordering::SeqCst) == current_thread() {
+            return Err(illegal_state::<T>(env, "is borrowed by this thread"));
+        }
+
+        set_slot_pointer::<T>(env, peer, std::ptr::null())?;
+        if !T::CLEANER {
+            // Release the reference held by the peer, `slot` is another one
+            unsafe { Arc::decrement_strong_count(Arc::as_ptr(&slot)) };
+        }
+
+        slot
+    };
+
+    // Waits for other threads borrowing the value
+    let value = slot
+        .value
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take();
+    Ok(value)
+}
+
+/// Take the value owned by `peer`, closing it.
+///
+/// Throws a `java.lang.IllegalStateException` if the peer is closed, or if the current thread is borrowing its value.
+pub fn detach<T: NativeHandle>(env: &JNIEnv, peer: JObject) -> Result<T> {
+    close(env, peer)?.ok_or_else(|| illegal_state::<T>(env, "is closed"))
+}
+
+/// Drop the value owned by `peer`, closing it. Returns whether it owned a value, i.e. `false` if it's already closed.
+pub fn dispose<T: NativeHandle>(env: &JNIEnv, peer: JObject) -> Result<bool> {
+    close::<T>(env, peer).map(|value| value.is_some())
+}
+
+/// Body of the `dispose` native methods generated by `#[native_handle(dispose = "...")]`.
+#[doc(hidden)]
+pub fn dispose_native<T: NativeHandle>(env: JNIEnv, peer: JObject) {
+    match crate::panic::catch_unwind(false, || dispose::<T>(&env, peer)) {
+        Ok(Ok(_)) => {}
+        Ok(Err(e)) => {
+            if !env.exception_check().unwrap_or(false) {
+                if let Err(e) = env.throw_new("java/lang/RuntimeException", e.to_string()) {
+                    println!("Error while throwing Java exception: {}", e);
+                }
+            }
+        }
+        Err(panic) => crate::panic::throw_panic(&env, "java/lang/Error", panic),
+    }
+}
@@ -17,6 +17,8 @@
 //!
 //! Structs without the package attribute will be ignored by `robusta_jni`.
 //!
+//! Nested classes also need an `#[outer_class(Outer)]` attribute naming their enclosing class (`Outer.Middle` for deeper nesting).
+//!
 //! In order to use the features of `robusta_jni`, declared structs should also implement the [`Signature`] trait.
 //! This can be done manually or with autoderive.
 //!
@@ -38,6 +40,11 @@
 //!     #[derive(Signature)]
 //!     #[package(my.awesome.package)]
 //!     struct B;
+//!
+//!     #[derive(Signature)]
+//!     #[package(my.awesome.package)]
+//!     #[outer_class(B)] // my.awesome.package.B.C
+//!     struct C;
 //! }
 //! ```
 //!
@@ -51,8 +58,15 @@
 //!
 //! Native methods can optionally accept a [`JNIEnv`] parameter as first parameter (after `self` if present).
 //!
+//! Methods of structs implementing the conversion traits can take `self`, `&self` or `&mut self`: the receiver is converted from the Java instance
+//! for every call. Changes made through `&mut self` are discarded unless written back to the Java instance, see the [`write_back`](convert::write_back) module.
+//!
 //! Methods are declared as standard Rust functions with public visibility and "jni" ABI, and are matched by name with Java methods.
-//! No special handling is needed.
+//! No special handling is needed. Overloads of a Java method are written as Rust methods with different names and the same
+//! `#[java_name = "javaName"]` attribute. With `#[bridge(register_natives)]`, methods are registered when the library is loaded
+//! instead of being exported by symbol name, see the [`registration`] module.
+//! Threads spawned from Rust can call Java through [`vm::with_env`], which attaches them to the Java VM when needed,
+//! and find application classes through the class loaders captured by the [`class_loader`] module.
 //!
 //! Example:
 //!
@@ -108,9 +122,15 @@
 //!
 //! When using `#[call_type(safe)]` or omitting `call_type` attribute, the output type **must** be [`jni::errors::Result<T>`](jni::errors::Result)
 //! with `T` being the actual method return type. Otherwise when using `#[call_type(unchecked)]` `T` is sufficient.
+//! The output type can also be a [`CallResult<T>`](exception::CallResult), which captures and clears the Java exception thrown by the method, if any:
+//! see the [`exception`] module.
 //!
 //! **When using `#[call_type(unchecked)]` if a Java exception is thrown while calling a method a panic is raised.**
 //!
+//! Imported methods and `#[field]`s are only looked up at runtime, unless the `ROBUSTA_CLASSPATH` environment variable lists
+//! the directories and JARs of the Java classes: `#[bridge]` then reports declarations which don't match them as compile errors.
+//! They can also all be checked at once at startup with the `verify` feature, see the [`verify`](mod@verify) module.
+//!
 //! ## Static methods
 //!
 //! Example:
@@ -239,6 +259,11 @@
 //! You can make a Rust native method raise a Java exception simply by returning a [`jni::errors::Result`] with an `Err` variant.
 //! See the [`convert`] module documentation for more information.
 //!
+//! ## Panics
+//! Panics raised inside native methods are caught and rethrown as Java exceptions (by default `java.lang.Error`)
+//! instead of unwinding into the JVM. This can be customized or disabled per method with the `#[catch_panic]` attribute,
+//! see the [`panic`](mod@panic) module documentation for more information.
+//!
 //! ## Library-provided conversions
 //!
 //! | **Rust**                                                                           | **Java**                          |
@@ -252,8 +277,27 @@
 //! | i64                                                                                | long                              |
 //! | i16                                                                                | short                             |
 //! | String                                                                             | String                            |
-//! | Vec\<T\>†                                                                          | ArrayList\<T\>                    |
+//! | Vec\<T\>†                                                                          | ArrayList\<T\>※                   |
+//! | JavaCollection\<T, K\>†                                                            | K (e.g. List\<T\>)※               |
+//! | HashMap\<K, V\>†                                                                   | HashMap\<K, V\>††                 |
+//! | BTreeMap\<K, V\>†                                                                  | TreeMap\<K, V\>††                 |
+//! | HashSet\<T\>†                                                                      | HashSet\<T\>††                    |
+//! | BTreeSet\<T\>†                                                                     | TreeSet\<T\>††                    |
 //! | Box<[u8]>                                                                          | byte[]                            |
+//! | Box<[bool]>                                                                        | boolean[]                         |
+//! | Box<[i8]>                                                                          | byte[]                            |
+//! | Box<[u16]>                                                                         | char[]                            |
+//! | Box<[i16]>                                                                         | short[]                           |
+//! | Box<[i32]>                                                                         | int[]                             |
+//! | Box<[i64]>                                                                         | long[]                            |
+//! | Box<[f32]>                                                                         | float[]                           |
+//! | Box<[f64]>                                                                         | double[]                          |
+//! | JavaSlice<'env, T>, JavaSliceMut<'env, T>¶                                         | T[] (borrowed, input only)        |
+//! | &'borrow [u8], &'borrow mut [u8]∥                                                  | ByteBuffer (direct, input only)   |
+//! | DirectByteBuffer∥                                                                  | ByteBuffer (direct)               |
+//! | Structs without an `#[instance]` field (derived)                                   | class (new object, by value)      |
+//! | Fieldless enums (derived)                                                          | enum                              |
+//! | Option\<T\>†                                                                       | T (`null` for `None`)§            |
 //! | [jni::JObject<'env>](jni::objects::JObject)                                      ‡ | *(any Java object as input type)* |
 //! | [jni::jobject](jni::sys::jobject)                                                    | *(any Java object as output)*     |
 //!
@@ -261,10 +305,20 @@
 //!
 //! ‡ The special `'env` lifetime **must** be used
 //!
+//! †† Any `java.util.Map`/`java.util.Set` implementation is accepted as input
+//!
+//! ※ Any `java.lang.Iterable` is accepted as input, see the [`convert::collection`] module documentation
+//!
+//! § Primitive types are mapped to their wrapper class, e.g. `Option<i32>` to `Integer`
+//!
+//! ¶ Zero-copy views of primitive arrays (`T` is the JNI element type, e.g. `f32` for `float[]`), see the [`convert::slice`] module documentation
+//!
+//! ∥ Only direct buffers are supported; borrowed slices cover the bytes between the buffer position and limit, see the [`convert::buffer`] module documentation
+//!
 //! ## Limitations
 //!
 //! Currently there are some limitations in the conversion mechanism:
-//!  * Boxed types are supported only through the opaque `JObject`/`jobject` types
+//!  * Boxed types are supported only through the opaque `JObject`/`jobject` types, or as `Option<T>` for nullable values
 //!  * Automatic type conversion is limited to the table outlined above, though easily extendable if needed.
 //!
 //! [`Signature`]: convert::Signature
@@ -273,8 +327,21 @@
 
 pub use robusta_codegen::bridge;
 
+pub mod cache;
+pub mod class_loader;
+mod cleaner;
 pub mod convert;
+pub mod exception;
+pub mod handle;
+pub mod panic;
+pub mod registration;
+#[cfg(feature = "verify")]
+pub mod verify;
+pub mod vm;
 
 pub use jni;
 
 pub use static_assertions::assert_type_eq_all;
+
+#[cfg(feature = "verify")]
+pub use verify::verify;
@@ -0,0 +1,135 @@
+//! Panic handling for native methods.
+//!
+//! Unwinding out of an `extern "system"` function is not allowed, so by default the body of every exported method
+//! is run inside [`catch_unwind`]. If the method panics, the panic is turned into a Java exception
+//! (`java.lang.Error` unless otherwise specified) and a zeroed value is returned to the JVM, just like when a `#[call_type(safe)]`
+//! conversion fails.
+//!
+//! This behaviour can be customized per method with the `catch_panic` attribute:
+//!
+//! ```ignore
+//! #[catch_panic(exception_class = "java.lang.IllegalStateException", backtrace)]
+//! ```
+//!
+//! Both parameters are optional. With `backtrace`, the backtrace of the panicking thread is appended to the exception message.
+//!
+//! Panic catching can be turned off on hot paths with:
+//!
+//! ```ignore
+//! #[catch_panic(disable)]
+//! ```
+//!
+//! In that case a panic in the method will abort the process.
+//!
+//! If a Java exception is already pending when the panic is caught (e.g. a `#[call_type(unchecked)]` imported method call failed),
+//! the pending exception is left untouched and propagated to the caller, as it is the actual cause of the panic.
+//!
+
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::cell::{Cell, RefCell};
+use std::fmt::{self, Display, Formatter};
+use std::panic::{self as std_panic, AssertUnwindSafe};
+use std::sync::Once;
+
+use jni::JNIEnv;
+
+thread_local! {
+    static CAPTURE_BACKTRACE: Cell<bool> = const { Cell::new(false) };
+    static LAST_BACKTRACE: RefCell<Option<Backtrace>> = const { RefCell::new(None) };
+}
+
+static INSTALL_HOOK: Once = Once::new();
+
+/// A panic caught while running a native method.
+#[derive(Debug)]
+pub struct Panic {
+    message: String,
+    backtrace: Option<Backtrace>,
+}
+
+impl Panic {
+    fn from_payload(payload: Box<dyn Any + Send>, backtrace: Option<Backtrace>) -> Self {
+        let message = if let Some(s) = payload.downcast_ref::<&'static str>() {
+            (*s).to_string()
+        } else if let Some(s) = payload.downcast_ref::<String>() {
+            s.clone()
+        } else {
+            "Box<dyn Any>".to_string()
+        };
+
+        Panic { message, backtrace }
+    }
+
+    /// Panic message, if the payload was a string (`"Box<dyn Any>"` otherwise).
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Backtrace captured at the panic site, if requested.
+    pub fn backtrace(&self) -> Option<&Backtrace> {
+        self.backtrace.as_ref()
+    }
+}
+
+impl Display for Panic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Rust panic: {}", self.message)?;
+
+        if let Some(backtrace) = &self.backtrace {
+            write!(f, "\n{}", backtrace)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Install (once) a panic hook that records the backtrace of panicking threads that asked for it.
+/// The previously installed hook is still called afterwards.
+fn install_backtrace_hook() {
+    INSTALL_HOOK.call_once(|| {
+        let previous_hook = std_panic::take_hook();
+
+        std_panic::set_hook(Box::new(move |info| {
+            if CAPTURE_BACKTRACE.with(Cell::get) {
+                LAST_BACKTRACE.with(|b| *b.borrow_mut() = Some(Backtrace::force_capture()));
+            }
+
+            previous_hook(info)
+        }));
+    });
+}
+
+/// Run `f`, catching any panic raised while running it.
+///
+/// When `capture_backtrace` is `true`, the returned [`Panic`] also carries the backtrace of the panic site.
+pub fn catch_unwind<F, R>(capture_backtrace: bool, f: F) -> Result<R, Panic>
+where
+    F: FnOnce() -> R,
+{
+    if capture_backtrace {
+        install_backtrace_hook();
+    }
+
+    let previous_capture = CAPTURE_BACKTRACE.with(|c| c.replace(capture_backtrace));
+    let result = std_panic::catch_unwind(AssertUnwindSafe(f));
+    CAPTURE_BACKTRACE.with(|c| c.set(previous_capture));
+
+    result.map_err(|payload| {
+        let backtrace = LAST_BACKTRACE.with(|b| b.borrow_mut().take());
+        Panic::from_payload(payload, backtrace.filter(|_| capture_backtrace))
+    })
+}
+
+/// Throw a Java exception of class `exception_class` (in classpath format, e.g. `java/lang/Error`) describing `panic`.
+///
+/// If a Java exception is already pending, no new exception is thrown.
+pub fn throw_panic(env: &JNIEnv, exception_class: &str, panic: Panic) {
+    if env.exception_check().unwrap_or(false) {
+        return;
+    }
+
+    if let Err(e) = env.throw_new(exception_class, panic.to_string()) {
+        println!("Error while throwing Java exception: {}", e);
+    }
+}
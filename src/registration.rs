@@ -0,0 +1,156 @@
+//! Registering native methods with `RegisterNatives`.
+//!
+//! By default, every exported method is a `#[no_mangle]` function named after the Java method it implements
+//! (e.g. `Java_com_example_robusta_HelloWorld_special`), and the JVM finds it by name when the method is first called.
+//! This doesn't survive class renaming by ProGuard/R8 and exports one dynamic symbol per method.
+//!
+//! With `#[bridge(register_natives)]`, exported methods are no longer `#[no_mangle]`: instead, the macro emits a `JNI_OnLoad`
+//! function that registers all of them when the library is loaded, class by class, with the Java signatures
+//! computed from the [`Signature`](crate::convert::Signature) of their parameter and return types.
+//! The class loaders of the declaring classes are captured as well, see the [`class_loader`](crate::class_loader) module.
+//! Only the declaring classes have to be kept by name in ProGuard/R8 rules, not their symbols.
+//!
+//! If a class can't be found, or one of its methods has no matching `native` declaration, `System.loadLibrary` throws
+//! a `java.lang.UnsatisfiedLinkError` listing the offending methods.
+//!
+//! Only one module per library can use `register_natives`, since a library can only have one `JNI_OnLoad` function.
+//!
+//! Example:
+//! ```
+//! use robusta_jni::bridge;
+//!
+//! #[bridge(register_natives)]
+//! mod jni {
+//!     use robusta_jni::convert::Signature;
+//!
+//!     #[derive(Signature)]
+//!     #[package(com.example.robusta)]
+//!     struct HelloWorld;
+//!
+//!     impl HelloWorld {
+//!         pub extern "jni" fn special(input: i32) -> i32 {
+//!             input * 2
+//!         }
+//!     }
+//! }
+//! ```
+//!
+
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+use jni::sys::{jint, JNI_ERR, JNI_VERSION_1_6};
+use jni::{JNIEnv, JavaVM, NativeMethod};
+
+use crate::exception::JavaException;
+
+/// Error raised when the native methods of a class can't be registered.
+#[derive(Debug)]
+pub struct RegistrationError {
+    class: String,
+    unmatched: Vec<String>,
+    cause: String,
+}
+
+impl RegistrationError {
+    /// Class whose methods couldn't be registered, in classpath format (e.g. `com/example/robusta/HelloWorld`).
+    pub fn class(&self) -> &str {
+        &self.class
+    }
+
+    /// Name and signature of every method without a matching `native` declaration in the class.
+    pub fn unmatched_methods(&self) -> &[String] {
+        &self.unmatched
+    }
+}
+
+impl Display for RegistrationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "can't register native methods of class `{}`", self.class)?;
+
+        if !self.unmatched.is_empty() {
+            write!(
+                f,
+                ": no matching `native` declaration for {}",
+                self.unmatched.join(", ")
+            )?;
+        }
+
+        write!(f, " (cause: {})", self.cause)
+    }
+}
+
+impl Error for RegistrationError {}
+
+/// Clear the pending Java exception, if any, returning its description.
+fn take_pending_exception(env: &JNIEnv) -> Option<String> {
+    let description = JavaException::catch(env).map(|e| e.to_string());
+    // Still pending if it couldn't be captured
+    let _ = env.exception_clear();
+    description
+}
+
+/// Register `methods` on the class `classpath_path` (e.g. `com/example/robusta/HelloWorld`).
+///
+/// If registration fails, each method is registered again on its own to find the ones without a matching `native` declaration.
+pub fn register_natives(
+    env: &JNIEnv,
+    classpath_path: &str,
+    methods: &[NativeMethod],
+) -> Result<(), RegistrationError> {
+    let class =
+        crate::class_loader::find_class(env, classpath_path).map_err(|e| RegistrationError {
+            class: classpath_path.to_string(),
+            unmatched: Vec::new(),
+            cause: take_pending_exception(env).unwrap_or_else(|| e.to_string()),
+        })?;
+
+    if crate::class_loader::capture_class(env, classpath_path, class).is_err() {
+        let _ = env.exception_clear();
+    }
+
+    env.register_native_methods(class, methods).map_err(|e| {
+        let cause = take_pending_exception(env).unwrap_or_else(|| e.to_string());
+
+        let unmatched = methods
+            .iter()
+            .filter(|m| {
+                let failed = env
+                    .register_native_methods(class, std::slice::from_ref(*m))
+                    .is_err();
+                let _ = env.exception_clear();
+
+                failed
+            })
+            .map(|m| format!("`{}{}`", Cow::from(&*m.name), Cow::from(&*m.sig)))
+            .collect();
+
+        RegistrationError {
+            class: classpath_path.to_string(),
+            unmatched,
+            cause,
+        }
+    })
+}
+
+/// Body of the `JNI_OnLoad` function generated by `#[bridge(register_natives)]` and `#[bridge(verify)]`.
+///
+/// Captures the Java VM for the [`vm`](crate::vm) module, then runs `register` and turns its error, if any, into a `java.lang.UnsatisfiedLinkError` thrown by `System.loadLibrary`.
+pub fn on_load(vm: JavaVM, register: impl FnOnce(&JNIEnv) -> Result<(), Box<dyn Error>>) -> jint {
+    crate::vm::set_java_vm(vm);
+
+    let env = match crate::vm::java_vm().map(JavaVM::get_env) {
+        Some(Ok(env)) => env,
+        _ => return JNI_ERR,
+    };
+
+    if let Err(e) = register(&env) {
+        if let Err(e) = env.throw_new("java/lang/UnsatisfiedLinkError", e.to_string()) {
+            println!("Error while throwing Java exception: {}", e);
+            return JNI_ERR;
+        }
+    }
+
+    JNI_VERSION_1_6
+}
@@ -0,0 +1,465 @@
+//! Startup check of the Java members used by bridge modules.
+//!
+//! Imported methods, constructors and fields are looked up the first time they are used, so a mismatch between Rust declarations
+//! and Java classes only shows up when the code path using them runs.
+//!
+//! `#[bridge]` records every member its module uses: imported methods and constructors, `#[field]`s, and the fields and enum constants
+//! read and written by the conversion derive macros, with their class, name and signature. [`verify`] resolves all of them at once
+//! with reflection, returning the list of those that can't be resolved. With `#[bridge(verify)]`, the generated `JNI_OnLoad` function
+//! runs the check when the library is loaded, and `System.loadLibrary` throws a `java.lang.UnsatisfiedLinkError` listing the mismatches.
+//!
+//! Recording the members requires the `verify` feature, which also enables this module: without it, the generated code doesn't
+//! depend on the `inventory` crate, and `#[bridge(verify)]` is a compile error.
+//!
+//! Classes are loaded but not initialized, so that their static initializers don't run from `JNI_OnLoad`.
+//! Members whose signature depends on type parameters, and static methods and constructors taking the class as a parameter, aren't recorded.
+//!
+//! Example:
+//! ```
+//! use robusta_jni::jni::JNIEnv;
+//!
+//! fn check(env: &JNIEnv) {
+//!     if let Err(e) = robusta_jni::verify(env) {
+//!         for mismatch in e.mismatches() {
+//!             eprintln!("{}", mismatch);
+//!         }
+//!     }
+//! }
+//! ```
+//!
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Display, Formatter};
+
+use jni::errors::{Error, Result};
+use jni::objects::{JObject, JValue};
+use jni::sys::JNI_FALSE;
+use jni::JNIEnv;
+
+use crate::exception::JavaException;
+
+#[doc(hidden)]
+pub use inventory;
+
+/// Kind of a Java member.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MemberKind {
+    Constructor,
+    Method,
+    StaticMethod,
+    Field,
+    StaticField,
+}
+
+impl Display for MemberKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            MemberKind::Constructor => "constructor",
+            MemberKind::Method => "method",
+            MemberKind::StaticMethod => "static method",
+            MemberKind::Field => "field",
+            MemberKind::StaticField => "static field",
+        };
+
+        f.write_str(kind)
+    }
+}
+
+/// Java member used by a bridge module.
+#[derive(Debug)]
+pub struct Member {
+    kind: MemberKind,
+    class: &'static str,
+    name: &'static str,
+    signature: &'static [&'static str],
+}
+
+impl Member {
+    #[doc(hidden)]
+    pub const fn new(
+        kind: MemberKind,
+        class: &'static str,
+        name: &'static str,
+        signature: &'static [&'static str],
+    ) -> Self {
+        Member {
+            kind,
+            class,
+            name,
+            signature,
+        }
+    }
+
+    pub fn kind(&self) -> MemberKind {
+        self.kind
+    }
+
+    /// Declaring class, in classpath format (e.g. `com/example/robusta/HelloWorld`).
+    pub fn class(&self) -> &'static str {
+        self.class
+    }
+
+    /// Java name, `<init>` for constructors.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// [Type signature](https://docs.oracle.com/en/java/javase/15/docs/specs/jni/types.html#type-signatures) (e.g. `(I)Ljava/lang/String;`).
+    pub fn signature(&self) -> String {
+        self.signature.concat()
+    }
+}
+
+impl Display for Member {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let class = self.class.replace('/', ".");
+
+        match self.kind {
+            MemberKind::Field | MemberKind::StaticField => write!(
+                f,
+                "{} `{}.{}: {}`",
+                self.kind,
+                class,
+                self.name,
+                self.signature()
+            ),
+            kind => write!(f, "{} `{}.{}{}`", kind, class, self.name, self.signature()),
+        }
+    }
+}
+
+/// Members of a bridge module, collected from every module of the program.
+#[doc(hidden)]
+pub struct Members(pub &'static [Member]);
+
+inventory::collect!(Members);
+
+/// Every member used by the bridge modules of the program.
+pub fn members() -> impl Iterator<Item = &'static Member> {
+    inventory::iter::<Members>.into_iter().flat_map(|m| m.0)
+}
+
+/// Member which can't be resolved.
+#[derive(Debug)]
+pub struct Mismatch {
+    member: &'static Member,
+    cause: String,
+}
+
+impl Mismatch {
+    pub fn member(&self) -> &'static Member {
+        self.member
+    }
+
+    /// Description of the Java exception thrown when resolving the member (e.g. `java.lang.NoSuchMethodError: add`).
+    pub fn cause(&self) -> &str {
+        &self.cause
+    }
+}
+
+impl Display for Mismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "can't resolve {} ({})", self.member, self.cause)
+    }
+}
+
+/// Error returned by [`verify`] when some members can't be resolved.
+#[derive(Debug)]
+pub struct VerifyError {
+    mismatches: Vec<Mismatch>,
+}
+
+impl VerifyError {
+    /// Members which can't be resolved, by class.
+    pub fn mismatches(&self) -> &[Mismatch] {
+        &self.mismatches
+    }
+}
+
+impl Display for VerifyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "can't resolve {} Java member(s)", self.mismatches.len())?;
+
+        for mismatch in &self.mismatches {
+            write!(f, "\n  {} ({})", mismatch.member, mismatch.cause)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+/// Clear the pending Java exception, if any, returning its description.
+fn take_pending_exception(env: &JNIEnv, error: Error) -> String {
+    let description = JavaException::catch(env)
+        .map(|e| e.to_string())
+        .unwrap_or_else(|| error.to_string());
+    // Still pending if it couldn't be captured
+    let _ = env.exception_clear();
+    description
+}
+
+fn call_object<'env>(
+    env: &JNIEnv<'env>,
+    obj: JObject<'env>,
+    name: &str,
+    signature: &str,
+    args: &[JValue],
+) -> Result<JObject<'env>> {
+    env.call_method(obj, name, signature, args)?.l()
+}
+
+fn is_static(env: &JNIEnv, member: JObject) -> Result<bool> {
+    const STATIC: i32 = 0x0008;
+
+    Ok(env.call_method(member, "getModifiers", "()I", &[])?.i()? & STATIC != 0)
+}
+
+/// Class loader used to load `classpath_path`: the one captured by the [`class_loader`](crate::class_loader) module, if any,
+/// or else the context class loader of the current thread.
+fn class_loader<'env>(env: &JNIEnv<'env>, classpath_path: &str) -> Result<JObject<'env>> {
+    match crate::class_loader::class_loader(classpath_path)
+        .or_else(crate::class_loader::default_class_loader)
+    {
+        Some(loader) => env.new_local_ref(loader.as_obj()),
+        None => {
+            let thread = env
+                .call_static_method(
+                    "java/lang/Thread",
+                    "currentThread",
+                    "()Ljava/lang/Thread;",
+                    &[],
+                )?
+                .l()?;
+            call_object(
+                env,
+                thread,
+                "getContextClassLoader",
+                "()Ljava/lang/ClassLoader;",
+                &[],
+            )
+        }
+    }
+}
+
+/// The class, then its superclasses and superinterfaces.
+fn supertypes<'env>(env: &JNIEnv<'env>, class: JObject<'env>) -> Result<Vec<JObject<'env>>> {
+    let mut types = vec![class];
+
+    let mut i = 0;
+    while i < types.len() {
+        let superclass = call_object(env, types[i], "getSuperclass", "()Ljava/lang/Class;", &[])?;
+        if !superclass.is_null() {
+            types.push(superclass);
+        }
+
+        let interfaces =
+            call_object(env, types[i], "getInterfaces", "()[Ljava/lang/Class;", &[])?.into_raw();
+        for j in 0..env.get_array_length(interfaces)? {
+            types.push(env.get_object_array_element(interfaces, j)?);
+        }
+
+        i += 1;
+    }
+
+    Ok(types)
+}
+
+/// Look up `member` in `types` with reflection, returning why it can't be resolved, if it can't.
+///
+/// Unlike `GetMethodID` and `GetFieldID`, reflection doesn't initialize classes: from `JNI_OnLoad`, static initializers
+/// calling native methods of the library being loaded would fail.
+fn resolve<'env>(
+    env: &JNIEnv<'env>,
+    loader: JObject<'env>,
+    types: &[JObject<'env>],
+    member: &Member,
+) -> Result<Option<String>> {
+    let (descriptor, is_field) = match member.kind {
+        MemberKind::Field | MemberKind::StaticField => (format!("(){}", member.signature()), true),
+        _ => (member.signature(), false),
+    };
+    // Loads the parameter and return types, without initializing them either
+    let method_type = {
+        let descriptor = env.new_string(descriptor)?;
+        env.call_static_method(
+            "java/lang/invoke/MethodType",
+            "fromMethodDescriptorString",
+            "(Ljava/lang/String;Ljava/lang/ClassLoader;)Ljava/lang/invoke/MethodType;",
+            &[JValue::Object(descriptor.into()), JValue::Object(loader)],
+        )?
+        .l()?
+    };
+    let return_type = call_object(env, method_type, "returnType", "()Ljava/lang/Class;", &[])?;
+    let parameters = call_object(
+        env,
+        method_type,
+        "parameterArray",
+        "()[Ljava/lang/Class;",
+        &[],
+    )?;
+    let name = JObject::from(env.new_string(member.name)?);
+
+    if member.kind == MemberKind::Constructor {
+        return match call_object(
+            env,
+            types[0],
+            "getDeclaredConstructor",
+            "([Ljava/lang/Class;)Ljava/lang/reflect/Constructor;",
+            &[JValue::Object(parameters)],
+        ) {
+            Ok(_) => Ok(None),
+            Err(Error::JavaException) => {
+                env.exception_clear()?;
+                Ok(Some("no such constructor".to_string()))
+            }
+            Err(e) => Err(e),
+        };
+    }
+
+    for &ty in types {
+        let found = if is_field {
+            call_object(
+                env,
+                ty,
+                "getDeclaredField",
+                "(Ljava/lang/String;)Ljava/lang/reflect/Field;",
+                &[JValue::Object(name)],
+            )
+        } else {
+            call_object(
+                env,
+                ty,
+                "getDeclaredMethod",
+                "(Ljava/lang/String;[Ljava/lang/Class;)Ljava/lang/reflect/Method;",
+                &[JValue::Object(name), JValue::Object(parameters)],
+            )
+        };
+        let found = match found {
+            Ok(found) => found,
+            Err(Error::JavaException) => {
+                env.exception_clear()?;
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+
+        let found_type = if is_field {
+            call_object(env, found, "getType", "()Ljava/lang/Class;", &[])?
+        } else {
+            call_object(env, found, "getReturnType", "()Ljava/lang/Class;", &[])?
+        };
+        if !env.is_same_object(found_type, return_type)? {
+            continue;
+        }
+
+        let expected_static = matches!(
+            member.kind,
+            MemberKind::StaticMethod | MemberKind::StaticField
+        );
+        return Ok(match (is_static(env, found)?, expected_static) {
+            (true, false) => Some(format!(
+                "the {} is static",
+                if is_field { "field" } else { "method" }
+            )),
+            (false, true) => Some(format!(
+                "the {} isn't static",
+                if is_field { "field" } else { "method" }
+            )),
+            _ => None,
+        });
+    }
+
+    Ok(Some(format!(
+        "no such {} in the class or its supertypes",
+        if is_field { "field" } else { "method" }
+    )))
+}
+
+/// Check the members of the class `classpath_path`, adding the ones which can't be resolved to `mismatches`.
+fn verify_class(
+    env: &JNIEnv,
+    classpath_path: &str,
+    members: &[&'static Member],
+    mismatches: &mut Vec<Mismatch>,
+) -> Result<()> {
+    let loader = class_loader(env, classpath_path)?;
+    let class = {
+        let name = env.new_string(classpath_path.replace('/', "."))?;
+        env.call_static_method(
+            "java/lang/Class",
+            "forName",
+            "(Ljava/lang/String;ZLjava/lang/ClassLoader;)Ljava/lang/Class;",
+            &[
+                JValue::Object(name.into()),
+                JValue::Bool(JNI_FALSE),
+                JValue::Object(loader),
+            ],
+        )
+        .and_then(|c| c.l())
+    };
+    let class = match class {
+        Ok(class) => class,
+        Err(e) => {
+            let cause = take_pending_exception(env, e);
+            mismatches.extend(members.iter().map(|&member| Mismatch {
+                member,
+                cause: cause.clone(),
+            }));
+            return Ok(());
+        }
+    };
+    let types = supertypes(env, class)?;
+
+    for &member in members {
+        let mut cause = None;
+        env.with_local_frame(16, || {
+            cause = resolve(env, loader, &types, member)
+                .unwrap_or_else(|e| Some(take_pending_exception(env, e)));
+            Ok(JObject::null())
+        })?;
+
+        if let Some(cause) = cause {
+            mismatches.push(Mismatch { member, cause });
+        }
+    }
+
+    Ok(())
+}
+
+/// Resolve every member used by the bridge modules of the program, see the [module documentation](self).
+///
+/// Classes are loaded with the class loaders captured by the [`class_loader`](crate::class_loader) module,
+/// but aren't initialized.
+pub fn verify(env: &JNIEnv) -> std::result::Result<(), VerifyError> {
+    let mut classes: BTreeMap<&str, Vec<&Member>> = BTreeMap::new();
+    let mut seen = BTreeSet::new();
+    for member in members() {
+        if seen.insert((member.class, member.kind, member.name, member.signature)) {
+            classes.entry(member.class).or_default().push(member);
+        }
+    }
+
+    let mut mismatches = Vec::new();
+    for (classpath_path, members) in classes {
+        let checked = env.with_local_frame(16, || {
+            verify_class(env, classpath_path, &members, &mut mismatches)?;
+            Ok(JObject::null())
+        });
+
+        if let Err(e) = checked {
+            let cause = take_pending_exception(env, e);
+            mismatches.extend(members.into_iter().map(|member| Mismatch {
+                member,
+                cause: cause.clone(),
+            }));
+        }
+    }
+
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(VerifyError { mismatches })
+    }
+}
@@ -0,0 +1,141 @@
+//! Access to the Java VM from any thread.
+//!
+//! Calling Java requires a [`JNIEnv`], which is only valid on the thread it belongs to: threads spawned from Rust
+//! have to be attached to the Java VM first. This module keeps the [`JavaVM`] of the library in a global,
+//! so that any thread can get an environment with [`with_env`] or [`with_daemon_env`]:
+//!
+//! ```
+//! use robusta_jni::bridge;
+//!
+//! #[bridge]
+//! mod jni {
+//!     use robusta_jni::convert::Signature;
+//!     use robusta_jni::jni::errors::Result as JniResult;
+//!     use robusta_jni::jni::JNIEnv;
+//!     use robusta_jni::vm;
+//!
+//!     #[derive(Signature)]
+//!     #[package(com.example.robusta)]
+//!     struct Progress;
+//!
+//!     impl Progress {
+//!         pub extern "java" fn report(env: &JNIEnv, percent: i32) -> JniResult<()> {}
+//!
+//!         pub extern "jni" fn startWork() {
+//!             std::thread::spawn(|| {
+//!                 for percent in (0..=100).step_by(10) {
+//!                     vm::with_env(|env| Progress::report(env, percent)).unwrap().unwrap();
+//!                 }
+//!             });
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! The Java VM is captured automatically, when the library is loaded if it uses `#[bridge(register_natives)]` or `#[bridge(verify)]`,
+//! or else on the first call to one of its native methods (later calls only check a `std::sync::Once`): until then,
+//! [`with_env`] fails with [`VmError::Uninitialized`]. A library with a hand-written `JNI_OnLoad` can capture it
+//! earlier with [`set_java_vm`].
+//!
+//! A thread attached by this module stays attached, so that later calls don't pay for attaching it again,
+//! and is detached automatically when it exits. Threads that are already attached, e.g. Java threads calling a native method,
+//! are used as they are.
+//!
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::sync::OnceLock;
+
+use jni::objects::JObject;
+use jni::{JNIEnv, JavaVM};
+
+/// Capacity of the local reference frame pushed around each closure.
+const LOCAL_FRAME_CAPACITY: i32 = 16;
+
+static JAVA_VM: OnceLock<JavaVM> = OnceLock::new();
+
+/// Error raised when a thread can't get an environment.
+#[derive(Debug)]
+pub enum VmError {
+    /// The Java VM hasn't been captured yet.
+    Uninitialized,
+    /// The thread couldn't be attached to the Java VM.
+    Jni(jni::errors::Error),
+}
+
+impl Display for VmError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            VmError::Uninitialized => write!(f, "the Java VM hasn't been captured yet"),
+            VmError::Jni(e) => write!(f, "can't attach thread to the Java VM: {}", e),
+        }
+    }
+}
+
+impl Error for VmError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            VmError::Uninitialized => None,
+            VmError::Jni(e) => Some(e),
+        }
+    }
+}
+
+impl From<jni::errors::Error> for VmError {
+    fn from(e: jni::errors::Error) -> Self {
+        VmError::Jni(e)
+    }
+}
+
+/// Set the Java VM used by this module, if not set already.
+pub fn set_java_vm(vm: JavaVM) {
+    let _ = JAVA_VM.set(vm);
+}
+
+/// Capture the Java VM of `env`, if not captured already.
+///
+/// Called by the generated glue of native methods, unless the bridge module generates a `JNI_OnLoad`.
+pub fn capture(env: &JNIEnv) {
+    if JAVA_VM.get().is_none() {
+        if let Ok(vm) = env.get_java_vm() {
+            set_java_vm(vm);
+        }
+    }
+}
+
+/// The Java VM, if captured.
+pub fn java_vm() -> Option<&'static JavaVM> {
+    JAVA_VM.get()
+}
+
+/// Pops the local reference frame when dropped, even if the closure panics.
+struct LocalFrame<'a, 'env>(&'a JNIEnv<'env>);
+
+impl Drop for LocalFrame<'_, '_> {
+    fn drop(&mut self) {
+        let _ = self.0.pop_local_frame(JObject::null());
+    }
+}
+
+fn run<R>(env: JNIEnv, f: impl FnOnce(&JNIEnv<'_>) -> R) -> Result<R, VmError> {
+    // Permanently attached threads never return to Java, so their local references have to be freed here
+    env.push_local_frame(LOCAL_FRAME_CAPACITY)?;
+    let _frame = LocalFrame(&env);
+
+    Ok(f(&env))
+}
+
+/// Run `f` with the environment of the current thread, attaching it to the Java VM if needed.
+///
+/// Local references created by `f` are freed when it returns.
+pub fn with_env<R>(f: impl FnOnce(&JNIEnv<'_>) -> R) -> Result<R, VmError> {
+    let vm = java_vm().ok_or(VmError::Uninitialized)?;
+    run(vm.attach_current_thread_permanently()?, f)
+}
+
+/// Same as [`with_env`], but the current thread is attached as a daemon thread if needed,
+/// so that it doesn't prevent the Java VM from exiting.
+pub fn with_daemon_env<R>(f: impl FnOnce(&JNIEnv<'_>) -> R) -> Result<R, VmError> {
+    let vm = java_vm().ok_or(VmError::Uninitialized)?;
+    run(vm.attach_current_thread_as_daemon()?, f)
+}
@@ -1,17 +1,100 @@
+use std::fmt::{self, Display, Formatter};
+
 use robusta_jni::bridge;
+use robusta_jni::exception::{CallError, IntoJavaException};
+
+#[derive(Debug, IntoJavaException)]
+pub enum LookupError {
+    #[exception(class = "java.util.NoSuchElementException")]
+    Missing(String),
+    #[exception(class = "java.lang.IllegalArgumentException", message = "blank key")]
+    Blank,
+    Java(#[exception(cause)] CallError),
+}
+
+impl Display for LookupError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            LookupError::Missing(key) => write!(f, "no value for {}", key),
+            LookupError::Blank => write!(f, "blank"),
+            LookupError::Java(e) => write!(f, "lookup failed: {}", e),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("missing key `{0}`")]
+    Missing(String),
+    #[error("invalid value")]
+    Invalid(#[from] std::num::ParseIntError),
+}
 
 #[bridge]
 pub mod jni {
+    use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
     use std::convert::TryInto;
+    use std::error::Error;
+    use std::sync::atomic::{AtomicI32, Ordering};
+
+    use anyhow::Context;
 
+    use robusta_jni::class_loader;
+    use robusta_jni::convert::collection::{Collection, JavaCollection, LinkedList, List};
     use robusta_jni::convert::{
-        IntoJavaValue, JValueWrapper, Signature, TryFromJavaValue, TryIntoJavaValue,
+        DirectByteBuffer, FromJavaValue, IntoJavaValue, JValueWrapper, JavaSlice, JavaSliceMut,
+        Signature, TryFromJavaValue, TryIntoJavaValue, WriteBack,
     };
+    use robusta_jni::exception::{CallError, CallResult};
+    use robusta_jni::handle::NativeHandle;
     use robusta_jni::jni::errors::Result as JniResult;
-    use robusta_jni::jni::objects::AutoLocal;
+    use robusta_jni::jni::objects::{AutoLocal, JObject};
     use robusta_jni::jni::JNIEnv;
+    use robusta_jni::vm;
+
+    use crate::LookupError;
+
+    #[derive(
+        Signature,
+        TryIntoJavaValue,
+        IntoJavaValue,
+        TryFromJavaValue,
+        FromJavaValue,
+        Clone,
+        Copy,
+        Debug,
+        PartialEq,
+    )]
+    #[package()]
+    pub enum UserStatus {
+        #[java_name = "ACTIVE"]
+        Active,
+        #[java_name = "SUSPENDED"]
+        Suspended,
+        #[java_name = "DELETED"]
+        Deleted,
+    }
 
-    #[derive(Signature, TryIntoJavaValue, IntoJavaValue, TryFromJavaValue)]
+    #[derive(Signature, TryIntoJavaValue, TryFromJavaValue, Clone, Debug, PartialEq)]
+    #[package()]
+    #[constructor]
+    pub struct Point {
+        x: i32,
+        y: i32,
+        label: String,
+    }
+
+    #[derive(Signature, TryIntoJavaValue, TryFromJavaValue, Clone, Debug, PartialEq)]
+    #[package()]
+    pub struct Measurement {
+        name: String,
+        #[java_name = "v"]
+        value: f64,
+    }
+
+    #[derive(
+        Signature, TryIntoJavaValue, IntoJavaValue, TryFromJavaValue, FromJavaValue, WriteBack,
+    )]
     #[package()]
     pub struct User<'env: 'borrow, 'borrow> {
         #[instance]
@@ -47,6 +130,36 @@ pub mod jni {
             user_pw + "_pass"
         }
 
+        pub extern "jni" fn passwordLength(&self) -> i32 {
+            self.password.len() as i32
+        }
+
+        #[write_back]
+        pub extern "jni" fn changePassword(&mut self, password: String) {
+            self.password = password;
+        }
+
+        #[call_type(unchecked)]
+        #[write_back]
+        pub extern "jni" fn appendToPassword(&mut self, suffix: String) -> i32 {
+            self.password.push_str(&suffix);
+            self.password.len() as i32
+        }
+
+        #[write_back]
+        pub extern "jni" fn changePasswordIfNumeric(
+            &mut self,
+            password: String,
+        ) -> Result<(), crate::ConfigError> {
+            self.password = password;
+            self.password.parse::<i32>()?;
+            Ok(())
+        }
+
+        pub extern "jni" fn discardPasswordChange(&mut self, password: String) {
+            self.password = password;
+        }
+
         pub extern "jni" fn getInt(self, v: i32) -> i32 {
             v
         }
@@ -95,6 +208,150 @@ pub mod jni {
             v
         }
 
+        pub extern "jni" fn getPrimitiveBoolArray(self, v: Box<[bool]>) -> Box<[bool]> {
+            v
+        }
+
+        pub extern "jni" fn getPrimitiveByteArray(self, v: Box<[i8]>) -> Box<[i8]> {
+            v
+        }
+
+        pub extern "jni" fn getPrimitiveCharArray(self, v: Box<[u16]>) -> Box<[u16]> {
+            v
+        }
+
+        pub extern "jni" fn getPrimitiveShortArray(self, v: Box<[i16]>) -> Box<[i16]> {
+            v
+        }
+
+        pub extern "jni" fn getPrimitiveIntArray(self, v: Box<[i32]>) -> Box<[i32]> {
+            v
+        }
+
+        pub extern "jni" fn getPrimitiveLongArray(self, v: Box<[i64]>) -> Box<[i64]> {
+            v
+        }
+
+        pub extern "jni" fn getPrimitiveFloatArray(self, v: Box<[f32]>) -> Box<[f32]> {
+            v
+        }
+
+        pub extern "jni" fn getPrimitiveDoubleArray(self, v: Box<[f64]>) -> Box<[f64]> {
+            v
+        }
+
+        pub extern "jni" fn sumFloatArray(self, v: JavaSlice<'env, f32>) -> f32 {
+            v.iter().sum()
+        }
+
+        pub extern "jni" fn scaleIntArray(self, mut v: JavaSliceMut<'env, i32>, factor: i32) {
+            v.iter_mut().for_each(|x| *x *= factor);
+        }
+
+        pub extern "jni" fn addIntArray(
+            self,
+            mut dst: JavaSliceMut<'env, i32>,
+            src: JavaSlice<'env, i32>,
+        ) {
+            dst.iter_mut().zip(src.iter()).for_each(|(d, s)| *d += s);
+        }
+
+        pub extern "jni" fn sumAsDoubleArray(
+            self,
+            env: &JNIEnv<'env>,
+            array: JObject<'env>,
+        ) -> JniResult<f64> {
+            Ok(JavaSlice::<f64>::new(array, env)?.iter().sum())
+        }
+
+        pub extern "jni" fn byteBufferSum(self, data: &'borrow [u8]) -> i32 {
+            data.iter().map(|&b| b as i32).sum()
+        }
+
+        pub extern "jni" fn fillByteBuffer(self, data: &'borrow mut [u8], value: i8) {
+            data.fill(value as u8)
+        }
+
+        pub extern "jni" fn newByteBuffer(self, len: i32, value: i8) -> DirectByteBuffer {
+            From::from(vec![value as u8; len as usize])
+        }
+
+        pub extern "jni" fn getMap(self, v: HashMap<String, i32>) -> HashMap<String, i32> {
+            v
+        }
+
+        pub extern "jni" fn getSortedMap(self, v: BTreeMap<i32, String>) -> BTreeMap<i32, String> {
+            v
+        }
+
+        pub extern "jni" fn getSet(self, v: HashSet<String>) -> HashSet<String> {
+            v
+        }
+
+        pub extern "jni" fn getSortedSet(self, v: BTreeSet<i64>) -> BTreeSet<i64> {
+            v
+        }
+
+        pub extern "jni" fn sortedStrings(
+            self,
+            v: JavaCollection<String, Collection>,
+        ) -> JavaCollection<String, LinkedList> {
+            let mut v = v.into_inner();
+            v.sort();
+            From::from(v)
+        }
+
+        pub extern "jni" fn nextStatus(self, status: UserStatus) -> UserStatus {
+            match status {
+                UserStatus::Active => UserStatus::Suspended,
+                UserStatus::Suspended => UserStatus::Deleted,
+                UserStatus::Deleted => UserStatus::Active,
+            }
+        }
+
+        #[call_type(unchecked)]
+        pub extern "jni" fn statusToString(status: UserStatus) -> String {
+            format!("{:?}", status)
+        }
+
+        #[java_name = "describe"]
+        pub extern "jni" fn describeInt(v: i32) -> String {
+            format!("int {}", v)
+        }
+
+        #[java_name = "describe"]
+        pub extern "jni" fn describeStatus(status: UserStatus) -> String {
+            format!("status {:?}", status)
+        }
+
+        #[java_name = "describe"]
+        pub extern "jni" fn describeLabeledPoint(label: Option<String>, p: Point) -> String {
+            format!("{} ({}, {})", label.unwrap_or(p.label), p.x, p.y)
+        }
+
+        pub extern "jni" fn translatePoint(self, p: Point, dx: i32, dy: i32) -> Point {
+            Point {
+                x: p.x + dx,
+                y: p.y + dy,
+                label: format!("{} (translated)", p.label),
+            }
+        }
+
+        pub extern "jni" fn scaleMeasurement(self, m: Measurement, factor: f64) -> Measurement {
+            Measurement {
+                value: m.value * factor,
+                ..m
+            }
+        }
+
+        pub extern "jni" fn getOptionalString(self, v: Option<String>) -> Option<String> {
+            v
+        }
+
+        pub extern "jni" fn getOptionalInt(self, v: Option<i32>) -> Option<i32> {
+            v
+        }
+
         pub extern "jni" fn intToString(self, v: i32) -> String {
             format!("{}", v)
         }
@@ -139,6 +396,16 @@ pub mod jni {
             format!("{:?}", v)
         }
 
+        pub extern "jni" fn panicking(self, message: String) -> i32 {
+            panic!("{}", message)
+        }
+
+        #[call_type(unchecked)]
+        #[catch_panic(exception_class = "java.lang.IllegalStateException")]
+        pub extern "jni" fn panickingUnchecked(message: String) -> i32 {
+            panic!("{}", message)
+        }
+
         pub extern "java" fn getPassword(
             &self,
             env: &JNIEnv,
@@ -150,6 +417,100 @@ pub mod jni {
         ) -> ::robusta_jni::jni::errors::Result<i32> {
         }
 
+        pub extern "java" fn parseCount(env: &JNIEnv, s: String) -> CallResult<i32> {}
+
+        pub extern "jni" fn lookupCount(env: &JNIEnv, key: String) -> Result<i32, LookupError> {
+            match key.as_str() {
+                "" => Err(LookupError::Blank),
+                "missing" => Err(LookupError::Missing(key)),
+                _ => User::parseCount(env, key).map_err(LookupError::Java),
+            }
+        }
+
+        #[java_name = "parseCount"]
+        pub extern "java" fn parseCountRaw(env: &JNIEnv, s: String) -> JniResult<i32> {}
+
+        pub extern "jni" fn propagateParseCount(env: &JNIEnv, s: String) -> JniResult<i32> {
+            User::parseCountRaw(env, s)
+        }
+
+        #[call_type(safe(
+            exception_class = "java.lang.IllegalStateException",
+            message = "can't parse count",
+            wrap_pending
+        ))]
+        pub extern "jni" fn wrapParseCount(env: &JNIEnv, s: String) -> JniResult<i32> {
+            User::parseCountRaw(env, s)
+        }
+
+        #[call_type(safe(
+            exception_class = "java.lang.IllegalStateException",
+            message = "can't look up count",
+            wrap_pending
+        ))]
+        pub extern "jni" fn wrapLookupCount(env: &JNIEnv, key: String) -> Result<i32, LookupError> {
+            User::lookupCount(env, key)
+        }
+
+        pub extern "jni" fn parseCountOnThread(s: String) -> i32 {
+            std::thread::spawn(move || {
+                // The second call reuses the attachment of the first one
+                let count = vm::with_env(|env| User::parseCount(env, s).unwrap_or(-1)).unwrap();
+                let total = vm::with_env(|env| User::getTotalUsersCount(env).unwrap()).unwrap();
+                assert!(total > 0);
+                count
+            })
+            .join()
+            .unwrap()
+        }
+
+        pub extern "jni" fn findClassOnThread(name: String) -> bool {
+            std::thread::spawn(move || {
+                vm::with_env(|env| {
+                    class_loader::class_loader(&name).is_some()
+                        && class_loader::find_class(env, &name).is_ok()
+                })
+                .unwrap()
+            })
+            .join()
+            .unwrap()
+        }
+
+        pub extern "jni" fn parsePort(value: String) -> Result<i32, Box<dyn Error + Send + Sync>> {
+            Ok(value.parse()?)
+        }
+
+        pub extern "jni" fn configValue(key: String) -> Result<i32, crate::ConfigError> {
+            match key.split_once('=') {
+                Some((_, value)) => Ok(value.parse()?),
+                None => Err(crate::ConfigError::Missing(key)),
+            }
+        }
+
+        pub extern "jni" fn checkedPort(value: String) -> anyhow::Result<i32> {
+            let port: i32 = value
+                .parse()
+                .with_context(|| format!("invalid port `{}`", value))?;
+            anyhow::ensure!(port > 0, "port {} out of range", port);
+            Ok(port)
+        }
+
+        pub extern "jni" fn describeParseCount(env: &JNIEnv, s: String) -> String {
+            match User::parseCount(env, s) {
+                Ok(count) => format!("count: {}", count),
+                Err(CallError::Exception(e)) => format!(
+                    "{} (IOException: {}, cause: {}, traced: {})",
+                    e,
+                    e.is_instance_of(env, "java/io/IOException"),
+                    e.cause().map(ToString::to_string).unwrap_or_default(),
+                    e.stack_trace()
+                        .iter()
+                        .any(|f| f.contains("User.parseCount")),
+                ),
+                Err(CallError::Jni(e)) => panic!("{}", e),
+            }
+        }
+
         pub extern "java" fn multipleParameters(
             &self,
             env: &JNIEnv,
@@ -158,6 +519,20 @@ pub mod jni {
         ) -> ::robusta_jni::jni::errors::Result<String> {
         }
 
+        pub extern "java" fn nullableParameter(
+            &self,
+            env: &JNIEnv,
+            s: Option<String>,
+        ) -> ::robusta_jni::jni::errors::Result<Option<String>> {
+        }
+
+        pub extern "java" fn joinStrings(
+            &self,
+            env: &JNIEnv,
+            v: JavaCollection<String, List>,
+        ) -> ::robusta_jni::jni::errors::Result<String> {
+        }
+
         #[constructor]
         pub extern "java" fn new(
             env: &'borrow JNIEnv<'env>,
@@ -166,4 +541,99 @@ pub mod jni {
         ) -> JniResult<Self> {
         }
     }
+
+    #[derive(Signature)]
+    #[package()]
+    #[outer_class(User)]
+    pub struct Settings;
+
+    impl Settings {
+        pub extern "jni" fn normalize_key(env: &JNIEnv, key: String) -> String {
+            let prefix = Settings::defaultPrefix(env).unwrap();
+            format!("{}{}", prefix, key.trim().to_lowercase().replace(' ', "_"))
+        }
+
+        pub extern "java" fn defaultPrefix(env: &JNIEnv) -> JniResult<String> {}
+    }
+
+    #[derive(Signature, NativeHandle)]
+    #[package()]
+    #[native_handle(dispose = "close", cleaner)]
+    pub struct Accumulator {
+        values: Vec<i64>,
+    }
+
+    impl Accumulator {
+        pub extern "jni" fn create() -> JniResult<Accumulator> {
+            Ok(Accumulator { values: Vec::new() })
+        }
+
+        pub extern "jni" fn add(&mut self, value: i64) -> JniResult<i64> {
+            self.values.push(value);
+            self.total()
+        }
+
+        pub extern "jni" fn total(&self) -> JniResult<i64> {
+            Ok(self.values.iter().sum())
+        }
+
+        pub extern "jni" fn merge(&mut self, other: &Accumulator) -> JniResult<i64> {
+            self.values.extend_from_slice(&other.values);
+            self.total()
+        }
+
+        pub extern "jni" fn drain(self) -> JniResult<i64> {
+            self.total()
+        }
+
+        pub extern "jni" fn droppedCount() -> i32 {
+            ACCUMULATORS_DROPPED.load(Ordering::SeqCst)
+        }
+    }
+
+    static ACCUMULATORS_DROPPED: AtomicI32 = AtomicI32::new(0);
+
+    impl Drop for Accumulator {
+        fn drop(&mut self) {
+            ACCUMULATORS_DROPPED.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+}
+
+#[bridge(register_natives, verify)]
+pub mod registered {
+    use robusta_jni::convert::{Signature, TryFromJavaValue, TryIntoJavaValue};
+    use robusta_jni::jni::errors::Result as JniResult;
+    use robusta_jni::jni::objects::AutoLocal;
+
+    #[derive(Signature, TryIntoJavaValue, TryFromJavaValue)]
+    #[package()]
+    pub struct Counter<'env: 'borrow, 'borrow> {
+        #[instance]
+        raw: AutoLocal<'env, 'borrow>,
+        count: i32,
+    }
+
+    impl<'env: 'borrow, 'borrow> Counter<'env, 'borrow> {
+        pub extern "jni" fn peek(&self, amount: i32) -> JniResult<i32> {
+            Ok(self.count + amount)
+        }
+
+        pub extern "jni" fn describe(count: i32, label: String) -> JniResult<String> {
+            Ok(format!("{}: {}", label, count))
+        }
+
+        #[java_name = "peek"]
+        pub extern "jni" fn peekScaled(&self, amount: i32, factor: i32) -> JniResult<i32> {
+            Ok(self.count + amount * factor)
+        }
+
+        pub extern "jni" fn assertCount(self, expected: i32) {
+            assert_eq!(self.count, expected);
+        }
+
+        pub extern "jni" fn verifiedMembers() -> i32 {
+            robusta_jni::verify::members().count() as i32
+        }
+    }
 }
@@ -0,0 +1,24 @@
+public class Accumulator implements AutoCloseable {
+    static {
+        System.loadLibrary("native");
+    }
+
+    private long nativeHandle;
+
+    private Accumulator() {
+    }
+
+    public static native Accumulator create();
+
+    public native long add(long value);
+
+    public native long total();
+
+    public native long merge(Accumulator other);
+
+    public native long drain();
+
+    public static native int droppedCount();
+
+    public native void close();
+}
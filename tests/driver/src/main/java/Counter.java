@@ -0,0 +1,21 @@
+public class Counter {
+    static {
+        System.loadLibrary("native");
+    }
+
+    private int count;
+
+    public Counter(int count) {
+        this.count = count;
+    }
+
+    public native int peek(int amount);
+
+    public native int peek(int amount, int factor);
+
+    public static native String describe(int count, String label);
+
+    public native void assertCount(int expected);
+
+    public static native int verifiedMembers();
+}
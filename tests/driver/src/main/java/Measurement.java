@@ -0,0 +1,9 @@
+public class Measurement {
+    public String name;
+    public double v;
+
+    public Measurement(String name, double v) {
+        this.name = name;
+        this.v = v;
+    }
+}
@@ -1,4 +1,11 @@
+import java.io.IOException;
+import java.nio.ByteBuffer;
+import java.util.Collection;
 import java.util.List;
+import java.util.Map;
+import java.util.Set;
+import java.util.SortedMap;
+import java.util.SortedSet;
 
 public class User {
     static {
@@ -35,6 +42,64 @@ public class User {
 
     public native byte[] getByteArray(byte[] x);
 
+    public native boolean[] getPrimitiveBoolArray(boolean[] x);
+
+    public native byte[] getPrimitiveByteArray(byte[] x);
+
+    public native char[] getPrimitiveCharArray(char[] x);
+
+    public native short[] getPrimitiveShortArray(short[] x);
+
+    public native int[] getPrimitiveIntArray(int[] x);
+
+    public native long[] getPrimitiveLongArray(long[] x);
+
+    public native float[] getPrimitiveFloatArray(float[] x);
+
+    public native double[] getPrimitiveDoubleArray(double[] x);
+
+    public native float sumFloatArray(float[] x);
+
+    public native void scaleIntArray(int[] x, int factor);
+
+    public native void addIntArray(int[] dst, int[] src);
+
+    public native double sumAsDoubleArray(Object array);
+
+    public native int byteBufferSum(ByteBuffer x);
+
+    public native void fillByteBuffer(ByteBuffer x, byte value);
+
+    public native ByteBuffer newByteBuffer(int len, byte value);
+
+    public native Map<String, Integer> getMap(Map<String, Integer> x);
+
+    public native SortedMap<Integer, String> getSortedMap(Map<Integer, String> x);
+
+    public native Set<String> getSet(Set<String> x);
+
+    public native SortedSet<Long> getSortedSet(Set<Long> x);
+
+    public native List<String> sortedStrings(Collection<String> x);
+
+    public native UserStatus nextStatus(UserStatus x);
+
+    public static native String statusToString(UserStatus x);
+
+    public static native String describe(int x);
+
+    public static native String describe(UserStatus x);
+
+    public static native String describe(String label, Point p);
+
+    public native Point translatePoint(Point p, int dx, int dy);
+
+    public native Measurement scaleMeasurement(Measurement m, double factor);
+
+    public native String getOptionalString(String x);
+
+    public native Integer getOptionalInt(Integer x);
+
     public native String intToString(int x);
 
     public native String boolToString(boolean x);
@@ -57,12 +122,46 @@ public class User {
 
     public native String byteArrayToString(byte[] x);
 
+    public native int panicking(String message);
+
+    public native static int panickingUnchecked(String message);
+
     private native static void initNative();
 
     public native static String userCountStatus();
 
     public native String hashedPassword(int seed);
 
+    public native int passwordLength();
+
+    public native void changePassword(String password);
+
+    public native int appendToPassword(String suffix);
+
+    public native void changePasswordIfNumeric(String password);
+
+    public native void discardPasswordChange(String password);
+
+    public static native String describeParseCount(String s);
+
+    public static native int lookupCount(String key);
+
+    public static native int propagateParseCount(String s);
+
+    public static native int wrapParseCount(String s);
+
+    public static native int wrapLookupCount(String key);
+
+    public static native int parseCountOnThread(String s);
+
+    public static native boolean findClassOnThread(String name);
+
+    public static native int parsePort(String value);
+
+    public static native int checkedPort(String value);
+
+    public static native int configValue(String key);
+
     public User(String username, String password) {
         User.TOTAL_USERS_COUNT += 1;
 
@@ -74,6 +173,14 @@ public class User {
         return TOTAL_USERS_COUNT;
     }
 
+    public static int parseCount(String s) throws IOException {
+        if (s.isEmpty()) {
+            throw new IOException("empty count", new IllegalArgumentException("blank"));
+        }
+
+        return Integer.parseInt(s);
+    }
+
     public String getPassword() {
         return password;
     }
@@ -81,4 +188,20 @@ public class User {
     public String multipleParameters(int i, String s) {
         return s;
     }
+
+    public String nullableParameter(String s) {
+        return s;
+    }
+
+    public String joinStrings(List<String> v) {
+        return String.join(",", v);
+    }
+
+    public static class Settings {
+        public static native String normalize_key(String key);
+
+        public static String defaultPrefix() {
+            return "user.";
+        }
+    }
 }
@@ -0,0 +1,6 @@
+public enum UserStatus {
+    ACTIVE,
+    SUSPENDED,
+    DELETED,
+    LEGACY
+}
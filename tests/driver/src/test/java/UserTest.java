@@ -1,11 +1,27 @@
 import org.junit.jupiter.api.BeforeEach;
 import org.junit.jupiter.api.Test;
 
+import java.io.IOException;
+import java.nio.ByteBuffer;
+import java.nio.ReadOnlyBufferException;
+import java.util.LinkedList;
 import java.util.List;
+import java.util.Map;
+import java.util.NoSuchElementException;
+import java.util.Set;
+import java.util.SortedMap;
+import java.util.SortedSet;
 import java.util.function.Function;
+import java.util.stream.Collectors;
+import java.util.stream.IntStream;
 
 import static org.junit.jupiter.api.Assertions.assertEquals;
+import static org.junit.jupiter.api.Assertions.assertNull;
+import static org.junit.jupiter.api.Assertions.assertSame;
 import static org.junit.jupiter.api.Assertions.assertArrayEquals;
+import static org.junit.jupiter.api.Assertions.assertThrows;
+import static org.junit.jupiter.api.Assertions.assertFalse;
+import static org.junit.jupiter.api.Assertions.assertTrue;
 
 public class UserTest {
     private User u;
@@ -22,6 +38,29 @@ public class UserTest {
         assertEquals(expected, actual);
     }
 
+    @Test
+    public void writeBackTest() {
+        assertEquals(4, u.passwordLength());
+
+        u.changePassword("secret");
+        assertEquals("secret", u.getPassword());
+
+        assertEquals(9, u.appendToPassword("123"));
+        assertEquals("secret123", u.getPassword());
+
+        u.discardPasswordChange("lost");
+        assertEquals("secret123", u.getPassword());
+
+        // Failed calls are not written back
+        assertThrows(RuntimeException.class, () -> u.changePasswordIfNumeric("lost"));
+        assertEquals("secret123", u.getPassword());
+
+        // Unchanged fields are not set again
+        String password = u.getPassword();
+        u.changePassword(new String(password));
+        assertSame(password, u.getPassword());
+    }
+
     @Test
     public void intTest() {
         assertValueRoundTrip(u::getInt, u::intToString, 0, "0");
@@ -124,6 +163,14 @@ public class UserTest {
         assertValueRoundTrip(u::getString, Function.identity(), "️️𒅄", "️️𒅄"); // 4 bytes in utf-8
     }
 
+    @Test
+    public void optionalTest() {
+        assertEquals("hello!", u.getOptionalString("hello!"));
+        assertNull(u.getOptionalString(null));
+        assertEquals(42, u.getOptionalInt(42));
+        assertNull(u.getOptionalInt(null));
+    }
+
     @Test
     public void intArrayTest() {
         assertValueRoundTrip(u::getIntArray, u::intArrayToString, List.of(), "[]");
@@ -142,11 +189,289 @@ public class UserTest {
         assertArrayValueRoundTrip(u::getByteArray, u::byteArrayToString, new byte[] {1, 2, 3}, "[1, 2, 3]");
     }
 
+    @Test
+    public void primitiveArrayTest() {
+        assertArrayEquals(new boolean[] {true, false, true}, u.getPrimitiveBoolArray(new boolean[] {true, false, true}));
+        assertArrayEquals(new byte[] {-1, 0, 1}, u.getPrimitiveByteArray(new byte[] {-1, 0, 1}));
+        assertArrayEquals(new char[] {'a', '\u0142', '\uffff'}, u.getPrimitiveCharArray(new char[] {'a', '\u0142', '\uffff'}));
+        assertArrayEquals(new short[] {Short.MIN_VALUE, 0, Short.MAX_VALUE}, u.getPrimitiveShortArray(new short[] {Short.MIN_VALUE, 0, Short.MAX_VALUE}));
+        assertArrayEquals(new int[] {Integer.MIN_VALUE, 0, Integer.MAX_VALUE}, u.getPrimitiveIntArray(new int[] {Integer.MIN_VALUE, 0, Integer.MAX_VALUE}));
+        assertArrayEquals(new long[] {Long.MIN_VALUE, 0, Long.MAX_VALUE}, u.getPrimitiveLongArray(new long[] {Long.MIN_VALUE, 0, Long.MAX_VALUE}));
+        assertArrayEquals(new float[] {-1.5f, 0f, 3.25f}, u.getPrimitiveFloatArray(new float[] {-1.5f, 0f, 3.25f}));
+        assertArrayEquals(new double[] {-1.5, 0, 3.25}, u.getPrimitiveDoubleArray(new double[] {-1.5, 0, 3.25}));
+        assertArrayEquals(new int[0], u.getPrimitiveIntArray(new int[0]));
+    }
+
+    @Test
+    public void borrowedArrayTest() {
+        assertEquals(6.5f, u.sumFloatArray(new float[] {1f, 2.5f, 3f}));
+
+        int[] values = new int[] {1, 2, 3};
+        u.scaleIntArray(values, 2);
+        assertArrayEquals(new int[] {2, 4, 6}, values);
+
+        u.addIntArray(values, new int[] {1, 1, 1});
+        assertArrayEquals(new int[] {3, 5, 7}, values);
+        assertThrows(IllegalArgumentException.class, () -> u.addIntArray(values, values));
+
+        assertEquals(4.0, u.sumAsDoubleArray(new double[] {1.5, 2.5}));
+        RuntimeException e = assertThrows(RuntimeException.class, () -> u.sumAsDoubleArray(new int[] {1, 2}));
+        assertTrue(e.getMessage().contains("Invalid JValue type cast: [D"));
+    }
+
+    @Test
+    public void byteBufferTest() {
+        ByteBuffer buffer = ByteBuffer.allocateDirect(8);
+        buffer.put(new byte[] {1, 2, 3, 4, 5, 6, 7, 8});
+        buffer.position(2).limit(5);
+        assertEquals(3 + 4 + 5, u.byteBufferSum(buffer));
+
+        u.fillByteBuffer(buffer, (byte) 9);
+        buffer.clear();
+        byte[] content = new byte[8];
+        buffer.get(content);
+        assertArrayEquals(new byte[] {1, 2, 9, 9, 9, 6, 7, 8}, content);
+
+        ByteBuffer created = u.newByteBuffer(4, (byte) 7);
+        assertTrue(created.isDirect());
+        assertEquals(4, created.remaining());
+        assertEquals(28, u.byteBufferSum(created));
+
+        assertThrows(RuntimeException.class, () -> u.byteBufferSum(ByteBuffer.allocate(4)));
+
+        ByteBuffer readOnly = created.asReadOnlyBuffer();
+        assertEquals(28, u.byteBufferSum(readOnly));
+        assertThrows(ReadOnlyBufferException.class, () -> u.fillByteBuffer(readOnly, (byte) 1));
+        assertEquals(28, u.byteBufferSum(created));
+    }
+
+    @Test
+    public void mapTest() {
+        assertEquals(Map.of(), u.getMap(Map.of()));
+        assertEquals(Map.of("a", 1, "b", 2), u.getMap(Map.of("a", 1, "b", 2)));
+
+        SortedMap<Integer, String> sorted = u.getSortedMap(Map.of(3, "c", 1, "a", 2, "b"));
+        assertEquals(List.of(1, 2, 3), List.copyOf(sorted.keySet()));
+        assertEquals(List.of("a", "b", "c"), List.copyOf(sorted.values()));
+
+        // Local references to entries are deleted once converted
+        Map<String, Integer> large = IntStream.range(0, 100_000).boxed()
+                .collect(Collectors.toMap(Object::toString, Function.identity()));
+        assertEquals(large, u.getMap(large));
+    }
+
+    @Test
+    public void setTest() {
+        assertEquals(Set.of(), u.getSet(Set.of()));
+        assertEquals(Set.of("a", "b"), u.getSet(Set.of("a", "b")));
+
+        SortedSet<Long> sorted = u.getSortedSet(Set.of(3L, 1L, 2L));
+        assertEquals(List.of(1L, 2L, 3L), List.copyOf(sorted));
+
+        Set<String> large = IntStream.range(0, 100_000).mapToObj(Integer::toString).collect(Collectors.toSet());
+        assertEquals(large, u.getSet(large));
+    }
+
+    @Test
+    public void collectionTest() {
+        List<String> sorted = u.sortedStrings(Set.of("c", "a", "b"));
+        assertTrue(sorted instanceof LinkedList);
+        assertEquals(List.of("a", "b", "c"), sorted);
+
+        assertEquals(List.of(1, 2), u.getIntArray(new LinkedList<>(List.of(1, 2))));
+
+        // Elements of another class are a conversion error, not a panic
+        @SuppressWarnings({"rawtypes", "unchecked"})
+        List<Integer> strings = (List) List.of("a");
+        RuntimeException e = assertThrows(RuntimeException.class, () -> u.getIntArray(strings));
+        assertTrue(e.getMessage().contains("Invalid JValue type cast: java.lang.Integer"), e.getMessage());
+    }
+
+    @Test
+    public void enumTest() {
+        assertEquals(UserStatus.SUSPENDED, u.nextStatus(UserStatus.ACTIVE));
+        assertEquals(UserStatus.ACTIVE, u.nextStatus(UserStatus.DELETED));
+        assertEquals("Suspended", User.statusToString(UserStatus.SUSPENDED));
+
+        IllegalArgumentException e = assertThrows(IllegalArgumentException.class, () -> u.nextStatus(UserStatus.LEGACY));
+        assertEquals("unknown UserStatus constant: LEGACY", e.getMessage());
+    }
+
+    @Test
+    public void valueTypeTest() {
+        Point p = u.translatePoint(new Point(1, 2, "origin"), 10, 20);
+        assertEquals(11, p.x);
+        assertEquals(22, p.y);
+        assertEquals("origin (translated)", p.label);
+
+        Measurement m = u.scaleMeasurement(new Measurement("length", 1.5), 2);
+        assertEquals("length", m.name);
+        assertEquals(3.0, m.v);
+    }
+
+    @Test
+    public void overloadedNativesTest() {
+        assertEquals("int 42", User.describe(42));
+        assertEquals("status Suspended", User.describe(UserStatus.SUSPENDED));
+        assertEquals("origin (1, 2)", User.describe(null, new Point(1, 2, "origin")));
+        assertEquals("label (1, 2)", User.describe("label", new Point(1, 2, "origin")));
+    }
+
+    @Test
+    public void registeredNativesTest() {
+        Counter c = new Counter(3);
+        assertEquals(5, c.peek(2));
+        assertEquals(9, c.peek(2, 3));
+        assertEquals("total: 3", Counter.describe(3, "total"));
+        c.assertCount(3);
+        assertThrows(Error.class, () -> c.assertCount(4));
+    }
+
+    @Test
+    public void verifyTest() {
+        // Loading the library already failed if any of them couldn't be resolved
+        assertTrue(Counter.verifiedMembers() > 0);
+    }
+
+    @Test
+    public void nativeHandleTest() {
+        try (Accumulator a = Accumulator.create(); Accumulator b = Accumulator.create()) {
+            assertEquals(3, a.add(3));
+            assertEquals(7, a.add(4));
+            b.add(10);
+            assertEquals(17, a.merge(b));
+            assertEquals(10, b.total());
+            assertEquals(17, a.drain());
+        }
+    }
+
+    @Test
+    public void nativeHandleClosedTest() {
+        Accumulator a = Accumulator.create();
+        a.add(1);
+        a.close();
+        a.close();
+        assertThrows(IllegalStateException.class, a::total);
+        assertThrows(IllegalStateException.class, a::drain);
+        assertThrows(IllegalStateException.class, () -> Accumulator.create().merge(a));
+    }
+
+    @Test
+    public void nativeHandleCleanerTest() throws InterruptedException {
+        int dropped = Accumulator.droppedCount();
+        Accumulator.create().add(1);
+
+        for (int i = 0; i < 100 && Accumulator.droppedCount() == dropped; i++) {
+            System.gc();
+            Thread.sleep(10);
+        }
+        assertTrue(Accumulator.droppedCount() > dropped);
+    }
+
+    @Test
+    public void nativeHandleBorrowedTest() {
+        try (Accumulator a = Accumulator.create()) {
+            a.add(1);
+            assertThrows(IllegalStateException.class, () -> a.merge(a));
+            assertEquals(1, a.total());
+        }
+    }
+
+    @Test
+    public void nestedClassTest() {
+        assertEquals("user.display_name", User.Settings.normalize_key(" Display Name "));
+    }
+
+    @Test
+    public void javaExceptionCaptureTest() {
+        assertEquals("count: 3", User.describeParseCount("3"));
+        assertEquals("java.io.IOException: empty count (IOException: true, cause: java.lang.IllegalArgumentException: blank, traced: true)",
+                User.describeParseCount(""));
+        assertEquals("java.lang.NumberFormatException: For input string: \"x\" (IOException: false, cause: , traced: true)",
+                User.describeParseCount("x"));
+    }
+
+    @Test
+    public void intoJavaExceptionTest() {
+        assertEquals(7, User.lookupCount("7"));
+
+        NoSuchElementException missing = assertThrows(NoSuchElementException.class, () -> User.lookupCount("missing"));
+        assertEquals("no value for missing", missing.getMessage());
+
+        IllegalArgumentException blank = assertThrows(IllegalArgumentException.class, () -> User.lookupCount(""));
+        assertEquals("blank key", blank.getMessage());
+
+        RuntimeException failed = assertThrows(RuntimeException.class, () -> User.lookupCount("x"));
+        assertTrue(failed.getMessage().startsWith("lookup failed: "));
+        assertTrue(failed.getCause() instanceof NumberFormatException);
+    }
+
+    @Test
+    public void pendingExceptionTest() {
+        assertEquals(5, User.propagateParseCount("5"));
+        NumberFormatException propagated = assertThrows(NumberFormatException.class, () -> User.propagateParseCount("x"));
+        assertEquals("For input string: \"x\"", propagated.getMessage());
+
+        IllegalStateException wrapped = assertThrows(IllegalStateException.class, () -> User.wrapParseCount(""));
+        assertEquals("can't parse count", wrapped.getMessage());
+        assertTrue(wrapped.getCause() instanceof IOException);
+
+        // Exceptions thrown for returned errors are not wrapped
+        assertEquals(3, User.wrapLookupCount("3"));
+        IllegalArgumentException blank = assertThrows(IllegalArgumentException.class, () -> User.wrapLookupCount(""));
+        assertEquals("blank key", blank.getMessage());
+        RuntimeException failed = assertThrows(RuntimeException.class, () -> User.wrapLookupCount("x"));
+        assertFalse(failed instanceof IllegalStateException);
+        assertTrue(failed.getCause() instanceof NumberFormatException);
+    }
+
+    @Test
+    public void nativeThreadTest() {
+        assertEquals(12, User.parseCountOnThread("12"));
+        assertEquals(-1, User.parseCountOnThread(""));
+    }
+
+    @Test
+    public void classLoaderTest() {
+        assertTrue(User.findClassOnThread("User"));
+        assertTrue(User.findClassOnThread("User$Settings"));
+        assertFalse(User.findClassOnThread("NotAClass"));
+    }
+
+    @Test
+    public void displayErrorTest() {
+        assertEquals(8080, User.parsePort("8080"));
+        RuntimeException invalid = assertThrows(RuntimeException.class, () -> User.parsePort("http"));
+        assertEquals("invalid digit found in string", invalid.getMessage());
+
+        assertEquals(443, User.checkedPort("443"));
+        RuntimeException chained = assertThrows(RuntimeException.class, () -> User.checkedPort("x"));
+        assertEquals("invalid port `x`: invalid digit found in string", chained.getMessage());
+        RuntimeException range = assertThrows(RuntimeException.class, () -> User.checkedPort("-1"));
+        assertEquals("port -1 out of range", range.getMessage());
+
+        assertEquals(42, User.configValue("answer=42"));
+        RuntimeException missing = assertThrows(RuntimeException.class, () -> User.configValue("answer"));
+        assertEquals(RuntimeException.class, missing.getClass());
+        assertEquals("missing key `answer`", missing.getMessage());
+        RuntimeException invalidValue = assertThrows(RuntimeException.class, () -> User.configValue("answer=x"));
+        assertEquals("invalid value: invalid digit found in string", invalidValue.getMessage());
+    }
+
     @Test
     public void staticMethod() {
         assertEquals(String.valueOf(User.getTotalUsersCount()), User.userCountStatus());
     }
 
+    @Test
+    public void panicTest() {
+        Error e = assertThrows(Error.class, () -> u.panicking("boom"));
+        assertTrue(e.getMessage().contains("boom"));
+
+        IllegalStateException ise = assertThrows(IllegalStateException.class, () -> User.panickingUnchecked("bang"));
+        assertTrue(ise.getMessage().contains("bang"));
+    }
+
     private <T> void assertValueRoundTrip(Function<T, T> func, Function<T, String> toString, T value, String text) {
         assertEquals(value, func.apply(value));
         assertEquals(text, toString.apply(value));
@@ -23,7 +23,7 @@ fn java_integration_tests() {
             Path::new(".").join("tests").join("driver").join(
                 if cfg!(target_os = "windows") { "gradlew.bat" } else { "gradlew" })
         ).expect("Gradle not found"))
-        .args(&["test", "-i"])
+        .args(["test", "-i"])
         .current_dir(
             Path::new(".").join("tests").join("driver").to_str().expect("Failed to get driver path")
         )
@@ -42,7 +42,7 @@ fn vm_creation_and_object_usage() {
             Path::new(".").join("tests").join("driver").join(
                 if cfg!(target_os = "windows") { "gradlew.bat" } else { "gradlew" })
         ).expect("Gradle not found"))
-        .args(&["test", "-i"])
+        .args(["test", "-i"])
         .current_dir(
             Path::new(".").join("tests").join("driver").to_str().expect("Failed to get driver path")
         )
@@ -64,7 +64,7 @@ fn vm_creation_and_object_usage() {
             std::env::var("DYLD_FALLBACK_LIBRARY_PATH").unwrap_or("".to_string()),
         ));
     let vm_args = InitArgsBuilder::new()
-        .option(&*format!(
+        .option(&format!(
             "-Djava.class.path={}",
             classpath.to_string_lossy()
         ))
@@ -76,9 +76,8 @@ fn vm_creation_and_object_usage() {
     User::initNative();
 
     let count = User::getTotalUsersCount(&env)
-        .or_else(|e| {
+        .inspect_err(|_| {
             let _ = print_exception(&env);
-            Err(e)
         })
         .expect("can't get user count");
 
@@ -87,9 +86,8 @@ fn vm_creation_and_object_usage() {
     let u = User::new(&env, "user".into(), "password".into()).expect("can't create user instance");
 
     let count = User::getTotalUsersCount(&env)
-        .or_else(|e| {
+        .inspect_err(|_| {
             let _ = print_exception(&env);
-            Err(e)
         })
         .expect("can't get user count");
     assert_eq!(count, 1);
@@ -103,5 +101,21 @@ fn vm_creation_and_object_usage() {
         u.multipleParameters(&env, 10, "test".to_string())
             .expect("Can't test multipleParameters"),
         "test"
-    )
+    );
+
+    assert_eq!(
+        u.nullableParameter(&env, Some("test".to_string()))
+            .expect("Can't test nullableParameter"),
+        Some("test".to_string())
+    );
+    assert_eq!(
+        u.nullableParameter(&env, None)
+            .expect("Can't test nullableParameter"),
+        None
+    );
+    assert_eq!(
+        u.joinStrings(&env, vec!["a".to_string(), "b".to_string()].into())
+            .expect("Can't test joinStrings"),
+        "a,b"
+    );
 }